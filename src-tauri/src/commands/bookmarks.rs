@@ -1,9 +1,9 @@
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::PathBuf};
 
 use tauri::State;
 use uuid::Uuid;
 
-use crate::{models::Bookmark, AppState};
+use crate::{error::QuietError, models::{Bookmark, TagCount}, AppState};
 
 const BOOKMARKS_FILE: &str = "bookmarks.json";
 
@@ -16,33 +16,145 @@ fn read_all(state: &AppState) -> Vec<Bookmark> {
     } else { vec![] }
 }
 
-fn write_all(state: &AppState, list: &[Bookmark]) -> Result<(), String> {
-    fs::create_dir_all(&state.app_dir).map_err(|e| e.to_string())?;
+fn write_all(state: &AppState, list: &[Bookmark]) -> Result<(), QuietError> {
+    fs::create_dir_all(&state.app_dir)?;
     let p = path(state);
-    let bytes = serde_json::to_vec_pretty(list).map_err(|e| e.to_string())?;
-    fs::write(p, bytes).map_err(|e| e.to_string())
+    let bytes = serde_json::to_vec_pretty(list)?;
+    Ok(fs::write(p, bytes)?)
 }
 
 #[tauri::command]
-pub fn add_bookmark(path: String, page: Option<u32>, section: Option<String>, note: Option<String>, state: State<AppState>) -> Result<(), String> {
+pub fn add_bookmark(path: String, page: Option<u32>, section: Option<String>, note: Option<String>, tags: Option<Vec<String>>, state: State<AppState>) -> Result<(), QuietError> {
     let mut all = read_all(&state);
     let id = Uuid::new_v4().to_string();
     let created_at = time::OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap_or_else(|_| "".into());
-    all.push(Bookmark { id, path, page, section, note, created_at });
+    let tags = normalize_tags(tags.unwrap_or_default());
+    all.push(Bookmark { id, path, page, section, note, created_at, tags });
     write_all(&state, &all)
 }
 
 #[tauri::command]
-pub fn list_bookmarks(path: Option<String>, state: State<AppState>) -> Result<Vec<Bookmark>, String> {
+pub fn list_bookmarks(path: Option<String>, tag: Option<String>, state: State<AppState>) -> Result<Vec<Bookmark>, QuietError> {
     let mut all = read_all(&state);
     if let Some(p) = path { all.retain(|b| b.path == p); }
+    if let Some(t) = tag { all.retain(|b| b.tags.contains(&t)); }
     Ok(all)
 }
 
+// Distinct tags over all bookmarks with how many bookmarks carry each, sorted by tag.
 #[tauri::command]
-pub fn remove_bookmark(id: String, state: State<AppState>) -> Result<(), String> {
+pub fn list_all_tags(state: State<AppState>) -> Result<Vec<TagCount>, QuietError> {
+    Ok(tag_counts(&read_all(&state)))
+}
+
+fn tag_counts(all: &[Bookmark]) -> Vec<TagCount> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for t in all.iter().flat_map(|b| &b.tags) { *counts.entry(t).or_default() += 1; }
+    counts.into_iter().map(|(tag, count)| TagCount { tag: tag.to_string(), count }).collect()
+}
+
+// Trimmed, non-empty and without duplicates, in the order given.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for t in tags {
+        let t = t.trim();
+        if !t.is_empty() && !out.iter().any(|o| o == t) { out.push(t.to_string()); }
+    }
+    out
+}
+
+#[tauri::command]
+pub fn remove_bookmark(id: String, state: State<AppState>) -> Result<(), QuietError> {
     let mut all = read_all(&state);
     all.retain(|b| b.id != id);
     write_all(&state, &all)
 }
 
+// Case-insensitive substring search over note, section and path. Bookmarks whose note
+// matches come first, then section matches, then path-only matches.
+#[tauri::command]
+pub fn search_bookmarks(query: String, state: State<AppState>) -> Result<Vec<Bookmark>, QuietError> {
+    Ok(matching_bookmarks(read_all(&state), &query))
+}
+
+fn matching_bookmarks(all: Vec<Bookmark>, query: &str) -> Vec<Bookmark> {
+    let lq = query.trim().to_lowercase();
+    if lq.is_empty() { return vec![]; }
+    let contains = |s: Option<&str>| s.is_some_and(|s| s.to_lowercase().contains(&lq));
+    let mut ranked: Vec<(u8, Bookmark)> = all
+        .into_iter()
+        .filter_map(|b| {
+            let rank = if contains(b.note.as_deref()) { 0 }
+                else if contains(b.section.as_deref()) { 1 }
+                else if contains(Some(&b.path)) { 2 }
+                else { return None };
+            Some((rank, b))
+        })
+        .collect();
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked.into_iter().map(|(_, b)| b).collect()
+}
+
+// Change fields of an existing bookmark; arguments left as None keep their current value.
+// The id and creation time never change.
+#[tauri::command]
+pub fn update_bookmark(id: String, note: Option<String>, page: Option<u32>, section: Option<String>, state: State<AppState>) -> Result<(), QuietError> {
+    update(&state, &id, note, page, section)
+}
+
+fn update(state: &AppState, id: &str, note: Option<String>, page: Option<u32>, section: Option<String>) -> Result<(), QuietError> {
+    let mut all = read_all(state);
+    let b = all.iter_mut().find(|b| b.id == id).ok_or_else(|| QuietError::NotFound(format!("bookmark {}", id)))?;
+    if note.is_some() { b.note = note; }
+    if page.is_some() { b.page = page; }
+    if section.is_some() { b.section = section; }
+    write_all(state, &all)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_preserves_id_and_created_at() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = AppState::new(tmp.path().to_path_buf());
+        let original = Bookmark { id: "b1".into(), path: "/docs/a.pdf".into(), page: Some(3), section: None, note: Some("old".into()), created_at: "2024-01-01T00:00:00Z".into(), tags: vec![] };
+        write_all(&state, &[original]).unwrap();
+        update(&state, "b1", Some("new note".into()), None, Some("Intro".into())).unwrap();
+        let b = &read_all(&state)[0];
+        assert_eq!((b.id.as_str(), b.created_at.as_str()), ("b1", "2024-01-01T00:00:00Z"));
+        assert_eq!(b.note.as_deref(), Some("new note"));
+        assert_eq!(b.page, Some(3));
+        assert_eq!(b.section.as_deref(), Some("Intro"));
+        assert!(update(&state, "missing", None, None, None).is_err());
+    }
+
+    #[test]
+    fn test_bookmarks_without_tags_parse() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = AppState::new(tmp.path().to_path_buf());
+        let old = r#"[{"id":"b1","path":"/docs/a.pdf","page":2,"section":null,"note":null,"createdAt":"2024-01-01T00:00:00Z"}]"#;
+        fs::write(path(&state), old).unwrap();
+        let all = read_all(&state);
+        assert_eq!(all.len(), 1);
+        assert!(all[0].tags.is_empty());
+    }
+
+    #[test]
+    fn test_tag_counts() {
+        let mk = |id: &str, tags: &[&str]| Bookmark { id: id.into(), path: "/a".into(), page: None, section: None, note: None, created_at: String::new(), tags: tags.iter().map(|t| t.to_string()).collect() };
+        let all = vec![mk("1", &["work", "urgent"]), mk("2", &["work"]), mk("3", &[])];
+        let counts = tag_counts(&all);
+        assert_eq!(counts, vec![TagCount { tag: "urgent".into(), count: 1 }, TagCount { tag: "work".into(), count: 2 }]);
+        assert_eq!(normalize_tags(vec![" work ".into(), "work".into(), "".into()]), vec!["work".to_string()]);
+    }
+
+    #[test]
+    fn test_search_bookmarks_ranks_notes_first() {
+        let mk = |id: &str, path: &str, note: Option<&str>| Bookmark { id: id.into(), path: path.into(), page: None, section: None, note: note.map(String::from), created_at: String::new(), tags: vec![] };
+        let all = vec![mk("path", "/docs/Kernel.pdf", None), mk("note", "/docs/a.pdf", Some("see KERNEL tricks")), mk("none", "/docs/b.pdf", Some("other"))];
+        let ids: Vec<String> = matching_bookmarks(all, "kernel").into_iter().map(|b| b.id).collect();
+        assert_eq!(ids, vec!["note".to_string(), "path".to_string()]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use tauri::State;
+
+use crate::{
+    commands::{library, settings},
+    error::QuietError,
+    models::{DocumentText, OutlineEntry, PdfiumStatus},
+    util::{extract_pdf, extract_text, outline, pdfium_loader},
+    AppState,
+};
+
+// Hierarchical table of contents for a document: Markdown/HTML headings or PDF bookmarks.
+#[tauri::command]
+pub fn document_outline(path: String, state: State<AppState>) -> Result<Vec<OutlineEntry>, QuietError> {
+    let p = PathBuf::from(&path);
+    if !p.exists() { return Err(QuietError::NotFound(path)); }
+    outline::document_outline(&p, settings::read_settings(&state).text_limit(&p))
+}
+
+// Whether pdfium could be loaded, from where, and which directories were searched. Explains
+// poor PDF text, which comes from the lopdf fallback when pdfium is missing.
+#[tauri::command]
+pub fn pdfium_status() -> Result<PdfiumStatus, QuietError> {
+    Ok(pdfium_loader::pdfium_status())
+}
+
+// Password for an encrypted PDF, used by later extractions (reindex, search, outline) of that
+// path. Kept in memory for this session only; an empty password forgets it.
+#[tauri::command]
+pub fn set_pdf_password(path: String, password: String) -> Result<(), QuietError> {
+    extract_pdf::set_pdf_password(&PathBuf::from(&path), &password);
+    Ok(())
+}
+
+// Form feed between the pages of a PDF in `get_document_text`
+const PAGE_BREAK: &str = "\n\u{000C}\n";
+
+// The whole extracted text of a document, as the indexer reads it: a text file up to the
+// configured byte limit, or a PDF's pages up to the page limit joined by form feeds.
+// `truncated` tells when a limit left some out.
+#[tauri::command]
+pub fn get_document_text(path: String, state: State<AppState>) -> Result<DocumentText, QuietError> {
+    document_text(&state, Path::new(&path))
+}
+
+fn document_text(state: &AppState, path: &Path) -> Result<DocumentText, QuietError> {
+    if !path.exists() { return Err(QuietError::NotFound(path.to_string_lossy().to_string())); }
+    let settings = settings::read_settings(state);
+    if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf")) {
+        let doc = extract_pdf::extract_pdf_document_cached(path, &state.app_dir.join("cache"), settings.max_pdf_pages)?;
+        let truncated = doc.is_truncated();
+        let text = doc.pages.into_iter().map(|(_, text)| text).collect::<Vec<_>>().join(PAGE_BREAK);
+        return Ok(DocumentText { title: doc.title, text, truncated });
+    }
+    if !extract_text::is_text_file(path, &library::extra_text_exts(state)) {
+        return Err(QuietError::InvalidInput(format!("no text to export from {}", path.to_string_lossy())));
+    }
+    let limit = settings.text_limit(path);
+    let (title, text) = extract_text::extract_title_and_text(path, limit).map_err(QuietError::Extraction)?;
+    let truncated = extract_text::text_truncated(path, limit, text.len());
+    Ok(DocumentText { title, text, truncated })
+}
+
+// Text of one page (1-based) of a PDF, from the extraction cache when it's current.
+#[tauri::command]
+pub fn get_pdf_page_text(path: String, page: u32, state: State<AppState>) -> Result<String, QuietError> {
+    let p = Path::new(&path);
+    if !p.exists() { return Err(QuietError::NotFound(path)); }
+    if !p.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf")) { return Err(QuietError::InvalidInput(format!("not a PDF: {}", path))); }
+    extract_pdf::pdf_page_text_cached(p, &state.app_dir.join("cache"), page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_text_of_text_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = AppState::new(tmp.path().join("app"));
+        let path = tmp.path().join("notes.txt");
+        std::fs::write(&path, "Notes\nfirst line\nsecond line").unwrap();
+        let doc = document_text(&state, &path).unwrap();
+        assert_eq!((doc.title.as_str(), doc.text.as_str(), doc.truncated), ("Notes", "Notes first line second line", false));
+
+        let mut s = settings::read_settings(&state);
+        s.max_text_bytes = 10;
+        settings::write_settings(&state, &s).unwrap();
+        let doc = document_text(&state, &path).unwrap();
+        assert!(doc.truncated);
+        assert_eq!(doc.text, "Notes firs");
+
+        assert!(matches!(document_text(&state, &tmp.path().join("missing.txt")), Err(QuietError::NotFound(_))));
+        std::fs::write(tmp.path().join("image.png"), b"png").unwrap();
+        assert!(matches!(document_text(&state, &tmp.path().join("image.png")), Err(QuietError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,63 @@
+use tauri::State;
+
+use crate::{error::QuietError, models::HistoryEntry, util::recent_list::RecentList, AppState};
+
+// Older queries fall off the end
+const HISTORY: RecentList = RecentList { file: "search_history.json", cap: 100 };
+
+// Add `query` to the history. Repeating the latest query only refreshes its time.
+pub fn record(state: &AppState, query: &str) {
+    let query = query.trim();
+    if query.is_empty() { return; }
+    let searched_at = time::OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap_or_default();
+    HISTORY.update(&state.app_dir, |all| push_entry(all, HistoryEntry { query: query.to_string(), searched_at }));
+}
+
+fn push_entry(mut all: Vec<HistoryEntry>, entry: HistoryEntry) -> Vec<HistoryEntry> {
+    if all.first().is_some_and(|last| last.query == entry.query) { all.remove(0); }
+    HISTORY.push_front(all, entry)
+}
+
+// Recent queries, most recent first.
+#[tauri::command]
+pub fn get_search_history(state: State<AppState>) -> Result<Vec<HistoryEntry>, QuietError> {
+    Ok(HISTORY.read(&state.app_dir))
+}
+
+#[tauri::command]
+pub fn clear_search_history(state: State<AppState>) -> Result<(), QuietError> {
+    HISTORY.clear(&state.app_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(query: &str, at: &str) -> HistoryEntry { HistoryEntry { query: query.into(), searched_at: at.into() } }
+
+    #[test]
+    fn test_consecutive_repeats_collapse_and_history_is_capped() {
+        let mut all = vec![];
+        all = push_entry(all, entry("knuth", "1"));
+        all = push_entry(all, entry("knuth", "2"));
+        all = push_entry(all, entry("tantivy", "3"));
+        all = push_entry(all, entry("knuth", "4"));
+        let queries: Vec<&str> = all.iter().map(|e| e.query.as_str()).collect();
+        assert_eq!(queries, vec!["knuth", "tantivy", "knuth"]);
+        assert_eq!(all[2].searched_at, "2");
+        for i in 0..150 { all = push_entry(all, entry(&format!("q{}", i), "5")); }
+        assert_eq!(all.len(), HISTORY.cap);
+        assert_eq!(all[0].query, "q149");
+    }
+
+    #[test]
+    fn test_record_persists_and_skips_blank_queries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = AppState::new(tmp.path().join("app"));
+        record(&state, "  ");
+        record(&state, " quartz ");
+        let all: Vec<HistoryEntry> = HISTORY.read(&state.app_dir);
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].query, "quartz");
+    }
+}
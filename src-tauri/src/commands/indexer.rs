@@ -1,34 +1,69 @@
-use tauri::{State, async_runtime::spawn_blocking};
-use crate::{AppState, util::tantivy_index};
-use std::fs;
+use tauri::{AppHandle, Manager, State, async_runtime::spawn_blocking};
+use crate::{AppState, error::QuietError, models::{IndexPreview, IndexReport, IndexUpdateStats}, util::{extract_pdf, tantivy_index}};
+use std::{fs, path::Path};
 
 #[tauri::command]
-pub async fn reindex_all(state: State<'_, AppState>) -> Result<(), String> {
-    let state_clone = AppState { app_dir: state.app_dir.clone(), index: std::sync::Mutex::new(None), reader: std::sync::Mutex::new(None) };
-    spawn_blocking(move || tantivy_index::rebuild_index(&state_clone))
+pub async fn reindex_all(app: AppHandle) -> Result<(), QuietError> {
+    // The rebuild swaps the new index in on the app's own state, closing its cached handles
+    let handle = app.clone();
+    spawn_blocking(move || tantivy_index::rebuild_index_with_progress(&app.state::<AppState>(), &|p| { let _ = app.emit_all("reindex-progress", p); }))
         .await
-        .map_err(|e| format!("join error: {:?}", e))?
-        ;
-    // After rebuild, drop cached handles so next search opens new index
-    tantivy_index::drop_cached_index(&state);
+        .map_err(|e| QuietError::Other(format!("join error: {:?}", e)))??;
+    emit_unreachable(&handle);
     Ok(())
 }
 
+// Tell the UI which watched folders the last index run could not read, if any.
+pub(crate) fn emit_unreachable(app: &AppHandle) {
+    let Some(state) = app.try_state::<AppState>() else { return };
+    let unreachable = tantivy_index::load_report(&state).unreachable;
+    if !unreachable.is_empty() { let _ = app.emit_all("folders-unreachable", unreachable); }
+}
+
+// Re-extract only files whose fingerprint changed since the last update and drop deleted ones.
 #[tauri::command]
-pub async fn index_incremental(state: State<'_, AppState>) -> Result<(), String> {
-    let state_clone = AppState { app_dir: state.app_dir.clone(), index: std::sync::Mutex::new(None), reader: std::sync::Mutex::new(None) };
-    tauri::async_runtime::spawn_blocking(move || tantivy_index::incremental_update(&state_clone))
+pub async fn reindex_incremental(app: AppHandle) -> Result<IndexUpdateStats, QuietError> {
+    // Runs on the app's state so the cached writer is reused
+    let handle = app.clone();
+    let stats = spawn_blocking(move || tantivy_index::incremental_update(&app.state::<AppState>()))
         .await
-        .map_err(|e| format!("join error: {:?}", e))??;
-    tantivy_index::drop_cached_index(&state);
-    Ok(())
+        .map_err(|e| QuietError::Other(format!("join error: {:?}", e)))??;
+    emit_unreachable(&handle);
+    Ok(stats)
+}
+
+// Drop a single deleted or moved file from the index.
+#[tauri::command]
+pub fn remove_from_index(path: String, state: State<AppState>) -> Result<(), QuietError> {
+    tantivy_index::remove_path(&state, &path)
 }
 
 #[tauri::command]
-pub fn clear_extract_cache(state: State<AppState>) -> Result<(), String> {
+pub fn clear_extract_cache(state: State<AppState>) -> Result<(), QuietError> {
     let sys_tmp = std::env::temp_dir().join("quietlibrary-cache");
-    if sys_tmp.exists() { fs::remove_dir_all(&sys_tmp).map_err(|e| e.to_string())?; }
+    if sys_tmp.exists() { fs::remove_dir_all(&sys_tmp)?; }
     let app_cache = state.app_dir.join("cache");
-    if app_cache.exists() { fs::remove_dir_all(&app_cache).map_err(|e| e.to_string())?; }
+    if app_cache.exists() { fs::remove_dir_all(&app_cache)?; }
     Ok(())
 }
+
+// Drop the cached extraction of one file so its next index or search extracts it again,
+// leaving every other document's cache in place. Returns how many cache entries were removed.
+#[tauri::command]
+pub fn clear_extract_cache_for(path: String, state: State<AppState>) -> Result<usize, QuietError> {
+    Ok(extract_pdf::remove_cached_document(Path::new(&path), &state.app_dir.join("cache"))?)
+}
+
+// Counts of the files a reindex would read, by extension, without extracting or indexing.
+#[tauri::command]
+pub async fn preview_index(app: AppHandle) -> Result<IndexPreview, QuietError> {
+    spawn_blocking(move || tantivy_index::preview_index(&app.state::<AppState>()))
+        .await
+        .map_err(|e| QuietError::Other(format!("join error: {:?}", e)))?
+}
+
+// Files the last index run could not read or extract (e.g. offline cloud placeholders).
+#[tauri::command]
+pub fn get_index_report(state: State<AppState>) -> Result<IndexReport, QuietError> {
+    Ok(tantivy_index::load_report(&state))
+}
@@ -1,15 +1,39 @@
-use std::{fs, path::PathBuf};
+use std::{collections::HashSet, fs, path::{Component, Path, PathBuf}};
 
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use serde::{Deserialize, Serialize};
 use tauri::State;
 
-use crate::AppState;
+use crate::{error::QuietError, models::WatchedFolder, util::{tantivy_index, watcher::FolderWatcher}, AppState};
 
 const LIBRARY_FILE: &str = "library.json";
 
-#[derive(Default, Serialize, Deserialize)]
+// Gitignore-style globs, one per line, excluding paths relative to the directory holding it
+pub(crate) const IGNORE_FILE: &str = ".quietignore";
+
+#[derive(Serialize, Deserialize)]
 pub(crate) struct LibraryData {
     folders: Vec<String>,
+    // Gitignore-style globs matched against paths relative to each watched folder
+    #[serde(default)]
+    excludes: Vec<String>,
+    // Deepest directory level below a watched folder that is scanned; None = unlimited
+    #[serde(default)]
+    max_depth: Option<u32>,
+    // Whether symlinked files and directories are indexed; cycles are detected either way
+    #[serde(default = "default_follow_symlinks")]
+    follow_symlinks: bool,
+    // Extensions indexed as plain text besides txt/md/html/docx/odt/rtf, e.g. "rs", "py", "csv"
+    #[serde(default)]
+    extra_text_exts: Vec<String>,
+}
+
+fn default_follow_symlinks() -> bool { true }
+
+impl Default for LibraryData {
+    fn default() -> Self {
+        LibraryData { folders: Vec::new(), excludes: Vec::new(), max_depth: None, follow_symlinks: true, extra_text_exts: Vec::new() }
+    }
 }
 
 fn lib_path(state: &AppState) -> PathBuf {
@@ -25,27 +49,120 @@ pub(crate) fn read_library(state: &AppState) -> LibraryData {
     }
 }
 
-fn write_library(state: &AppState, data: &LibraryData) -> Result<(), String> {
+fn write_library(state: &AppState, data: &LibraryData) -> Result<(), QuietError> {
     let p = lib_path(state);
-    fs::create_dir_all(&state.app_dir).map_err(|e| e.to_string())?;
-    let bytes = serde_json::to_vec_pretty(data).map_err(|e| e.to_string())?;
-    fs::write(p, bytes).map_err(|e| e.to_string())
+    fs::create_dir_all(&state.app_dir)?;
+    let bytes = serde_json::to_vec_pretty(data)?;
+    Ok(fs::write(p, bytes)?)
 }
 
+// Like `add_watched_folders`, a folder inside (or containing) a watched one is not added; the
+// returned warnings say which folders it overlaps. Adding a watched folder again does nothing.
 #[tauri::command]
-pub fn add_watched_folder(path: String, state: State<AppState>) -> Result<(), String> {
+pub fn add_watched_folder(path: String, state: State<AppState>, watcher: State<FolderWatcher>) -> Result<Vec<String>, QuietError> {
+    let path = normalize_folder(&path);
     let mut data = read_library(&state);
-    if !data.folders.iter().any(|p| p == &path) {
-        data.folders.push(path);
-        write_library(&state, &data)?;
+    let existing: Vec<String> = data.folders.iter().map(|f| normalize_folder(f)).collect();
+    if existing.contains(&path) { return Ok(vec![]); }
+    let conflicts = folder_conflicts(&existing, &path);
+    if !conflicts.is_empty() { return Ok(conflicts); }
+    watcher.watch(&path);
+    data.folders.push(path);
+    write_library(&state, &data)?;
+    Ok(vec![])
+}
+
+// Problems with the library configuration, one message each; empty when all is well.
+#[tauri::command]
+pub fn validate_library(state: State<AppState>) -> Result<Vec<String>, QuietError> {
+    Ok(overlap_warnings(&watched_folders(&state)))
+}
+
+// Pairs of watched folders where one is inside the other, or both are the same directory
+// reached through different paths (symlinks, `..`). Paths are canonicalized when they exist.
+// Indexing reads each file once regardless, filed under the deepest folder, but removing or
+// restricting a search to the outer folder then behaves unexpectedly.
+pub(crate) fn overlap_warnings(folders: &[String]) -> Vec<String> {
+    let resolved: Vec<PathBuf> = folders.iter().map(|f| fs::canonicalize(f).unwrap_or_else(|_| PathBuf::from(f))).collect();
+    let mut warnings = Vec::new();
+    for (i, a) in resolved.iter().enumerate() {
+        for (j, b) in resolved.iter().enumerate().skip(i + 1) {
+            let (fa, fb) = (&folders[i], &folders[j]);
+            if a == b { warnings.push(format!("{} and {} are the same folder", fa, fb)); }
+            else if b.starts_with(a) { warnings.push(format!("{} is inside watched folder {}", fb, fa)); }
+            else if a.starts_with(b) { warnings.push(format!("{} is inside watched folder {}", fa, fb)); }
+        }
     }
-    Ok(())
+    warnings
 }
 
+// Adds several folders with one library write, returning those actually added. Paths already
+// watched, repeated in `paths`, or nested inside (or containing) a watched folder or one
+// added earlier in the batch are skipped, so no file is indexed under two roots.
 #[tauri::command]
-pub fn list_watched_folders(state: State<AppState>) -> Result<Vec<String>, String> {
-    let data = read_library(&state);
-    Ok(data.folders)
+pub fn add_watched_folders(paths: Vec<String>, state: State<AppState>, watcher: State<FolderWatcher>) -> Result<Vec<String>, QuietError> {
+    let mut data = read_library(&state);
+    let existing: Vec<String> = data.folders.iter().map(|f| normalize_folder(f)).collect();
+    let added = new_folders(&existing, paths.iter().map(|p| normalize_folder(p)).collect());
+    if added.is_empty() { return Ok(added); }
+    for path in &added { watcher.watch(path); }
+    data.folders.extend(added.iter().cloned());
+    write_library(&state, &data)?;
+    Ok(added)
+}
+
+// The form a watched folder is stored and compared in: canonical (symlinks and `..` resolved,
+// no trailing separator; without the `\\?\` prefix on Windows) when the folder exists, else
+// cleaned lexically so an offline folder can still be matched and removed.
+pub(crate) fn normalize_folder(path: &str) -> String {
+    if let Ok(real) = dunce::canonicalize(path) { return real.to_string_lossy().to_string(); }
+    let mut clean = PathBuf::new();
+    for c in Path::new(path).components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => { clean.pop(); }
+            c => clean.push(c),
+        }
+    }
+    clean.to_string_lossy().to_string()
+}
+
+// Whether one folder is inside the other (or they are equal), component-wise.
+fn folders_overlap(a: &str, b: &str) -> bool {
+    Path::new(a).starts_with(b) || Path::new(b).starts_with(a)
+}
+
+// The `candidates` that neither equal nor overlap (one inside the other) any folder in
+// `existing` or an earlier accepted candidate. Comparison is component-wise.
+fn new_folders(existing: &[String], candidates: Vec<String>) -> Vec<String> {
+    let mut added: Vec<String> = Vec::new();
+    for path in candidates {
+        if existing.iter().chain(added.iter()).any(|f| folders_overlap(f, &path)) { continue; }
+        added.push(path);
+    }
+    added
+}
+
+// Why `path` can't join the `existing` folders: one message per folder it is inside or
+// contains. Both sides are in `normalize_folder` form.
+fn folder_conflicts(existing: &[String], path: &str) -> Vec<String> {
+    existing
+        .iter()
+        .filter(|f| folders_overlap(f, path))
+        .map(|f| if Path::new(path).starts_with(f) { format!("{} is inside watched folder {}", path, f) } else { format!("{} contains watched folder {}", path, f) })
+        .collect()
+}
+
+#[tauri::command]
+pub fn list_watched_folders(state: State<AppState>) -> Result<Vec<WatchedFolder>, QuietError> {
+    Ok(read_library(&state).folders.into_iter().map(|path| folder_status(&state, path)).collect())
+}
+
+fn folder_status(state: &AppState, path: String) -> WatchedFolder {
+    let exists = Path::new(&path).is_dir();
+    let readable = fs::read_dir(&path).is_ok();
+    let file_count = tantivy_index::indexed_file_count(state, &path);
+    WatchedFolder { path, exists, readable, file_count }
 }
 
 // Internal helper for other commands (non-IPC) to access folders without exposing serde types
@@ -53,9 +170,303 @@ pub(crate) fn watched_folders(state: &AppState) -> Vec<String> {
     read_library(state).folders
 }
 
+pub(crate) fn extra_text_exts(state: &AppState) -> Vec<String> {
+    read_library(state).extra_text_exts
+}
+
+// The watched folder a file belongs to. With nested watched folders the deepest one wins.
+pub(crate) fn owning_folder(path: &Path, folders: &[String]) -> Option<String> {
+    folders
+        .iter()
+        .filter(|f| path.starts_with(f))
+        .max_by_key(|f| Path::new(f).components().count())
+        .cloned()
+}
+
+// Exclude patterns bound to one watched folder, so globs see paths relative to it.
+pub(crate) struct ExcludeMatcher {
+    root: PathBuf,
+    set: GlobSet,
+}
+
+impl ExcludeMatcher {
+    pub(crate) fn new(root: &Path, patterns: &[String]) -> Result<Self, String> {
+        let mut builder = GlobSetBuilder::new();
+        for p in patterns {
+            builder.add(Glob::new(p).map_err(|e| e.to_string())?);
+        }
+        Ok(ExcludeMatcher { root: root.to_path_buf(), set: builder.build().map_err(|e| e.to_string())? })
+    }
+
+    // Matcher for `root` using the library's patterns; invalid stored patterns are skipped.
+    pub(crate) fn for_folder(state: &AppState, root: &Path) -> Self {
+        let patterns: Vec<String> = read_library(state).excludes.into_iter().filter(|p| Glob::new(p).is_ok()).collect();
+        Self::new(root, &patterns).unwrap_or_else(|_| ExcludeMatcher { root: root.to_path_buf(), set: GlobSet::empty() })
+    }
+
+    // Patterns of the `IGNORE_FILE` in `dir`, if there is one. Blank lines, `#` comments and
+    // invalid patterns are skipped.
+    fn from_ignore_file(dir: &Path) -> Option<Self> {
+        let text = fs::read_to_string(dir.join(IGNORE_FILE)).ok()?;
+        let patterns: Vec<String> = text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#') && Glob::new(l).is_ok())
+            .map(String::from)
+            .collect();
+        Self::new(dir, &patterns).ok()
+    }
+
+    // Directories also match as "dir/", so `**/node_modules/**` prunes the whole subtree.
+    pub(crate) fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let rel = match path.strip_prefix(&self.root) { Ok(r) => r, Err(_) => return false };
+        if self.set.is_match(rel) { return true; }
+        is_dir && self.set.is_match(format!("{}/", rel.to_string_lossy()))
+    }
+}
+
+// Traversal state for one watched folder: exclude patterns, the depth limit, symlink handling,
+// the directories already visited and how many directories the depth limit cut off.
+pub(crate) struct FolderWalk {
+    root: PathBuf,
+    excludes: ExcludeMatcher,
+    // `IGNORE_FILE`s found in the directories entered so far; each applies below its directory,
+    // so a file is checked against every one between it and the root
+    ignore_files: Vec<ExcludeMatcher>,
+    max_depth: Option<u32>,
+    follow_symlinks: bool,
+    visited: HashSet<PathBuf>,
+    skipped_dirs: usize,
+}
+
+impl FolderWalk {
+    pub(crate) fn new(root: &Path, excludes: ExcludeMatcher, max_depth: Option<u32>, follow_symlinks: bool) -> Self {
+        let visited = fs::canonicalize(root).into_iter().collect();
+        let ignore_files = ExcludeMatcher::from_ignore_file(root).into_iter().collect();
+        FolderWalk { root: root.to_path_buf(), excludes, ignore_files, max_depth, follow_symlinks, visited, skipped_dirs: 0 }
+    }
+
+    pub(crate) fn for_folder(state: &AppState, root: &Path) -> Self {
+        let data = read_library(state);
+        Self::new(root, ExcludeMatcher::for_folder(state, root), data.max_depth, data.follow_symlinks)
+    }
+
+    // Excluded by the library's or an ignore file's patterns, or a symlink while symlinks are
+    // not followed.
+    pub(crate) fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if !self.follow_symlinks && fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) { return true; }
+        self.excludes.is_excluded(path, is_dir) || self.ignore_files.iter().any(|m| m.is_excluded(path, is_dir))
+    }
+
+    // Whether to descend into `dir`. Files directly in the watched folder are at depth 0, so
+    // with max_depth = 1 one level of subdirectories is scanned. A directory reached again
+    // (through a symlink back up the tree, or to a sibling) is walked only once.
+    pub(crate) fn enter_dir(&mut self, dir: &Path) -> bool {
+        let depth = dir.strip_prefix(&self.root).map(|r| r.components().count()).unwrap_or(0);
+        if self.max_depth.is_some_and(|max| depth as u32 > max) {
+            self.skipped_dirs += 1;
+            return false;
+        }
+        let entered = match fs::canonicalize(dir) {
+            Ok(real) => self.visited.insert(real),
+            Err(_) => false,
+        };
+        if entered { self.ignore_files.extend(ExcludeMatcher::from_ignore_file(dir)); }
+        entered
+    }
+
+    pub(crate) fn log_skipped(&self) {
+        if self.skipped_dirs > 0 {
+            eprintln!("quietlibrary: folder={} skipped_dirs={} (max depth {})", self.root.to_string_lossy(), self.skipped_dirs, self.max_depth.unwrap_or(0));
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_max_depth(state: State<AppState>) -> Result<Option<u32>, QuietError> {
+    Ok(read_library(&state).max_depth)
+}
+
 #[tauri::command]
-pub fn remove_watched_folder(path: String, state: State<AppState>) -> Result<(), String> {
+pub fn set_max_depth(depth: Option<u32>, state: State<AppState>) -> Result<(), QuietError> {
     let mut data = read_library(&state);
-    data.folders.retain(|p| p != &path);
+    data.max_depth = depth;
     write_library(&state, &data)
 }
+
+#[tauri::command]
+pub fn get_follow_symlinks(state: State<AppState>) -> Result<bool, QuietError> {
+    Ok(read_library(&state).follow_symlinks)
+}
+
+#[tauri::command]
+pub fn set_follow_symlinks(follow: bool, state: State<AppState>) -> Result<(), QuietError> {
+    let mut data = read_library(&state);
+    data.follow_symlinks = follow;
+    write_library(&state, &data)
+}
+
+#[tauri::command]
+pub fn get_extra_text_exts(state: State<AppState>) -> Result<Vec<String>, QuietError> {
+    Ok(read_library(&state).extra_text_exts)
+}
+
+// Replaces the list. Entries are stored lowercase without a leading dot; takes effect on the
+// next reindex.
+#[tauri::command]
+pub fn set_extra_text_exts(exts: Vec<String>, state: State<AppState>) -> Result<(), QuietError> {
+    let mut data = read_library(&state);
+    data.extra_text_exts.clear();
+    for e in exts {
+        let e = e.trim().trim_start_matches('.').to_ascii_lowercase();
+        if !e.is_empty() && !data.extra_text_exts.contains(&e) { data.extra_text_exts.push(e); }
+    }
+    write_library(&state, &data)
+}
+
+#[tauri::command]
+pub fn add_exclude_pattern(pattern: String, state: State<AppState>) -> Result<(), QuietError> {
+    Glob::new(&pattern).map_err(|e| QuietError::InvalidInput(e.to_string()))?;
+    let mut data = read_library(&state);
+    if !data.excludes.iter().any(|p| p == &pattern) {
+        data.excludes.push(pattern);
+        write_library(&state, &data)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_exclude_patterns(state: State<AppState>) -> Result<Vec<String>, QuietError> {
+    Ok(read_library(&state).excludes)
+}
+
+#[tauri::command]
+pub fn remove_exclude_pattern(pattern: String, state: State<AppState>) -> Result<(), QuietError> {
+    let mut data = read_library(&state);
+    data.excludes.retain(|p| p != &pattern);
+    write_library(&state, &data)
+}
+
+#[tauri::command]
+pub fn remove_watched_folder(path: String, state: State<AppState>, watcher: State<FolderWatcher>) -> Result<(), QuietError> {
+    let path = normalize_folder(&path);
+    let mut data = read_library(&state);
+    // Stored entries may predate normalization, so they are normalized too
+    data.folders.retain(|p| {
+        if normalize_folder(p) != path { return true; }
+        watcher.unwatch(p);
+        false
+    });
+    write_library(&state, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owning_folder_prefers_deepest_root() {
+        let folders = vec!["/lib".to_string(), "/lib/papers".to_string(), "/other".to_string()];
+        assert_eq!(owning_folder(Path::new("/lib/papers/a.pdf"), &folders).as_deref(), Some("/lib/papers"));
+        assert_eq!(owning_folder(Path::new("/lib/notes/b.md"), &folders).as_deref(), Some("/lib"));
+        // Component-wise, so /lib/papers2 is not under /lib/papers
+        assert_eq!(owning_folder(Path::new("/lib/papers2/c.txt"), &folders).as_deref(), Some("/lib"));
+        assert_eq!(owning_folder(Path::new("/elsewhere/d.txt"), &folders), None);
+    }
+
+    #[test]
+    fn test_new_folders_skip_nested_and_duplicates() {
+        let existing = vec!["/lib".to_string(), "/home/me/docs/papers".to_string()];
+        let candidates = ["/lib/notes", "/home/me/docs", "/other", "/other", "/other/sub", "/lib2", "/home/me/music"].map(String::from).to_vec();
+        assert_eq!(new_folders(&existing, candidates), vec!["/other", "/lib2", "/home/me/music"]);
+        assert!(new_folders(&existing, vec!["/lib".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_folder_conflicts_name_overlapping_folders() {
+        let existing = vec!["/lib".to_string(), "/home/me/docs/papers".to_string(), "/home/me/docs/notes".to_string()];
+        assert_eq!(folder_conflicts(&existing, "/lib/notes"), vec!["/lib/notes is inside watched folder /lib"]);
+        assert_eq!(folder_conflicts(&existing, "/home/me/docs"), vec!["/home/me/docs contains watched folder /home/me/docs/papers", "/home/me/docs contains watched folder /home/me/docs/notes"]);
+        // Component-wise, not by substring
+        assert!(folder_conflicts(&existing, "/li").is_empty());
+        assert!(folder_conflicts(&existing, "/lib2").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_normalize_folder_equivalent_forms() {
+        let tmp = tempfile::tempdir().unwrap();
+        let docs = tmp.path().join("docs");
+        fs::create_dir_all(docs.join("sub")).unwrap();
+        let canonical = normalize_folder(&docs.to_string_lossy());
+        assert_eq!(canonical, fs::canonicalize(&docs).unwrap().to_string_lossy());
+        let s = |p: PathBuf| p.to_string_lossy().to_string();
+        assert_eq!(normalize_folder(&format!("{}/", s(docs.clone()))), canonical);
+        assert_eq!(normalize_folder(&s(docs.join("sub").join("..").join("."))), canonical);
+        // Relative paths resolve against the working directory
+        let up = "../".repeat(std::env::current_dir().unwrap().components().count() - 1);
+        assert_eq!(normalize_folder(&format!("{}{}", up, s(docs.strip_prefix("/").unwrap().to_path_buf()))), canonical);
+        // Folders that do not exist (e.g. an unplugged drive) are cleaned lexically
+        assert_eq!(normalize_folder("/media/usb/books/./old/../"), "/media/usb/books");
+        assert_eq!(new_folders(std::slice::from_ref(&canonical), vec![normalize_folder(&format!("{}/", canonical))]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_overlap_warnings_for_nested_folders() {
+        let tmp = tempfile::tempdir().unwrap();
+        let docs = tmp.path().join("docs");
+        fs::create_dir_all(docs.join("papers")).unwrap();
+        fs::create_dir_all(tmp.path().join("music")).unwrap();
+        let s = |p: PathBuf| p.to_string_lossy().to_string();
+        let folders = vec![s(docs.join("papers")), s(tmp.path().join("music")), s(docs.clone()), s(docs.join("papers").join(".."))];
+        let warnings = overlap_warnings(&folders);
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert_eq!(warnings[0], format!("{} is inside watched folder {}", folders[0], folders[2]));
+        assert!(warnings.contains(&format!("{} and {} are the same folder", folders[2], folders[3])));
+        assert!(overlap_warnings(&folders[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_exclude_patterns_match_relative_paths() {
+        let patterns = vec!["**/node_modules/**".to_string(), "*.tmp".to_string(), ".git/**".to_string()];
+        let m = ExcludeMatcher::new(Path::new("/lib"), &patterns).unwrap();
+        assert!(m.is_excluded(Path::new("/lib/node_modules"), true));
+        assert!(m.is_excluded(Path::new("/lib/app/node_modules"), true));
+        assert!(m.is_excluded(Path::new("/lib/app/node_modules/pkg/readme.md"), false));
+        assert!(m.is_excluded(Path::new("/lib/notes/draft.tmp"), false));
+        assert!(m.is_excluded(Path::new("/lib/.git"), true));
+        assert!(!m.is_excluded(Path::new("/lib/notes/draft.md"), false));
+        assert!(!m.is_excluded(Path::new("/lib/node_modules.md"), false));
+        // Patterns are relative to the watched folder, not the filesystem root
+        assert!(!ExcludeMatcher::new(Path::new("/lib"), &["lib/**".to_string()]).unwrap().is_excluded(Path::new("/lib/a.txt"), false));
+    }
+
+    #[test]
+    fn test_folder_walk_depth_limit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::create_dir_all(root.join("a/c")).unwrap();
+        let mut walk = FolderWalk::new(root, ExcludeMatcher::new(root, &[]).unwrap(), Some(1), true);
+        assert!(walk.enter_dir(&root.join("a")));
+        assert!(!walk.enter_dir(&root.join("a/b")));
+        assert!(!walk.enter_dir(&root.join("a/c")));
+        assert_eq!(walk.skipped_dirs, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_folder_walk_symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        std::os::unix::fs::symlink(root, root.join("sub/loop")).unwrap();
+        let mut walk = FolderWalk::new(root, ExcludeMatcher::new(root, &[]).unwrap(), None, true);
+        assert!(walk.enter_dir(&root.join("sub")));
+        // Points back at the root, which was visited first
+        assert!(!walk.enter_dir(&root.join("sub/loop")));
+        let strict = FolderWalk::new(root, ExcludeMatcher::new(root, &[]).unwrap(), None, false);
+        assert!(strict.is_excluded(&root.join("sub/loop"), true));
+        assert!(!strict.is_excluded(&root.join("sub"), true));
+    }
+}
@@ -3,4 +3,42 @@ pub mod indexer;
 pub mod search;
 pub mod open;
 pub mod bookmarks;
+pub mod document;
+pub mod settings;
+pub mod history;
+pub mod recent;
 
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    // Every `#[tauri::command]` under commands/ must appear in main.rs's generate_handler! list,
+    // otherwise the frontend gets "command not found" at runtime.
+    #[test]
+    fn test_all_commands_registered() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let main = fs::read_to_string(src.join("main.rs")).unwrap();
+        let mut missing = Vec::new();
+        for entry in fs::read_dir(src.join("commands")).unwrap() {
+            let path = entry.unwrap().path();
+            let module = path.file_stem().unwrap().to_string_lossy().to_string();
+            if module == "mod" { continue; }
+            let code = fs::read_to_string(&path).unwrap();
+            let mut lines = code.lines();
+            while let Some(line) = lines.next() {
+                if line.trim() != "#[tauri::command]" { continue; }
+                let sig = lines.next().unwrap_or("").trim();
+                let name = sig
+                    .trim_start_matches("pub ")
+                    .trim_start_matches("async ")
+                    .trim_start_matches("fn ")
+                    .split(['(', '<'])
+                    .next()
+                    .unwrap_or("");
+                let registered = format!("commands::{}::{},", module, name);
+                if !main.contains(&registered) { missing.push(registered); }
+            }
+        }
+        assert!(missing.is_empty(), "commands missing from generate_handler!: {:?}", missing);
+    }
+}
@@ -1,25 +1,180 @@
+use std::{path::Path, process::Command};
+
+use tauri::State;
+
+use crate::{commands::recent, error::QuietError, models::OpenTarget, util::extract_zip::archive_of, AppState};
+
+// Show `path` in the platform file manager (the archive for a member of one). Returns an error when no file manager could be run.
 #[tauri::command]
-pub fn reveal_in_os(_window: tauri::Window, path: String) -> Result<(), String> {
+pub fn reveal_in_os(_window: tauri::Window, path: String) -> Result<(), QuietError> {
+    reveal(on_disk(&path))
+}
+
+// The file holding `path`: for a member of an archive (`bundle.zip!/plan.md`), the archive.
+fn on_disk(path: &str) -> &str {
+    archive_of(path).unwrap_or(path)
+}
+
+fn reveal(path: &str) -> Result<(), QuietError> {
+    #[cfg(target_os = "windows")]
+    {
+        let p = std::path::Path::new(path);
+        let arg = if p.exists() { format!("/select,{}", path) } else { path.to_string() };
+        // explorer's exit status is unreliable (non-zero even on success), so only a failed spawn counts
+        Command::new("explorer").arg(arg).status().map_err(|e| QuietError::Io(format!("could not run explorer: {}", e)))?;
+        Ok(())
+    }
+    #[cfg(target_os = "macos")]
+    {
+        match Command::new("open").arg("-R").arg(path).status() {
+            Ok(s) if s.success() => Ok(()),
+            Ok(s) => Err(QuietError::Io(format!("open -R failed with {}", s))),
+            Err(e) => Err(QuietError::Io(format!("could not run open: {}", e))),
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let preferred = std::env::var(FILE_MANAGER_ENV).ok();
+        for cmd in linux_reveal_commands(Path::new(path), preferred.as_deref()) {
+            if Command::new(&cmd[0]).args(&cmd[1..]).status().is_ok_and(|s| s.success()) { return Ok(()); }
+        }
+        Err(QuietError::Io("no file manager could be opened; set QUIETLIBRARY_FILE_MANAGER (e.g. \"nautilus --select\")".into()))
+    }
+}
+
+// Command (with arguments) used before the built-in list, e.g. "thunar" or "dolphin --select";
+// the file path is appended.
+#[cfg(target_os = "linux")]
+const FILE_MANAGER_ENV: &str = "QUIETLIBRARY_FILE_MANAGER";
+
+// Invocations tried in order: the configured one, file managers that can select the file,
+// then `xdg-open` on the parent folder.
+#[cfg(target_os = "linux")]
+fn linux_reveal_commands(path: &Path, preferred: Option<&str>) -> Vec<Vec<String>> {
+    let file = path.to_string_lossy().to_string();
+    let mut cmds: Vec<Vec<String>> = Vec::new();
+    if let Some(pref) = preferred {
+        let mut cmd: Vec<String> = pref.split_whitespace().map(String::from).collect();
+        if !cmd.is_empty() {
+            cmd.push(file.clone());
+            cmds.push(cmd);
+        }
+    }
+    for fm in [&["nautilus", "--select"][..], &["dolphin", "--select"][..], &["nemo"][..]] {
+        let mut cmd: Vec<String> = fm.iter().map(|s| s.to_string()).collect();
+        cmd.push(file.clone());
+        cmds.push(cmd);
+    }
+    let folder = path.parent().unwrap_or(Path::new("/"));
+    cmds.push(vec!["xdg-open".into(), folder.to_string_lossy().to_string()]);
+    cmds
+}
+
+// Open a document in the system's default application, at `page` where the viewer supports
+// it: SumatraPDF's `-page` on Windows, a `#page=` fragment on macOS. Other viewers open the
+// file at its start; a member of an archive opens the archive. Records the open in the recent
+// documents and returns the resolved target.
+#[tauri::command]
+pub fn open_document(path: String, page: Option<u32>, state: State<AppState>) -> Result<OpenTarget, QuietError> {
+    let file = on_disk(&path);
+    if !Path::new(file).exists() { return Err(QuietError::NotFound(path)); }
+    let target = launch(file, page)?;
+    recent::record(&state, &path);
+    Ok(target)
+}
+
+// Open a search result or bookmark: a PDF opens in the system viewer, at `target.page` when
+// set; any other file (where a section or chapter can't be jumped to from outside) is revealed
+// in the file manager, the archive for one of its members. Records the open in the recent
+// documents. Returns the target with its `url` filled in and `section` passed through.
+#[tauri::command]
+pub fn open_target(target: OpenTarget, state: State<AppState>) -> Result<OpenTarget, QuietError> {
+    let file = on_disk(&target.path);
+    let p = Path::new(file);
+    if !p.exists() { return Err(QuietError::NotFound(target.path)); }
+    let resolved = if is_pdf(p) {
+        launch(file, target.page)?
+    } else {
+        reveal(file)?;
+        resolve_target(p, None)
+    };
+    recent::record(&state, &target.path);
+    Ok(OpenTarget { section: target.section, ..resolved })
+}
+
+fn is_pdf(path: &Path) -> bool { path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pdf")) }
+
+fn launch(path: &str, page: Option<u32>) -> Result<OpenTarget, QuietError> {
+    let p = Path::new(path);
+    let target = resolve_target(p, page);
     #[cfg(target_os = "windows")]
     {
-        use std::process::Command;
-        let p = std::path::Path::new(&path);
-        let arg = if p.exists() { format!("/select,{}", path) } else { path.clone() };
-        let _ = Command::new("explorer").arg(arg).status();
-        return Ok(());
+        if let (true, Some(n), Some(sumatra)) = (is_pdf(p), page, find_sumatra()) {
+            Command::new(sumatra).arg("-page").arg(n.to_string()).arg(path).spawn()?;
+        } else {
+            Command::new("cmd").args(["/C", "start", ""]).arg(path).spawn()?;
+        }
     }
     #[cfg(target_os = "macos")]
     {
-        use std::process::Command;
-        let _ = Command::new("open").arg("-R").arg(&path).status();
-        return Ok(());
+        let arg = if page.is_some() { target.url.clone() } else { path.to_string() };
+        Command::new("open").arg(arg).spawn()?;
     }
     #[cfg(target_os = "linux")]
     {
-        use std::process::Command;
-        let folder = std::path::Path::new(&path).parent().unwrap_or(std::path::Path::new("/"));
-        let _ = Command::new("xdg-open").arg(folder).status();
-        return Ok(());
+        Command::new("xdg-open").arg(path).spawn()?;
+    }
+    Ok(target)
+}
+
+#[cfg(target_os = "windows")]
+fn find_sumatra() -> Option<std::path::PathBuf> {
+    let mut candidates: Vec<std::path::PathBuf> = Vec::new();
+    for var in ["LOCALAPPDATA", "ProgramFiles", "ProgramFiles(x86)"] {
+        if let Some(dir) = std::env::var_os(var) { candidates.push(Path::new(&dir).join("SumatraPDF").join("SumatraPDF.exe")); }
+    }
+    if let Some(paths) = std::env::var_os("PATH") {
+        candidates.extend(std::env::split_paths(&paths).map(|d| d.join("SumatraPDF.exe")));
+    }
+    candidates.into_iter().find(|c| c.exists())
+}
+
+// file:// URL for `path`, with a PDF open-parameters `#page=N` fragment when a page is given.
+fn resolve_target(path: &Path, page: Option<u32>) -> OpenTarget {
+    let raw = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from("file://");
+    if !raw.starts_with('/') { url.push('/'); }
+    for b in raw.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => url.push(b as char),
+            _ => url.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    if let Some(n) = page { url.push_str(&format!("#page={}", n)); }
+    OpenTarget { url, path: path.to_string_lossy().to_string(), page, section: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_target_url() {
+        let t = resolve_target(Path::new("/docs/My Paper.pdf"), Some(12));
+        assert_eq!(t.url, "file:///docs/My%20Paper.pdf#page=12");
+        assert_eq!(t.page, Some(12));
+        assert_eq!(resolve_target(Path::new("/docs/a.pdf"), None).url, "file:///docs/a.pdf");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_linux_reveal_commands_order() {
+        let cmds = linux_reveal_commands(Path::new("/docs/a.pdf"), Some("thunar"));
+        assert_eq!(cmds[0], vec!["thunar", "/docs/a.pdf"]);
+        assert_eq!(cmds[1], vec!["nautilus", "--select", "/docs/a.pdf"]);
+        assert_eq!(cmds.last().unwrap(), &vec!["xdg-open".to_string(), "/docs".to_string()]);
+        assert_eq!(linux_reveal_commands(Path::new("/docs/a.pdf"), Some("  ")).len(), 4);
     }
 }
+
 // Intentionally minimal: PDF rendering is done client-side with PDF.js now.
@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use tauri::State;
+
+use crate::{error::QuietError, models::RecentDocument, util::{recent_list::RecentList, tantivy_index}, AppState};
+
+// Older opens fall off the end
+const RECENT: RecentList = RecentList { file: "recent.json", cap: 50 };
+
+// Move `doc_path` to the front of the recent documents.
+pub fn record(state: &AppState, doc_path: &str) {
+    let opened_at = time::OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap_or_default();
+    let title = tantivy_index::indexed_title(state, doc_path)
+        .unwrap_or_else(|| Path::new(doc_path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| doc_path.to_string()));
+    RECENT.update(&state.app_dir, |all| push_entry(all, RecentDocument { path: doc_path.to_string(), title, opened_at }));
+}
+
+fn push_entry(mut all: Vec<RecentDocument>, entry: RecentDocument) -> Vec<RecentDocument> {
+    all.retain(|d| d.path != entry.path);
+    RECENT.push_front(all, entry)
+}
+
+// Documents opened through `open_document` / `open_target`, most recent first.
+#[tauri::command]
+pub fn get_recent_documents(state: State<AppState>) -> Result<Vec<RecentDocument>, QuietError> {
+    Ok(RECENT.read(&state.app_dir))
+}
+
+#[tauri::command]
+pub fn clear_recent_documents(state: State<AppState>) -> Result<(), QuietError> {
+    RECENT.clear(&state.app_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_moves_path_to_front() {
+        let tmp = tempfile::tempdir().unwrap();
+        let state = AppState::new(tmp.path().join("app"));
+        for p in ["/a.pdf", "/b.txt", "/a.pdf"] { record(&state, p); }
+        let paths: Vec<String> = RECENT.read::<RecentDocument>(&state.app_dir).into_iter().map(|d| d.path).collect();
+        assert_eq!(paths, vec!["/a.pdf", "/b.txt"]);
+        for i in 0..60 { record(&state, &format!("/{}.md", i)); }
+        let all: Vec<RecentDocument> = RECENT.read(&state.app_dir);
+        assert_eq!(all.len(), RECENT.cap);
+        assert_eq!(all[0].path, "/59.md");
+        // Not indexed: titled by file name
+        assert_eq!(all[0].title, "59.md");
+    }
+}
@@ -1,167 +1,567 @@
-use std::{fs, path::{Path, PathBuf}};
+use std::{fs, path::{Path, PathBuf}, sync::atomic::Ordering, time::{Duration, Instant}};
 
-use tauri::State;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State, async_runtime::{channel, spawn_blocking}};
 
 use crate::{
-    commands::library,
-    models::SearchResult,
+    error::QuietError,
+    commands::{history, library},
+    models::{PageHit, SearchField, SearchResponse, SearchOptions, SearchResult, SortMode},
     AppState,
 };
 use crate::util::tantivy_index;
 use crate::util::{
-    extract_text::{extract_title_and_text, is_supported_text},
-    extract_pdf::extract_pdf_pages_cached,
-    snippet::make_snippets,
+    extract_pdf::cached_pdf_document,
+    extract_zip::member_path,
+    snippet::{make_snippet, make_snippets_highlighted, split_at_matches, top_snippets, Snippet, DEFAULT_SNIPPET_LEN},
 };
 
+// Query syntax with the index:
+// - Words are all required: `rust async` is `rust AND async`.
+// - `a OR b` matches either; `AND` binds tighter, so `a OR b c` is `a OR (b AND c)`.
+// - `NOT a` or `-a` excludes files with the word in their title or body; `+a` requires it.
+// - Parentheses group: `(a OR b) NOT c`.
+// - `"a b"` is a phrase, `a NEAR/n b` both words within n words, `config*` any word starting
+//   with `config`. `title:`, `body:` and `section:` look in one field only.
+// `options` (all optional, see `SearchOptions`):
+// - `folder` restricts results to one watched folder; omit it to search the whole library.
+// - With `highlight`, each result carries the byte ranges of matches within its snippet.
+// - `snippetLen` sets the snippet context in bytes (default 400).
+// - `maxSnippetsPerDoc` caps the snippets from one file, page or section (default 3), keeping
+//   those with the most query terms.
+// - `sort` orders the best matches by relevance (default) or by file modification time, or
+//   every match (up to `SORT_COLLECT_CAP`) alphabetically by title or path. Sorting applies to
+//   the per-passage results, so a document's passages stay together, best first.
+// - `minSize` / `maxSize` keep only files within that byte size (inclusive).
+// - `modifiedAfter` (inclusive) / `modifiedBefore` (exclusive), in Unix seconds, keep only files
+//   modified in that window; after > before is rejected as invalid input.
+// - `groupByDocument` returns one result per file with its best snippets in `snippets`.
+// - `minScore` drops results scoring below it (see `SearchOptions::min_score` for ranges).
+// - `fields` limits which of `title`, `body` and `section` unscoped words are matched in
+//   (default all); an empty list is rejected as invalid input.
+// - `caseSensitive` matches words with their case as written, in bodies and sections. Needs
+//   the index, rebuilt since the option was added.
+// - `snippetParts` also returns each snippet split at its matches (`parts`).
+// - `regex` treats the query as a case-insensitive regular expression over document text;
+//   the score is the match count. Needs the index.
+// `limit` here always wins over `options.limit`.
 #[tauri::command]
-pub fn search(query: String, limit: u32, state: State<AppState>) -> Result<Vec<SearchResult>, String> {
+pub async fn search(query: String, limit: u32, options: Option<SearchOptions>, app: AppHandle) -> Result<SearchResponse, QuietError> {
+    run_search_blocking(app, query, limit, options, false).await
+}
+
+// `search` plus how many matching files there are of each type (by extension), counted in the
+// same pass. With the index the counts cover every match, not just the returned `limit`;
+// without one they cover the results found.
+#[tauri::command]
+pub async fn search_facets(query: String, limit: u32, options: Option<SearchOptions>, app: AppHandle) -> Result<SearchResponse, QuietError> {
+    run_search_blocking(app, query, limit, options, true).await
+}
+
+// Each search runs on the blocking pool, so a new one starts while older ones are still working.
+async fn run_search_blocking(app: AppHandle, query: String, limit: u32, options: Option<SearchOptions>, with_facets: bool) -> Result<SearchResponse, QuietError> {
+    spawn_blocking(move || run_search(&app.state::<AppState>(), &query, limit, options, with_facets))
+        .await
+        .map_err(|e| QuietError::Other(format!("join error: {:?}", e)))?
+}
+
+// Searches share the generation counter with streams: each one takes the next generation when
+// its thread starts, so whichever started last is the current query. One overtaken by a newer
+// search (the user kept typing) stops at its next document and resolves to no results, which
+// the frontend drops anyway; only the newest does the full work. Two calls made at nearly the
+// same moment may start in either order, so the one that counts as newer isn't necessarily the
+// one the UI sent last; the frontend only keeps the response to its latest query.
+fn run_search(state: &AppState, query: &str, limit: u32, options: Option<SearchOptions>, with_facets: bool) -> Result<SearchResponse, QuietError> {
+    let generation = state.search_generation.fetch_add(1, Ordering::SeqCst) + 1;
+    run_search_as(state, generation, query, limit, options, with_facets)
+}
+
+fn run_search_as(state: &AppState, generation: u64, query: &str, limit: u32, options: Option<SearchOptions>, with_facets: bool) -> Result<SearchResponse, QuietError> {
     let t0 = std::time::Instant::now();
     let q = query.trim();
-    if q.is_empty() { return Ok(vec![]); }
+    if q.is_empty() { return Ok(SearchResponse::default()); }
+    let opts = SearchOptions { limit, ..options.unwrap_or_default() };
+    // Grouping folds several passages into one result, so gather enough to fill `limit` documents
+    let mut collect_opts = if opts.group_by_document { SearchOptions { limit: limit.saturating_mul(GROUP_SNIPPETS as u32), ..opts.clone() } } else { opts.clone() };
+    // The alphabetical orders aren't about the best matches, so sort a wider set before truncating
+    if matches!(opts.sort, SortMode::TitleAsc | SortMode::PathAsc) { collect_opts.limit = collect_opts.limit.max(SORT_COLLECT_CAP); }
+    // Parts are cut at the highlighted matches, whichever way (words, regex) they were found
+    if opts.snippet_parts { collect_opts.highlight = true; }
+    let current = || state.search_generation.load(Ordering::SeqCst) == generation;
+    let SearchResponse { mut results, total, facets } = collect_results_faceted(state, q, &collect_opts, &mut |_| current(), with_facets)?;
+    if !current() {
+        eprintln!("quietlibrary: search q=\"{}\" superseded elapsed={}ms", q, t0.elapsed().as_millis());
+        return Ok(SearchResponse::default());
+    }
+    // Only queries that weren't overtaken while typing go into the history
+    history::record(state, q);
+
+    sort_results(&mut results, opts.sort);
+    if opts.group_by_document { results = group_by_document(results, GROUP_SNIPPETS); }
+    if results.len() as u32 > limit { results.truncate(limit as usize); }
+    if opts.snippet_parts {
+        for r in &mut results {
+            r.parts = split_at_matches(&r.snippet, &r.highlights, r.snippet.len());
+            if !opts.highlight { r.highlights.clear(); }
+        }
+    }
+    let elapsed = t0.elapsed();
+    eprintln!("quietlibrary: search q=\"{}\" n={} total={} elapsed={}ms", q, results.len(), total, elapsed.as_millis());
+    Ok(SearchResponse { results, total, facets })
+}
+
+// Results gathered for an alphabetical sort, however small `limit` is
+const SORT_COLLECT_CAP: u32 = 1000;
+
+// Relevance: score desc, then path. Newest/Oldest: by modification time (files without one
+// last), ties broken by relevance. TitleAsc/PathAsc: case-insensitive, ties broken by path,
+// then relevance.
+fn sort_results(results: &mut [SearchResult], sort: SortMode) {
+    let by_score = |a: &SearchResult, b: &SearchResult| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal).then(a.path.cmp(&b.path));
+    match sort {
+        SortMode::Relevance => results.sort_by(by_score),
+        SortMode::Newest => results.sort_by(|a, b| b.modified.is_some().cmp(&a.modified.is_some()).then(b.modified.cmp(&a.modified)).then(by_score(a, b))),
+        SortMode::Oldest => results.sort_by(|a, b| b.modified.is_some().cmp(&a.modified.is_some()).then(a.modified.cmp(&b.modified)).then(by_score(a, b))),
+        SortMode::TitleAsc => results.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()).then(a.path.to_lowercase().cmp(&b.path.to_lowercase())).then(by_score(a, b))),
+        SortMode::PathAsc => results.sort_by(|a, b| a.path.to_lowercase().cmp(&b.path.to_lowercase()).then(by_score(a, b))),
+    }
+}
+
+// Snippets kept per document when results are grouped
+const GROUP_SNIPPETS: usize = 5;
+
+// Merge results of the same path into the first (best-ranked) one, which keeps its page,
+// snippet and highlights and lists up to `per_doc` snippets. Order follows the first results.
+fn group_by_document(results: Vec<SearchResult>, per_doc: usize) -> Vec<SearchResult> {
+    let mut grouped: Vec<SearchResult> = Vec::new();
+    let mut slot: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    for r in results {
+        match slot.get(&r.path) {
+            Some(&i) => {
+                let g = &mut grouped[i];
+                if g.snippets.len() < per_doc && !r.snippet.is_empty() { g.snippets.push(r.snippet); }
+            }
+            None => {
+                slot.insert(r.path.clone(), grouped.len());
+                let snippets = if r.snippet.is_empty() { vec![] } else { vec![r.snippet.clone()] };
+                grouped.push(SearchResult { snippets, ..r });
+            }
+        }
+    }
+    grouped
+}
+
+#[derive(Clone, Serialize)]
+struct SearchBatch {
+    query: String,
+    generation: u64,
+    results: Vec<SearchResult>,
+}
+
+#[derive(Clone, Serialize)]
+struct SearchComplete {
+    query: String,
+    generation: u64,
+    total: usize,
+    cancelled: bool,
+}
+
+const STREAM_BATCH: usize = 20;
+const STREAM_INTERVAL: Duration = Duration::from_millis(100);
+// Batches waiting to be emitted before the search thread waits for the window
+const STREAM_QUEUE: usize = 8;
+
+// Streaming variant of `search`: emits `search-result` batches while results are collected,
+// then `search-complete` with the total. Each call bumps the search generation (shared with
+// `search`, see `run_search`); an older stream notices at its next checkpoint and stops,
+// reporting `cancelled: true`.
+// Results arrive in discovery order; unlike `search`, the fallback scan is not re-sorted and
+// `groupByDocument` is ignored. `limit`, when given, wins over `options.limit` as in `search`.
+#[tauri::command]
+pub async fn search_stream(query: String, limit: Option<u32>, options: Option<SearchOptions>, window: tauri::Window) -> Result<(), QuietError> {
+    let mut opts = options.unwrap_or_default();
+    if let Some(limit) = limit { opts.limit = limit; }
+    let app = window.app_handle();
+    let generation = app.state::<AppState>().search_generation.fetch_add(1, Ordering::SeqCst) + 1;
+    let q = query.trim().to_string();
+    // The search blocks, so it runs on its own thread and hands batches back here to emit
+    let (tx, mut rx) = channel::<Vec<SearchResult>>(STREAM_QUEUE);
+    let query = q.clone();
+    let task = spawn_blocking(move || -> Result<bool, QuietError> {
+        let state = app.state::<AppState>();
+        let limit = opts.limit as usize;
+        let mut sent = 0usize;
+        let mut last_send = Instant::now();
+        let mut flush = |all: &[SearchResult], force: bool| -> bool {
+            if state.search_generation.load(Ordering::SeqCst) != generation { return false; }
+            let end = all.len().min(limit);
+            if end > sent && (force || end - sent >= STREAM_BATCH || last_send.elapsed() >= STREAM_INTERVAL) {
+                let _ = tx.blocking_send(all[sent..end].to_vec());
+                sent = end;
+                last_send = Instant::now();
+            }
+            true
+        };
+        let results = if query.is_empty() { vec![] } else { collect_results(&state, &query, &opts, &mut |all| flush(all, false))? };
+        Ok(flush(&results, true))
+    });
+    let mut emitted = 0usize;
+    while let Some(results) = rx.recv().await {
+        emitted += results.len();
+        let _ = window.emit("search-result", SearchBatch { query: q.clone(), generation, results });
+    }
+    let completed = task.await.map_err(|e| QuietError::Other(format!("join error: {:?}", e)))??;
+    let _ = window.emit("search-complete", SearchComplete { query: q, generation, total: emitted, cancelled: !completed });
+    Ok(())
+}
+
+// PDF pages read per file when searching without an index
+const SCAN_PDF_PAGES: u32 = 50;
+
+// Gather results from the index when present, otherwise by scanning watched folders.
+// `progress` sees the growing list after each document/file and may stop the search early.
+fn collect_results(state: &AppState, q: &str, opts: &SearchOptions, progress: &mut dyn FnMut(&[SearchResult]) -> bool) -> Result<Vec<SearchResult>, QuietError> {
+    Ok(collect_results_faceted(state, q, opts, progress, false)?.results)
+}
+
+// `collect_results` with the total match count, plus file type counts when `with_facets` is set.
+fn collect_results_faceted(state: &AppState, q: &str, opts: &SearchOptions, progress: &mut dyn FnMut(&[SearchResult]) -> bool, with_facets: bool) -> Result<SearchResponse, QuietError> {
     // If an index exists, use it exclusively to avoid slow fallback scans.
     // When no index exists yet, fall back to on-demand scanning.
+    opts.validate()?;
     let index_dir = state.app_dir.join("index");
+    if (opts.regex || opts.case_sensitive) && !index_dir.exists() { return Err(QuietError::IndexMissing); }
     if index_dir.exists() {
-        return tantivy_index::search_index(&state, q, limit as usize);
+        let mut response = tantivy_index::search_index_faceted(state, q, opts, progress, with_facets)?;
+        let indexed = response.results.len();
+        scan_truncated_pages(state, q, opts, &mut response.results, progress);
+        response.total += tantivy_index::document_count(&response.results[indexed..]);
+        return Ok(response);
     }
 
-    let folders = library::watched_folders(&state);
+    let limit = opts.limit;
+    let folders = library::watched_folders(state);
     let mut results: Vec<SearchResult> = Vec::new();
-    // Use the app cache dir consistently for extractor caches during fallback scan
-    let cache_dir = state.app_dir.join("cache");
+    // Same formats, text limits and extractor cache as indexing; fewer PDF pages to stay responsive
+    let config = tantivy_index::ExtractConfig::with_pdf_pages(state, Some(SCAN_PDF_PAGES));
 
-    for folder in folders {
-        let path = PathBuf::from(&folder);
-        scan_folder(&path, &cache_dir, q, limit, &mut results)?;
-        if results.len() as u32 >= limit { break; }
+    for folder in &folders {
+        if opts.folder.as_ref().is_some_and(|f| f != folder) { continue; }
+        let path = PathBuf::from(folder);
+        let mut walk = library::FolderWalk::for_folder(state, &path);
+        scan_folder(&path, &config, q, opts, &mut walk, &mut results, progress)?;
+        walk.log_skipped();
+        if results.len() as u32 >= limit || !progress(&results) { break; }
     }
+    // Scanning a folder also walks watched folders nested in it; those results belong elsewhere
+    if let Some(f) = &opts.folder {
+        results.retain(|r| library::owning_folder(Path::new(&r.path), &folders).as_ref() == Some(f));
+    }
+    let facets = if with_facets { tantivy_index::file_type_counts(results.iter().map(|r| r.path.as_str())) } else { vec![] };
+    Ok(SearchResponse { total: tantivy_index::document_count(&results), results, facets })
+}
 
-    // sort by score desc, then by path
-    results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal).then(a.path.cmp(&b.path)));
-    if results.len() as u32 > limit { results.truncate(limit as usize); }
-    let elapsed = t0.elapsed();
-    eprintln!("quietlibrary: search q=\"{}\" n={} elapsed={}ms", q, results.len(), elapsed.as_millis());
-    Ok(results)
+// Pages past the page cap scanned by one search, over all truncated PDFs
+const SCAN_TRUNCATED_PAGES: usize = 2000;
+
+// Pages past the indexing page cap aren't in the index; scan them directly for PDFs the last
+// index run reported as truncated, while the index left room under the limit. Indexing caches
+// every page of a PDF, so only cached text is scanned: a PDF whose cache entry is gone or stale
+// is skipped rather than extracted in the middle of a search, and at most
+// SCAN_TRUNCATED_PAGES pages are read.
+fn scan_truncated_pages(state: &AppState, q: &str, opts: &SearchOptions, out: &mut Vec<SearchResult>, progress: &mut dyn FnMut(&[SearchResult]) -> bool) {
+    // Scanned pages are matched ignoring case
+    if opts.case_sensitive { return; }
+    let truncated = tantivy_index::load_report(state).truncated;
+    if truncated.is_empty() || out.len() as u32 >= opts.limit { return; }
+    let folders = library::watched_folders(state);
+    let cache_dir = state.app_dir.join("cache");
+    let mut budget = SCAN_TRUNCATED_PAGES;
+    for t in truncated {
+        let path = PathBuf::from(&t.path);
+        if opts.folder.is_some() && library::owning_folder(&path, &folders) != opts.folder { continue; }
+        let fp = tantivy_index::file_fp(&path);
+        if !opts.size_matches(fp.map(|(_, size)| size)) || !opts.modified_matches(fp.map(|(mtime, _)| mtime)) { continue; }
+        let Some(doc) = cached_pdf_document(&path, &cache_dir) else { continue };
+        for (page, text) in doc.pages.iter().filter(|(page, _)| *page > t.last_indexed_page) {
+            if budget == 0 { return; }
+            budget -= 1;
+            push_page_results(&path, q, opts, &doc.title, *page, text, Some(&doc.which), out);
+            if out.len() as u32 >= opts.limit { return; }
+        }
+        if !progress(out) { return; }
+    }
+}
+
+// Completions for the word being typed, from terms already in the index, most common first.
+// Empty without an index or for a blank prefix.
+#[tauri::command]
+pub fn suggest(prefix: String, limit: u32, state: State<AppState>) -> Result<Vec<String>, QuietError> {
+    tantivy_index::suggest_terms(&state, &prefix, limit as usize)
+}
+
+// "Did you mean": a corrected query for one that returned nothing, built from words in the
+// index that are at most two edits from a missing or rare query word. None if nothing is close.
+#[tauri::command]
+pub fn suggest_correction(query: String, state: State<AppState>) -> Result<Option<String>, QuietError> {
+    tantivy_index::suggest_correction(&state, query.trim())
 }
 
 // Return a sorted list of pages within a document that match the query.
 // Uses the Tantivy index when available; otherwise falls back to cached PDF text.
 #[tauri::command]
-pub fn search_document_pages(path: String, query: String, limit: u32, state: State<AppState>) -> Result<Vec<u32>, String> {
-    let t0 = std::time::Instant::now();
+pub fn search_document_pages(path: String, query: String, limit: u32, state: State<AppState>) -> Result<Vec<u32>, QuietError> {
     let q = query.trim();
     if q.is_empty() { return Ok(vec![]); }
-    let index_dir = state.app_dir.join("index");
-    if index_dir.exists() {
+    if state.app_dir.join("index").exists() {
         return tantivy_index::search_pages_for_document(&state, &path, q, limit as usize);
     }
-    // Fallback: use extract cache to scan pages
-    let p = std::path::PathBuf::from(&path);
+    Ok(scan_document_pages(&state, &path, q, limit).into_iter().map(|(page, _)| page).collect())
+}
+
+// Like `search_document_pages`, but best match first, with each page's score and a snippet
+// so a reader can point at the most relevant page.
+#[tauri::command]
+pub fn search_document_page_hits(path: String, query: String, limit: u32, state: State<AppState>) -> Result<Vec<PageHit>, QuietError> {
+    let q = query.trim();
+    if q.is_empty() { return Ok(vec![]); }
+    if state.app_dir.join("index").exists() {
+        return tantivy_index::search_page_hits(&state, &path, q, limit as usize);
+    }
+    let lq = q.to_lowercase();
+    let mut hits: Vec<PageHit> = scan_document_pages(&state, &path, q, limit)
+        .into_iter()
+        .map(|(page, text)| PageHit { page, score: text.to_lowercase().matches(&lq).count() as f32, snippet: make_snippet(&text, q, DEFAULT_SNIPPET_LEN) })
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.page.cmp(&b.page)));
+    Ok(hits)
+}
+
+// Without an index: pages of a PDF (from the extraction cache) containing `q`, in page order.
+fn scan_document_pages(state: &AppState, path: &str, q: &str, limit: u32) -> Vec<(u32, String)> {
+    let t0 = std::time::Instant::now();
+    let p = PathBuf::from(path);
+    let mut out: Vec<(u32, String)> = Vec::new();
     if p.extension().and_then(|s| s.to_str()).unwrap_or("").eq_ignore_ascii_case("pdf") {
         let cache_dir = state.app_dir.join("cache");
         if let Ok((_title, pages, _which)) = crate::util::extract_pdf::extract_pdf_pages_cached(&p, &cache_dir, limit) {
             let lq = q.to_lowercase();
-            let mut out: Vec<u32> = Vec::new();
-            for (num, text) in pages {
-                if text.to_lowercase().contains(&lq) { out.push(num); }
-            }
-            out.sort_unstable();
-            out.dedup();
-            let elapsed = t0.elapsed();
-            eprintln!("quietlibrary: search_document_pages (fallback) file={} hits={} elapsed={}ms", path, out.len(), elapsed.as_millis());
-            return Ok(out);
+            out = pages.into_iter().filter(|(_, text)| text.to_lowercase().contains(&lq)).collect();
+            out.sort_unstable_by_key(|(num, _)| *num);
+            out.dedup_by_key(|(num, _)| *num);
         }
     }
     let elapsed = t0.elapsed();
-    eprintln!("quietlibrary: search_document_pages file={} hits=0 elapsed={}ms", path, elapsed.as_millis());
-    Ok(vec![])
+    eprintln!("quietlibrary: search_document_pages (fallback) file={} hits={} elapsed={}ms", path, out.len(), elapsed.as_millis());
+    out
 }
 
-fn scan_folder(dir: &Path, cache_dir: &Path, q: &str, limit: u32, out: &mut Vec<SearchResult>) -> Result<(), String> {
+fn scan_folder(dir: &Path, config: &tantivy_index::ExtractConfig, q: &str, opts: &SearchOptions, walk: &mut library::FolderWalk, out: &mut Vec<SearchResult>, progress: &mut dyn FnMut(&[SearchResult]) -> bool) -> Result<(), String> {
+    let limit = opts.limit;
     if !dir.exists() { return Ok(()); }
     let entries = match fs::read_dir(dir) { Ok(e) => e, Err(_) => return Ok(()) };
     for entry in entries {
         let entry = match entry { Ok(e) => e, Err(_) => continue };
         let path = entry.path();
-        if path.is_dir() {
-            scan_folder(&path, cache_dir, q, limit, out)?;
-            if out.len() as u32 >= limit { return Ok(()); }
+        let is_dir = path.is_dir();
+        if walk.is_excluded(&path, is_dir) { continue; }
+        if is_dir {
+            if !walk.enter_dir(&path) { continue; }
+            scan_folder(&path, config, q, opts, walk, out, progress)?;
+            if out.len() as u32 >= limit || !progress(out) { return Ok(()); }
             continue;
         }
-        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
-        if is_supported_text(&path) {
-            match extract_title_and_text(&path) {
-                Ok((title, text)) => push_text_results(&path, q, &title, &text, out),
-                Err(_) => {}
-            }
-        } else if ext == "pdf" {
-            match extract_pdf_pages_cached(&path, cache_dir, 50) {
-                Ok((title, pages, which)) => {
-                    for (page, text) in &pages {
-                        push_page_results(&path, q, &title, *page, &text, Some(&which), out);
-                        if out.len() as u32 >= limit { return Ok(()); }
+        let fp = tantivy_index::file_fp(&path);
+        if !opts.size_matches(fp.map(|(_, size)| size)) || !opts.modified_matches(fp.map(|(mtime, _)| mtime)) { continue; }
+        let Some(extractor) = config.extractors.for_path(&path) else { continue };
+        match extractor.extract(&path) {
+            Ok(doc) => {
+                for part in &doc.parts {
+                    let title = part.title.as_deref().unwrap_or(&doc.title);
+                    match (part.page, &part.member) {
+                        (Some(page), _) => push_page_results(&path, q, opts, title, page, &part.body, doc.which.as_deref(), out),
+                        (None, Some(member)) => {
+                            let member = PathBuf::from(member_path(&path.to_string_lossy(), member));
+                            push_text_results(&member, q, opts, title, part.section.as_deref(), &part.body, out);
+                        }
+                        (None, None) => push_text_results(&path, q, opts, title, part.section.as_deref(), &part.body, out),
                     }
-                    eprintln!("quietlibrary: extractor={} file={} ({} pages)", which, path.to_string_lossy(), pages.len());
+                    if out.len() as u32 >= limit { return Ok(()); }
                 }
-                Err(_) => {
-                    // fallback to filename match
-                    let filename = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-                    let lf = filename.to_lowercase();
-                    let lq = q.to_lowercase();
-                    if lf.contains(&lq) {
-                        out.push(SearchResult { title: filename.to_string(), path: path.to_string_lossy().to_string(), page: None, section: None, snippet: String::new(), score: 0.05 });
-                    }
+                if let Some(which) = &doc.which {
+                    eprintln!("quietlibrary: extractor={} file={} ({} pages)", which, path.to_string_lossy(), doc.parts.len());
                 }
             }
-        } else if ext == "epub" {
-            // Keep EPUB as filename-only for now
-            let filename = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-            let lf = filename.to_lowercase();
-            let lq = q.to_lowercase();
-            if lf.contains(&lq) {
-                out.push(SearchResult {
-                    title: filename.to_string(),
-                    path: path.to_string_lossy().to_string(),
-                    page: None,
-                    section: None,
-                    snippet: String::new(),
-                    score: 0.05,
-                });
+            Err(_) => {
+                // fallback to filename match
+                let filename = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                let lf = filename.to_lowercase();
+                let lq = q.to_lowercase();
+                if lf.contains(&lq) && opts.searches(SearchField::Title) && opts.score_matches(0.05) {
+                    out.push(SearchResult { title: filename.to_string(), path: path.to_string_lossy().to_string(), page: None, section: None, snippet: String::new(), score: 0.05, modified: fp.map(|(m, _)| m), size: fp.map(|(_, s)| s), ..Default::default() });
+                }
             }
-        } else {
-            // unsupported type
         }
-        if out.len() as u32 >= limit { return Ok(()); }
+        if out.len() as u32 >= limit || !progress(out) { return Ok(()); }
     }
     Ok(())
 }
 
-fn push_text_results(path: &Path, q: &str, title: &str, text: &str, out: &mut Vec<SearchResult>) {
-    let snippets = make_snippets(text, q, 400);
-    for snippet in snippets {
+fn push_text_results(path: &Path, q: &str, opts: &SearchOptions, title: &str, section: Option<&str>, text: &str, out: &mut Vec<SearchResult>) {
+    if !opts.score_matches(1.0) { return; }
+    let snippets = top_snippets(make_snippets_highlighted(text, q, opts.snippet_len()), q, opts.max_snippets_per_doc());
+    let fp = tantivy_index::file_fp(path);
+    for Snippet { text: snippet, highlights } in snippets {
         out.push(SearchResult {
             title: title.to_string(),
             path: path.to_string_lossy().to_string(),
             page: None,
-            section: None,
+            section: section.map(|s| s.to_string()),
             snippet,
             score: 1.0,
+            match_count: highlights.len() as u32,
+            highlights: if opts.highlight { highlights } else { vec![] },
+            modified: fp.map(|(mtime, _)| mtime),
+            size: fp.map(|(_, size)| size),
+            ..Default::default()
         });
     }
 }
 
-fn push_page_results(path: &Path, q: &str, title: &str, page: u32, text: &str, extractor: Option<&str>, out: &mut Vec<SearchResult>) {
-    let mut snippets = make_snippets(text, q, 400);
-    if let Some(which) = extractor { for s in &mut snippets { s.push_str(&format!(" \u{00B7} [{}]", which)); } }
-    for snippet in snippets {
+#[allow(clippy::too_many_arguments)]
+fn push_page_results(path: &Path, q: &str, opts: &SearchOptions, title: &str, page: u32, text: &str, extractor: Option<&str>, out: &mut Vec<SearchResult>) {
+    if !opts.score_matches(1.1) { return; }
+    let snippets = top_snippets(make_snippets_highlighted(text, q, opts.snippet_len()), q, opts.max_snippets_per_doc());
+    let fp = tantivy_index::file_fp(path);
+    for Snippet { text: snippet, highlights } in snippets {
         out.push(SearchResult {
             title: title.to_string(),
             path: path.to_string_lossy().to_string(),
             page: Some(page),
             section: None,
+            extractor: extractor.map(|e| e.to_string()),
             snippet,
             score: 1.1,
+            match_count: highlights.len() as u32,
+            highlights: if opts.highlight { highlights } else { vec![] },
+            modified: fp.map(|(mtime, _)| mtime),
+            size: fp.map(|(_, size)| size),
+            ..Default::default()
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(path: &str, score: f32, modified: Option<u64>) -> SearchResult {
+        SearchResult { path: path.to_string(), score, modified, ..Default::default() }
+    }
+
+    fn paths(results: &[SearchResult]) -> Vec<&str> { results.iter().map(|r| r.path.as_str()).collect() }
+
+    // A library of one watched folder `docs/` holding `files`, not indexed
+    fn library_state(files: &[(&str, &str)]) -> (tempfile::TempDir, AppState) {
+        let tmp = tempfile::tempdir().unwrap();
+        let docs = tmp.path().join("docs");
+        fs::create_dir_all(&docs).unwrap();
+        for (name, content) in files { fs::write(docs.join(name), content).unwrap(); }
+        let app_dir = tmp.path().join("app");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::write(app_dir.join("library.json"), serde_json::to_vec(&serde_json::json!({ "folders": [docs.to_string_lossy()] })).unwrap()).unwrap();
+        (tmp, AppState::new(app_dir))
+    }
+
+    #[test]
+    fn test_sort_results_by_mtime() {
+        let mut results = vec![result("old", 3.0, Some(100)), result("unknown", 5.0, None), result("new", 1.0, Some(300)), result("mid", 2.0, Some(200))];
+        sort_results(&mut results, SortMode::Newest);
+        assert_eq!(paths(&results), vec!["new", "mid", "old", "unknown"]);
+        sort_results(&mut results, SortMode::Oldest);
+        assert_eq!(paths(&results), vec!["old", "mid", "new", "unknown"]);
+        sort_results(&mut results, SortMode::Relevance);
+        assert_eq!(paths(&results), vec!["unknown", "old", "mid", "new"]);
+    }
+
+    #[test]
+    fn test_sort_results_alphabetically() {
+        let titled = |title: &str, path: &str, score: f32| SearchResult { title: title.to_string(), ..result(path, score, None) };
+        let mut results = vec![titled("beta", "/b/2.txt", 1.0), titled("Alpha", "/c/1.txt", 2.0), titled("beta", "/b/2.txt", 4.0), titled("gamma", "/A/3.txt", 3.0)];
+        sort_results(&mut results, SortMode::TitleAsc);
+        assert_eq!(results.iter().map(|r| (r.title.as_str(), r.score)).collect::<Vec<_>>(), vec![("Alpha", 2.0), ("beta", 4.0), ("beta", 1.0), ("gamma", 3.0)]);
+        sort_results(&mut results, SortMode::PathAsc);
+        assert_eq!(paths(&results), vec!["/A/3.txt", "/b/2.txt", "/b/2.txt", "/c/1.txt"]);
+        assert_eq!(serde_json::to_string(&SortMode::TitleAsc).unwrap(), "\"titleAsc\"");
+    }
+
+    #[test]
+    fn test_min_score_drops_filename_only_hits() {
+        // Not a readable PDF, so only its name can match
+        let (_tmp, state) = library_state(&[("quartz-report.pdf", "not a pdf")]);
+        let hits = collect_results(&state, "quartz", &SearchOptions::default(), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].score, 0.05);
+        assert_eq!(hits[0].match_count, 0);
+        let strict = SearchOptions { min_score: Some(0.5), ..Default::default() };
+        assert!(collect_results(&state, "quartz", &strict, &mut |_| true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_match_count_counts_occurrences_in_snippet() {
+        let mut out = Vec::new();
+        push_text_results(Path::new("/docs/birds.txt"), "heron", &SearchOptions::default(), "Birds", None, "A heron, another Heron and a third HERON.", &mut out);
+        assert_eq!(out[0].match_count, 3);
+        // Not reported as highlights unless asked for
+        assert!(out[0].highlights.is_empty());
+    }
+
+    #[test]
+    fn test_superseded_search_returns_nothing() {
+        let (_tmp, state) = library_state(&[("notes.txt", "Notes\nquartz veins")]);
+        assert_eq!(run_search(&state, "quartz", 10, None, false).unwrap().results.len(), 1);
+        // A newer search took the next generation while this one ran
+        let stale = state.search_generation.fetch_add(1, Ordering::SeqCst);
+        assert!(run_search_as(&state, stale, "quartz", 10, None, false).unwrap().results.is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_searches_keep_only_the_newest() {
+        // Only one file matches, so a search has to read the whole library before it's done
+        let files: Vec<(String, String)> = (0..3000).map(|i| (format!("note{i}.txt"), format!("Note {i}\n{}", if i == 1500 { "quartz veins" } else { "granite" }))).collect();
+        let refs: Vec<(&str, &str)> = files.iter().map(|(n, c)| (n.as_str(), c.as_str())).collect();
+        let (_tmp, state) = library_state(&refs);
+        std::thread::scope(|s| {
+            let older = s.spawn(|| run_search(&state, "quartz", 10, None, false).unwrap());
+            // Start the second search once the first has taken its generation and is scanning
+            while state.search_generation.load(Ordering::SeqCst) == 0 { std::thread::yield_now(); }
+            let newer = run_search(&state, "quartz", 10, None, false).unwrap();
+            assert!(older.join().unwrap().results.is_empty());
+            assert_eq!(newer.results.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_snippet_parts_option() {
+        let (_tmp, state) = library_state(&[("notes.txt", "Notes\nquartz veins in granite")]);
+        let opts = SearchOptions { snippet_parts: true, ..Default::default() };
+        let results = run_search(&state, "veins", 10, Some(opts), false).unwrap().results;
+        let parts = &results[0].parts;
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].matched, "veins");
+        assert_eq!(format!("{}{}{}", parts[0].before, parts[0].matched, parts[0].after), results[0].snippet);
+        // Highlights were only needed to cut the parts
+        assert!(results[0].highlights.is_empty());
+    }
+
+    #[test]
+    fn test_group_by_document() {
+        let hit = |path: &str, page: u32, score: f32| SearchResult { path: path.to_string(), page: Some(page), snippet: format!("{} p{}", path, page), score, ..Default::default() };
+        let results = vec![hit("book", 12, 3.0), hit("notes", 1, 2.5), hit("book", 40, 2.0), hit("book", 7, 1.5), hit("notes", 2, 1.0)];
+        let grouped = group_by_document(results, 2);
+        assert_eq!(paths(&grouped), vec!["book", "notes"]);
+        assert_eq!((grouped[0].page, grouped[0].score), (Some(12), 3.0));
+        assert_eq!(grouped[0].snippets, vec!["book p12", "book p40"]);
+        assert_eq!(grouped[1].snippets, vec!["notes p1", "notes p2"]);
+    }
+}
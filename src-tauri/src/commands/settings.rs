@@ -0,0 +1,148 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::{error::QuietError, util::{extract_text::DEFAULT_MAX_TEXT_BYTES, retry::RetryPolicy}, AppState};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+// Query-time boosts applied to matches in each field; a weight of 0 excludes the field.
+// Scores are Tantivy's BM25, whose k1 (1.2) and b (0.75) are fixed by the library, so these
+// weights are the ranking knob. Titles default to double weight so a title match outranks
+// the same term buried in some other document's body.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FieldWeights {
+    pub title: f32,
+    pub body: f32,
+    pub section: f32,
+}
+
+impl Default for FieldWeights {
+    fn default() -> Self { FieldWeights { title: 2.0, body: 1.0, section: 1.0 } }
+}
+
+// User-tunable knobs. Every field has a default so older settings files keep parsing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub read_retry: RetryPolicy,
+    pub field_weights: FieldWeights,
+    // Stemming language for document bodies ("english", "french", ...; "" for none).
+    // Applied at index build time, so changing it requires a full reindex.
+    pub stem_language: String,
+    // Drop `stop_words` from document bodies at index time (also needs a reindex)
+    pub remove_stop_words: bool,
+    pub stop_words: Vec<String>,
+    // Bytes read from each text/Markdown/HTML file; the rest of a longer file is not indexed
+    pub max_text_bytes: usize,
+    // Extensions (e.g. "md") always read in full regardless of `max_text_bytes`
+    pub full_text_exts: Vec<String>,
+    // Pages indexed per PDF; later pages are only found by a slower direct scan
+    pub max_pdf_pages: u32,
+    // Memory for buffering documents before they're written out as a segment, split across the
+    // indexing threads. More means fewer, larger segments and a faster full reindex; less suits
+    // low-memory machines at the cost of more segment flushes and merging. Full reindexes use
+    // all of it, incremental updates only what their size needs. Clamped to 15 MB-2 GB.
+    pub writer_heap_bytes: usize,
+    // Also fingerprint files by a hash of their first 64 KB and their size, so a
+    // file rewritten unchanged (backup and sync tools) is not re-extracted just for its new
+    // mtime. Costs a read of every file whose mtime changed, and of each file once when
+    // turned on or rebuilt; off by default.
+    pub content_hash_fingerprints: bool,
+    // Extracted text a full reindex buffers between the extraction threads and the index writer.
+    // A bigger buffer keeps all threads busy when a few huge files stall the writer; a smaller
+    // one caps memory on large libraries, with extraction pausing more often.
+    pub extract_buffer_bytes: usize,
+    // PDFs extracted at once while indexing. Pdfium can hold hundreds of MB for one big
+    // scanned PDF, so PDFs get this many threads of their own while other files use the full
+    // indexing pool. Pdfium itself reads one document at a time (see pdfium_loader::with_pdfium);
+    // these threads also run cache lookups and the lopdf fallback. Clamped to 1-8.
+    pub pdf_threads: usize,
+}
+
+pub const DEFAULT_MAX_PDF_PAGES: u32 = 300;
+pub const DEFAULT_WRITER_HEAP_BYTES: usize = 128 * 1024 * 1024;
+pub const DEFAULT_EXTRACT_BUFFER_BYTES: usize = 256 * 1024 * 1024;
+pub const DEFAULT_PDF_THREADS: usize = 2;
+
+// Lucene's English stop-word list
+pub const DEFAULT_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it", "no", "not",
+    "of", "on", "or", "such", "that", "the", "their", "then", "there", "these", "they", "this", "to", "was",
+    "will", "with",
+];
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            read_retry: RetryPolicy::default(),
+            field_weights: FieldWeights::default(),
+            stem_language: "english".into(),
+            remove_stop_words: false,
+            stop_words: DEFAULT_STOP_WORDS.iter().map(|w| w.to_string()).collect(),
+            max_text_bytes: DEFAULT_MAX_TEXT_BYTES,
+            full_text_exts: Vec::new(),
+            max_pdf_pages: DEFAULT_MAX_PDF_PAGES,
+            writer_heap_bytes: DEFAULT_WRITER_HEAP_BYTES,
+            content_hash_fingerprints: false,
+            extract_buffer_bytes: DEFAULT_EXTRACT_BUFFER_BYTES,
+            pdf_threads: DEFAULT_PDF_THREADS,
+        }
+    }
+}
+
+impl Settings {
+    // How many bytes of `path` to read as text.
+    pub fn text_limit(&self, path: &Path) -> usize {
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+        if !ext.is_empty() && self.full_text_exts.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext)) { usize::MAX } else { self.max_text_bytes }
+    }
+}
+
+fn settings_path(state: &AppState) -> PathBuf {
+    state.app_dir.join(SETTINGS_FILE)
+}
+
+pub(crate) fn read_settings(state: &AppState) -> Settings {
+    let p = settings_path(state);
+    if let Ok(bytes) = fs::read(&p) {
+        serde_json::from_slice(&bytes).unwrap_or_default()
+    } else {
+        Settings::default()
+    }
+}
+
+pub(crate) fn write_settings(state: &AppState, settings: &Settings) -> Result<(), QuietError> {
+    let p = settings_path(state);
+    fs::create_dir_all(&state.app_dir)?;
+    let bytes = serde_json::to_vec_pretty(settings)?;
+    Ok(fs::write(p, bytes)?)
+}
+
+#[tauri::command]
+pub fn get_settings(state: State<AppState>) -> Result<Settings, QuietError> {
+    Ok(read_settings(&state))
+}
+
+#[tauri::command]
+pub fn update_settings(settings: Settings, state: State<AppState>) -> Result<(), QuietError> {
+    write_settings(&state, &settings)
+}
+
+#[tauri::command]
+pub fn get_field_weights(state: State<AppState>) -> Result<FieldWeights, QuietError> {
+    Ok(read_settings(&state).field_weights)
+}
+
+// Weights only affect query parsing, so changes apply to the next search without a rebuild.
+#[tauri::command]
+pub fn set_field_weights(weights: FieldWeights, state: State<AppState>) -> Result<(), QuietError> {
+    let all = [weights.title, weights.body, weights.section];
+    if all.iter().any(|w| !w.is_finite() || *w < 0.0) { return Err(QuietError::InvalidInput("field weights must be non-negative numbers".into())); }
+    if all.iter().all(|w| *w == 0.0) { return Err(QuietError::InvalidInput("at least one field weight must be positive".into())); }
+    let mut settings = read_settings(&state);
+    settings.field_weights = weights;
+    write_settings(&state, &settings)
+}
@@ -0,0 +1,86 @@
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+// Error returned by every command. It reaches the frontend as `{ kind, message }` so the UI can
+// branch on `kind` (e.g. offer "Build index" for `index_missing`) and still show `message`.
+#[derive(Debug, thiserror::Error)]
+pub enum QuietError {
+    #[error("the search index has not been built yet")]
+    IndexMissing,
+    #[error("{0}")]
+    Io(String),
+    #[error("pdfium is not available: {0}")]
+    PdfiumUnavailable(String),
+    #[error("{0}")]
+    Extraction(String),
+    // Encrypted PDF opened without (the right) password; see `set_pdf_password`
+    #[error("password required: {0}")]
+    PasswordRequired(String),
+    #[error("index error: {0}")]
+    Tantivy(String),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    // Failures from helpers that still report plain strings
+    #[error("{0}")]
+    Other(String),
+}
+
+impl QuietError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            QuietError::IndexMissing => "index_missing",
+            QuietError::Io(_) => "io",
+            QuietError::PdfiumUnavailable(_) => "pdfium_unavailable",
+            QuietError::Extraction(_) => "extraction",
+            QuietError::PasswordRequired(_) => "password_required",
+            QuietError::Tantivy(_) => "tantivy",
+            QuietError::NotFound(_) => "not_found",
+            QuietError::InvalidInput(_) => "invalid_input",
+            QuietError::Other(_) => "other",
+        }
+    }
+}
+
+impl Serialize for QuietError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("QuietError", 2)?;
+        s.serialize_field("kind", self.kind())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.end()
+    }
+}
+
+impl From<String> for QuietError {
+    fn from(e: String) -> Self { QuietError::Other(e) }
+}
+
+impl From<std::io::Error> for QuietError {
+    fn from(e: std::io::Error) -> Self { QuietError::Io(e.to_string()) }
+}
+
+// Our JSON files (library, settings, bookmarks) failing to encode or decode
+impl From<serde_json::Error> for QuietError {
+    fn from(e: serde_json::Error) -> Self { QuietError::Io(e.to_string()) }
+}
+
+impl From<tantivy::TantivyError> for QuietError {
+    fn from(e: tantivy::TantivyError) -> Self { QuietError::Tantivy(e.to_string()) }
+}
+
+impl From<tantivy::query::QueryParserError> for QuietError {
+    fn from(e: tantivy::query::QueryParserError) -> Self { QuietError::InvalidInput(format!("invalid query: {}", e)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_kind_and_message() {
+        let v = serde_json::to_value(QuietError::NotFound("/a.pdf".into())).unwrap();
+        assert_eq!(v, serde_json::json!({ "kind": "not_found", "message": "not found: /a.pdf" }));
+        let v = serde_json::to_value(QuietError::IndexMissing).unwrap();
+        assert_eq!(v["kind"], "index_missing");
+    }
+}
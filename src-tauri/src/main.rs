@@ -1,11 +1,13 @@
 #![cfg_attr(all(not(debug_assertions), target_os = "windows"), windows_subsystem = "windows")]
 
 mod commands;
+mod error;
 mod models;
 mod util;
 
 use std::path::PathBuf;
 use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
 use tantivy::{Index, IndexReader};
 
 use tauri::{Manager};
@@ -14,6 +16,15 @@ pub struct AppState {
     pub app_dir: PathBuf,
     pub index: Mutex<Option<Index>>,      // lazily opened
     pub reader: Mutex<Option<IndexReader>>, // lazily opened
+    pub writer: Mutex<Option<util::tantivy_index::CachedWriter>>, // opened by the first update, then reused
+    pub search_generation: AtomicU64,     // bumped per search and stream; older ones stop
+    pub index_generation: AtomicU64,      // bumped whenever the cached handles are dropped
+}
+
+impl AppState {
+    pub fn new(app_dir: PathBuf) -> Self {
+        AppState { app_dir, index: Mutex::new(None), reader: Mutex::new(None), writer: Mutex::new(None), search_generation: AtomicU64::new(0), index_generation: AtomicU64::new(0) }
+    }
 }
 
 fn resolve_app_dir(app: &tauri::AppHandle) -> PathBuf {
@@ -29,22 +40,68 @@ fn main() {
         .setup(|app| {
             let app_dir = resolve_app_dir(&app.app_handle());
             std::fs::create_dir_all(&app_dir).ok();
-            app.manage(AppState { app_dir, index: Mutex::new(None), reader: Mutex::new(None) });
+            let state = AppState::new(app_dir.clone());
+            util::tantivy_index::recover_interrupted_rebuild(&state);
+            let folders = commands::library::watched_folders(&state);
+            app.manage(state);
+            // Open the index now so the first search doesn't wait for it
+            let handle = app.app_handle();
+            tauri::async_runtime::spawn_blocking(move || util::tantivy_index::warm_index(&handle.state::<AppState>()));
+            // Keep the index current as files in watched folders change
+            app.manage(util::watcher::FolderWatcher::start(app.app_handle(), app_dir, &folders));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::library::add_watched_folder,
+            commands::library::add_watched_folders,
             commands::library::list_watched_folders,
+            commands::library::validate_library,
             commands::library::remove_watched_folder,
+            commands::library::add_exclude_pattern,
+            commands::library::list_exclude_patterns,
+            commands::library::remove_exclude_pattern,
+            commands::library::get_max_depth,
+            commands::library::set_max_depth,
+            commands::library::get_follow_symlinks,
+            commands::library::set_follow_symlinks,
+            commands::library::get_extra_text_exts,
+            commands::library::set_extra_text_exts,
             commands::indexer::reindex_all,
-            commands::indexer::index_incremental,
+            commands::indexer::reindex_incremental,
+            commands::indexer::remove_from_index,
             commands::indexer::clear_extract_cache,
+            commands::indexer::clear_extract_cache_for,
+            commands::indexer::get_index_report,
+            commands::indexer::preview_index,
             commands::search::search,
+            commands::search::search_facets,
             commands::search::search_document_pages,
+            commands::search::search_document_page_hits,
+            commands::search::search_stream,
+            commands::search::suggest,
+            commands::search::suggest_correction,
+            commands::history::get_search_history,
+            commands::history::clear_search_history,
+            commands::recent::get_recent_documents,
+            commands::recent::clear_recent_documents,
             commands::bookmarks::add_bookmark,
             commands::bookmarks::list_bookmarks,
             commands::bookmarks::remove_bookmark,
+            commands::bookmarks::update_bookmark,
+            commands::bookmarks::list_all_tags,
+            commands::bookmarks::search_bookmarks,
             commands::open::reveal_in_os,
+            commands::open::open_document,
+            commands::open::open_target,
+            commands::document::document_outline,
+            commands::document::pdfium_status,
+            commands::document::set_pdf_password,
+            commands::document::get_document_text,
+            commands::document::get_pdf_page_text,
+            commands::settings::get_settings,
+            commands::settings::update_settings,
+            commands::settings::get_field_weights,
+            commands::settings::set_field_weights,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,13 +1,189 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SearchResult {
     pub title: String,
     pub path: String,
     pub page: Option<u32>,
     pub section: Option<String>,
+    // PDF text extractor that read the page: "pdfium", "lopdf" or "ocr"
+    #[serde(default)]
+    pub extractor: Option<String>,
     pub snippet: String,
     pub score: f32,
+    // Byte ranges of query matches within `snippet`; only filled when highlighting is requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<(usize, usize)>,
+    // Occurrences of the query's words (or regex matches) in `snippet`, counted whether or not
+    // highlighting was requested; 0 for filename-only matches
+    #[serde(default)]
+    pub match_count: u32,
+    // Document metadata when the format records it (PDF Info dictionary)
+    #[serde(default)]
+    pub author: Option<String>,
+    // Creation date as YYYY-MM-DD
+    #[serde(default)]
+    pub created: Option<String>,
+    // Source file modification time, seconds since the Unix epoch
+    #[serde(default)]
+    pub modified: Option<u64>,
+    // Source file size in bytes (the whole file, also for a single PDF page)
+    #[serde(default)]
+    pub size: Option<u64>,
+    // With `group_by_document`: the document's best snippets, best first (`snippet` is the first)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snippets: Vec<String>,
+    // With `snippet_parts`: `snippet` split at each match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<SnippetParts>,
+}
+
+// A match and the text on either side of it, apart so a renderer can style the match without
+// searching the string again. All three are cut at char boundaries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnippetParts {
+    pub before: String,
+    pub matched: String,
+    pub after: String,
+}
+
+// A page of one document matching a query: its BM25 score (occurrence count when searched
+// without an index) and a snippet around the first match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageHit {
+    pub page: u32,
+    pub score: f32,
+    pub snippet: String,
+}
+
+// Matching files with one extension (lowercase, "" for none). A PDF counts once however
+// many of its pages match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FacetCount {
+    pub ext: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    // Documents matching the query in all, however many fit in `results`. Index documents are
+    // counted (each PDF page, EPUB chapter and Markdown section is one), not the per-paragraph
+    // snippets `results` expands them into, and `min_score` is not applied. Regex searches and
+    // searches without an index stop at the limit, so there it counts the documents found.
+    pub total: usize,
+    // Only from `search_facets`: matching files per type, most common first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub facets: Vec<FacetCount>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortMode {
+    #[default]
+    Relevance,
+    Newest,
+    Oldest,
+    // Alphabetical, ignoring case
+    TitleAsc,
+    PathAsc,
+}
+
+// Indexed fields a query's words are looked for in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchField {
+    // Document title, or the file name when the document has none
+    Title,
+    // Document text, including identifiers split for source files
+    Body,
+    // EPUB chapter or Markdown heading
+    Section,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SearchOptions {
+    pub limit: u32,
+    // Only return results from this watched folder (files under a nested watched folder
+    // belong to the nested one)
+    pub folder: Option<String>,
+    // Report match offsets in `SearchResult::highlights`
+    pub highlight: bool,
+    // Target snippet length in bytes; None uses `snippet::DEFAULT_SNIPPET_LEN`
+    pub snippet_len: Option<usize>,
+    // Most snippets one document (file, page or section) contributes, those holding the most
+    // query terms first; None uses `snippet::DEFAULT_MAX_SNIPPETS_PER_DOC`
+    pub max_snippets_per_doc: Option<usize>,
+    pub sort: SortMode,
+    // Inclusive bounds on the source file size in bytes
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    // One result per document carrying its top snippets, instead of one per matching passage
+    pub group_by_document: bool,
+    // Drop results scoring below this. Indexed search scores are BM25 with field weights,
+    // typically 0.5-20 (higher for short documents and title hits); without an index, text
+    // and EPUB matches score 1.0, PDF pages 1.1 and filename-only matches 0.05.
+    pub min_score: Option<f32>,
+    // Treat the query as a case-insensitive regular expression over document text (needs the index)
+    pub regex: bool,
+    // Source file modification window in seconds since the Unix epoch: `modified_after` is
+    // inclusive and `modified_before` exclusive, so back-to-back windows don't overlap.
+    // Either may be omitted; files of unknown mtime are excluded when one is set.
+    pub modified_after: Option<u64>,
+    pub modified_before: Option<u64>,
+    // Also return each result's snippet split at its matches in `SearchResult::parts`
+    pub snippet_parts: bool,
+    // Fields the query's words are matched in; None searches all of them (those with a
+    // non-zero field weight). Without an index, leaving out `Title` skips file-name matches.
+    pub fields: Option<Vec<SearchField>>,
+    // Match words with their case as written (`Error` but not `error`), in bodies and sections
+    // only; needs an index built with the `body_cs` field. Ignored for regex searches.
+    pub case_sensitive: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions { limit: 50, folder: None, highlight: false, snippet_len: None, max_snippets_per_doc: None, sort: SortMode::Relevance, min_size: None, max_size: None, group_by_document: false, min_score: None, regex: false, modified_after: None, modified_before: None, snippet_parts: false, fields: None, case_sensitive: false }
+    }
+}
+
+impl SearchOptions {
+    pub fn snippet_len(&self) -> usize { self.snippet_len.unwrap_or(crate::util::snippet::DEFAULT_SNIPPET_LEN) }
+
+    pub fn max_snippets_per_doc(&self) -> usize { self.max_snippets_per_doc.unwrap_or(crate::util::snippet::DEFAULT_MAX_SNIPPETS_PER_DOC) }
+
+    pub fn score_matches(&self, score: f32) -> bool { self.min_score.is_none_or(|min| score >= min) }
+
+    pub fn modified_matches(&self, modified: Option<u64>) -> bool {
+        if self.modified_after.is_none() && self.modified_before.is_none() { return true; }
+        match modified {
+            Some(t) => self.modified_after.is_none_or(|after| t >= after) && self.modified_before.is_none_or(|before| t < before),
+            None => false,
+        }
+    }
+
+    // Options no search can satisfy, reported instead of silently matching nothing
+    pub fn validate(&self) -> Result<(), crate::error::QuietError> {
+        if let (Some(after), Some(before)) = (self.modified_after, self.modified_before) {
+            if after > before { return Err(crate::error::QuietError::InvalidInput(format!("modifiedAfter ({}) is later than modifiedBefore ({})", after, before))); }
+        }
+        if self.fields.as_ref().is_some_and(|f| f.is_empty()) { return Err(crate::error::QuietError::InvalidInput("fields is empty".into())); }
+        Ok(())
+    }
+
+    pub fn searches(&self, field: SearchField) -> bool {
+        self.fields.as_ref().is_none_or(|f| f.contains(&field))
+    }
+
+    // With either size bound set, files of unknown size are excluded
+    pub fn size_matches(&self, size: Option<u64>) -> bool {
+        if self.min_size.is_none() && self.max_size.is_none() { return true; }
+        match size {
+            Some(size) => self.min_size.is_none_or(|min| size >= min) && self.max_size.is_none_or(|max| size <= max),
+            None => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,13 +195,157 @@ pub struct Bookmark {
     pub note: Option<String>,
     #[serde(rename = "createdAt")]
     pub created_at: String,
+    // Absent in bookmark files written before tags existed
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// One query in search_history.json; `searched_at` is RFC 3339 UTC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub query: String,
+    pub searched_at: String,
+}
+
+// One document in recent.json; `opened_at` is RFC 3339 UTC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentDocument {
+    pub path: String,
+    // From the index when the document is indexed, otherwise the file name
+    #[serde(default)]
+    pub title: String,
+    pub opened_at: String,
+}
+
+// One entry of the tag sidebar
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
 }
 
+// A document location to open. `path` is the file on disk, what gets validated and launched;
+// `url` is derived from it (a `file://` URL, plus `#page=N` for a PDF page) for viewers that
+// take URLs. `url` is ignored on input and may be omitted.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenTarget {
+    #[serde(default)]
     pub url: String,
     pub path: String,
     pub page: Option<u32>,
     pub section: Option<String>,
 }
 
+// Full extracted text of one document; `truncated` when the byte or page limit cut it short
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentText {
+    pub title: String,
+    pub text: String,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutlineEntry {
+    pub title: String,
+    pub level: u32,
+    // 1-based source line for Markdown/HTML headings; None for PDF bookmarks
+    pub line: Option<u32>,
+    // 1-based page for PDF bookmarks; None for text documents
+    pub page: Option<u32>,
+    pub children: Vec<OutlineEntry>,
+}
+
+// Payload of the `reindex-progress` event sent while a full reindex extracts files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReindexProgress {
+    pub done: usize,
+    pub total: usize,
+    pub file: String,
+}
+
+// Files touched by an incremental index update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexUpdateStats {
+    pub added: usize,
+    pub changed: usize,
+    pub deleted: usize,
+    // Files moved or renamed without changing; their documents were re-filed, not re-extracted
+    pub renamed: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexFileError {
+    pub path: String,
+    pub error: String,
+    // Read attempts made before giving up (0 when the file was skipped without reading)
+    pub attempts: u32,
+}
+
+// A PDF with more pages than the index takes (Settings::max_pdf_pages)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TruncatedFile {
+    pub path: String,
+    // Pages after this one aren't in the index
+    pub last_indexed_page: u32,
+    // Pages in the file, blank ones included
+    pub total_pages: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexReport {
+    pub errors: Vec<IndexFileError>,
+    #[serde(default)]
+    pub truncated: Vec<TruncatedFile>,
+    // Watched folders that could not be read (missing or no permission); their documents
+    // were kept from the previous index rather than dropped
+    #[serde(default)]
+    pub unreachable: Vec<String>,
+}
+
+// What a reindex would read, from walking the watched folders without extracting anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexPreview {
+    pub total_files: usize,
+    pub total_bytes: u64,
+    // Every extension found, most files first
+    pub by_extension: Vec<ExtensionStats>,
+    // Files no extractor reads, and their extensions (sorted)
+    pub unsupported: usize,
+    pub unsupported_exts: Vec<String>,
+    // Watched folders that could not be read
+    pub unreachable: Vec<String>,
+}
+
+// Files with one extension (lowercase, "" for none) in an `IndexPreview`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionStats {
+    pub ext: String,
+    pub count: usize,
+    pub bytes: u64,
+    pub supported: bool,
+}
+
+// A watched folder and whether it can be indexed right now. An unplugged drive shows up as
+// `exists: false` while its documents stay searchable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedFolder {
+    pub path: String,
+    pub exists: bool,
+    pub readable: bool,
+    // Files the index holds from this folder
+    pub file_count: usize,
+}
+
+// Result of the most recent pdfium bind: where the library came from, and the directories
+// searched for it. Without pdfium, PDFs fall back to the weaker lopdf extractor.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PdfiumStatus {
+    pub loaded: bool,
+    pub source: Option<String>,
+    pub tried: Vec<String>,
+}
@@ -0,0 +1,118 @@
+use tantivy::tokenizer::{Token, TokenStream, Tokenizer};
+
+// Splits source code into identifiers (runs of letters, digits and `_`) and each identifier
+// into its words at underscores and case changes: `parse_config`, `parseConfig` and
+// `ParseConfig` all give `parse`, `config` at consecutive positions, plus the joined word
+// `parseConfig` at the first word's position. Queries go through the same tokenizer, so any of
+// the spellings (or `parseconfig`) finds the others once lowercased. Case is kept here for
+// the split; `LowerCaser` runs after.
+#[derive(Clone, Default)]
+pub struct CodeTokenizer {
+    tokens: Vec<Token>,
+}
+
+pub struct CodeTokenStream<'a> {
+    tokens: &'a [Token],
+    next: usize,
+    token: Token,
+}
+
+impl Tokenizer for CodeTokenizer {
+    type TokenStream<'a> = CodeTokenStream<'a>;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> CodeTokenStream<'a> {
+        self.tokens.clear();
+        let mut position = 0;
+        let mut start = None;
+        for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+            let in_identifier = c.is_alphanumeric() || c == '_';
+            match (start, in_identifier) {
+                (None, true) => start = Some(i),
+                (Some(s), false) => {
+                    push_identifier(&text[s..i], s, &mut position, &mut self.tokens);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        CodeTokenStream { tokens: &self.tokens, next: 0, token: Token::default() }
+    }
+}
+
+impl TokenStream for CodeTokenStream<'_> {
+    fn advance(&mut self) -> bool {
+        let Some(token) = self.tokens.get(self.next) else { return false };
+        // A copy, since filters (LowerCaser, folding) edit the current token in place
+        self.token = token.clone();
+        self.next += 1;
+        true
+    }
+
+    fn token(&self) -> &Token { &self.token }
+
+    fn token_mut(&mut self) -> &mut Token { &mut self.token }
+}
+
+// Words of one identifier as byte ranges within it
+fn identifier_words(ident: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<(usize, char)> = ident.char_indices().collect();
+    let mut words = Vec::new();
+    let mut start: Option<usize> = None;
+    for (k, &(i, c)) in chars.iter().enumerate() {
+        if c == '_' {
+            if let Some(s) = start.take() { words.push((s, i)); }
+            continue;
+        }
+        if let Some(s) = start {
+            let prev = chars[k - 1].1;
+            let next_lower = chars.get(k + 1).is_some_and(|&(_, n)| n.is_lowercase());
+            // fooBar, and the last capital of an acronym before a word: HTTPServer
+            let boundary = c.is_uppercase() && (prev.is_lowercase() || prev.is_numeric() || (prev.is_uppercase() && next_lower));
+            if boundary {
+                words.push((s, i));
+                start = Some(i);
+            }
+        } else {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start { words.push((s, ident.len())); }
+    words
+}
+
+fn push_identifier(ident: &str, offset: usize, position: &mut usize, out: &mut Vec<Token>) {
+    let words = identifier_words(ident);
+    if words.is_empty() { return; }
+    let token = |from: usize, to: usize, text: String, position: usize| Token { offset_from: offset + from, offset_to: offset + to, position, text, position_length: 1 };
+    if words.len() > 1 {
+        let joined: String = words.iter().map(|&(a, b)| &ident[a..b]).collect();
+        out.push(token(0, ident.len(), joined, *position));
+    }
+    for (a, b) in words {
+        out.push(token(a, b, ident[a..b].to_string(), *position));
+        *position += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<(String, usize)> {
+        let mut tokenizer = CodeTokenizer::default();
+        let mut stream = tokenizer.token_stream(text);
+        let mut out = Vec::new();
+        while let Some(t) = stream.next() { out.push((t.text.clone(), t.position)); }
+        out
+    }
+
+    #[test]
+    fn test_splits_identifiers_and_keeps_whole() {
+        let expected = vec![("parseConfig".to_string(), 0), ("parse".to_string(), 0), ("Config".to_string(), 1)];
+        assert_eq!(tokens("parseConfig"), expected);
+        assert_eq!(tokens("parse_config")[1..], [("parse".to_string(), 0), ("config".to_string(), 1)]);
+        assert_eq!(tokens("parse_config")[0].0, "parseconfig");
+        let words: Vec<String> = tokens("let s = HTTPServer::new(__init__, v2Api);").into_iter().map(|(t, _)| t).collect();
+        assert_eq!(words, vec!["let", "s", "HTTPServer", "HTTP", "Server", "new", "init", "v2Api", "v2", "Api"]);
+    }
+}
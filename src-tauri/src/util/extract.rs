@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    commands::settings::Settings,
+    error::QuietError,
+    util::{
+        extract_epub::extract_epub_chapters,
+        extract_pdf::extract_pdf_document_cached,
+        extract_text::{extract_title_and_sections, is_supported_text, is_text_file},
+        extract_zip::{extract_zip_members, is_zip, MAX_ARCHIVE_TEXT_BYTES},
+    },
+};
+
+// One searchable part of a file: a PDF page, an EPUB chapter, a Markdown section, an archive
+// member, or the whole text of any other file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DocPart {
+    pub page: Option<u32>,
+    pub section: Option<String>,
+    pub body: String,
+    // Archive member the part was read from, indexed under its own `archive!/member` path
+    pub member: Option<String>,
+    // The member's own title, shown instead of the file's
+    pub title: Option<String>,
+}
+
+// Everything an `Extractor` reads from one file.
+#[derive(Debug, Default)]
+pub struct ExtractedDoc {
+    pub title: String,
+    pub parts: Vec<DocPart>,
+    pub author: Option<String>,
+    pub created: Option<String>,
+    // Pages in the file (blank ones included) when a page limit may have left some out of `parts`
+    pub total_pages: Option<usize>,
+    // Last page the page limit let into `parts`
+    pub last_page: Option<u32>,
+    // Which PDF text extractor produced the text ("pdfium", "lopdf", "ocr")
+    pub which: Option<String>,
+    // Source code, whose identifiers are also indexed word by word
+    pub code: bool,
+}
+
+// A file format the indexer and the no-index search can read. A new format is one impl plus
+// an entry in `Extractors::standard`.
+pub trait Extractor: Send + Sync {
+    fn supports(&self, path: &Path) -> bool;
+    fn extract(&self, path: &Path) -> Result<ExtractedDoc, QuietError>;
+}
+
+fn has_ext(path: &Path, ext: &str) -> bool {
+    path.extension().and_then(|s| s.to_str()).unwrap_or("").eq_ignore_ascii_case(ext)
+}
+
+// Plain text, Markdown (split at its headings), HTML, DOCX, ODT, RTF and the library's extra text
+// extensions, read up to the configured byte limit.
+pub struct TextExtractor {
+    pub extra_exts: Vec<String>,
+    pub settings: Settings,
+}
+
+impl Extractor for TextExtractor {
+    fn supports(&self, path: &Path) -> bool { is_text_file(path, &self.extra_exts) }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDoc, QuietError> {
+        let (title, sections) = extract_title_and_sections(path, self.settings.text_limit(path)).map_err(QuietError::Extraction)?;
+        let parts = sections.into_iter().map(|(section, body)| DocPart { section, body, ..Default::default() }).collect();
+        // Extra extensions are code and config files
+        Ok(ExtractedDoc { title, parts, code: !is_supported_text(path), ..Default::default() })
+    }
+}
+
+// PDFs through the extraction cache, one part per page up to `max_pages`.
+pub struct PdfExtractor {
+    pub cache_root: PathBuf,
+    pub max_pages: u32,
+}
+
+impl Extractor for PdfExtractor {
+    fn supports(&self, path: &Path) -> bool { has_ext(path, "pdf") }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDoc, QuietError> {
+        let pdf = extract_pdf_document_cached(path, &self.cache_root, self.max_pages)?;
+        let parts = pdf.pages.into_iter().map(|(page, body)| DocPart { page: Some(page), body, ..Default::default() }).collect();
+        Ok(ExtractedDoc {
+            title: pdf.title,
+            parts,
+            author: pdf.info.author,
+            created: pdf.info.created,
+            total_pages: Some(pdf.total_pages),
+            last_page: Some(pdf.last_page),
+            which: Some(pdf.which),
+            code: false,
+        })
+    }
+}
+
+// EPUBs, one part per chapter with the chapter name in `section`.
+pub struct EpubExtractor;
+
+impl Extractor for EpubExtractor {
+    fn supports(&self, path: &Path) -> bool { has_ext(path, "epub") }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDoc, QuietError> {
+        let (title, chapters) = extract_epub_chapters(path).map_err(QuietError::Extraction)?;
+        let parts = chapters.into_iter().map(|(chapter, body)| DocPart { section: Some(chapter), body, ..Default::default() }).collect();
+        Ok(ExtractedDoc { title, parts, ..Default::default() })
+    }
+}
+
+// ZIP archives, one part per text, Markdown, HTML or RTF member with the member's name in
+// `section`, each read up to the text limit and all together up to `MAX_ARCHIVE_TEXT_BYTES`.
+pub struct ZipExtractor {
+    pub settings: Settings,
+}
+
+impl Extractor for ZipExtractor {
+    fn supports(&self, path: &Path) -> bool { is_zip(path) }
+
+    fn extract(&self, path: &Path) -> Result<ExtractedDoc, QuietError> {
+        let members = extract_zip_members(path, self.settings.max_text_bytes, MAX_ARCHIVE_TEXT_BYTES).map_err(QuietError::Extraction)?;
+        let parts = members
+            .into_iter()
+            .map(|m| DocPart { section: Some(m.name.clone()), body: m.text, member: Some(m.name), title: Some(m.title), ..Default::default() })
+            .collect();
+        let title = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        Ok(ExtractedDoc { title, parts, ..Default::default() })
+    }
+}
+
+// The formats we read, asked in order; the first that supports a file extracts it.
+pub struct Extractors(Vec<Box<dyn Extractor>>);
+
+impl Extractors {
+    // Built-in formats. Text comes first so an extra text extension is never claimed by another.
+    pub fn standard(settings: &Settings, extra_text_exts: Vec<String>, cache_root: &Path, max_pdf_pages: u32) -> Self {
+        Extractors(vec![
+            Box::new(TextExtractor { extra_exts: extra_text_exts, settings: settings.clone() }),
+            Box::new(PdfExtractor { cache_root: cache_root.to_path_buf(), max_pages: max_pdf_pages }),
+            Box::new(EpubExtractor),
+            Box::new(ZipExtractor { settings: settings.clone() }),
+        ])
+    }
+
+    pub fn for_path(&self, path: &Path) -> Option<&dyn Extractor> {
+        self.0.iter().find(|e| e.supports(path)).map(|e| e.as_ref())
+    }
+
+    pub fn supports(&self, path: &Path) -> bool { self.for_path(path).is_some() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_picks_extractor_by_extension() {
+        let tmp = tempfile::tempdir().unwrap();
+        let extractors = Extractors::standard(&Settings::default(), vec!["rs".to_string()], tmp.path(), 10);
+        for supported in ["a.txt", "b.MD", "c.pdf", "d.epub", "e.rs", "g.zip"] { assert!(extractors.supports(Path::new(supported)), "{}", supported); }
+        assert!(!extractors.supports(Path::new("f.png")));
+
+        let md = tmp.path().join("notes.md");
+        std::fs::write(&md, "# Notes\n\nintro\n\n## Setup\n\nrun it").unwrap();
+        let doc = extractors.for_path(&md).unwrap().extract(&md).unwrap();
+        let sections: Vec<Option<&str>> = doc.parts.iter().map(|p| p.section.as_deref()).collect();
+        assert_eq!(sections, vec![Some("Notes"), Some("Setup")]);
+        assert!(doc.parts.iter().all(|p| p.page.is_none()) && doc.total_pages.is_none());
+    }
+}
@@ -1,2 +1,98 @@
-// EPUB content extraction is deferred in the MVP.
-// Future: open .epub (ZIP), read spine XHTML, convert to text, and return sections.
+use std::{collections::HashMap, path::Path};
+
+use epub::doc::{EpubDoc, NavPoint};
+
+// Cap total extracted text per book so a giant ebook can't blow up indexing, in the same
+// spirit as the per-PDF page cap.
+pub const MAX_EPUB_TEXT_BYTES: usize = 8 * 1024 * 1024;
+
+// Walk the spine in reading order and return the book title plus (chapter name, text) pairs.
+// Chapter names come from the table of contents; untitled spine items get "Chapter N".
+pub fn extract_epub_chapters(path: &Path) -> Result<(String, Vec<(String, String)>), String> {
+    let mut doc = EpubDoc::new(path).map_err(|e| e.to_string())?;
+    let title = doc
+        .get_title()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string());
+
+    let mut labels: HashMap<String, String> = HashMap::new();
+    collect_toc_labels(&doc.toc, &mut labels);
+
+    let spine: Vec<String> = doc.spine.iter().map(|item| item.idref.clone()).collect();
+    let mut chapters: Vec<(String, String)> = Vec::new();
+    let mut total = 0usize;
+    for (i, idref) in spine.iter().enumerate() {
+        let resource_path = doc.resources.get(idref).map(|r| r.path.to_string_lossy().to_string());
+        let (html, _mime) = match doc.get_resource_str(idref) { Some(r) => r, None => continue };
+        let mut text = html2text::from_read(html.as_bytes(), 80).trim().to_string();
+        if text.is_empty() { continue; }
+        if total + text.len() > MAX_EPUB_TEXT_BYTES {
+            let mut end = MAX_EPUB_TEXT_BYTES - total;
+            while end > 0 && !text.is_char_boundary(end) { end -= 1; }
+            text.truncate(end);
+        }
+        total += text.len();
+        let name = resource_path
+            .and_then(|p| labels.get(&p).cloned())
+            .unwrap_or_else(|| format!("Chapter {}", i + 1));
+        if !text.is_empty() { chapters.push((name, text)); }
+        if total >= MAX_EPUB_TEXT_BYTES {
+            eprintln!("quietlibrary: epub text capped at {} bytes file={}", MAX_EPUB_TEXT_BYTES, path.to_string_lossy());
+            break;
+        }
+    }
+    Ok((title, chapters))
+}
+
+// Map each content document (without #fragment) to the first TOC label pointing at it.
+fn collect_toc_labels(points: &[NavPoint], out: &mut HashMap<String, String>) {
+    for p in points {
+        let content = p.content.to_string_lossy();
+        let file = content.split('#').next().unwrap_or("").to_string();
+        let label = p.label.trim();
+        if !label.is_empty() { out.entry(file).or_insert_with(|| label.to_string()); }
+        collect_toc_labels(&p.children, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+    use zip::write::SimpleFileOptions;
+
+    fn write_epub(path: &Path) {
+        let file = std::fs::File::create(path).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        let opts = SimpleFileOptions::default();
+        let entries: [(&str, &str); 6] = [
+            ("mimetype", "application/epub+zip"),
+            ("META-INF/container.xml", r#"<?xml version="1.0"?><container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#),
+            ("OEBPS/content.opf", r#"<?xml version="1.0"?><package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id"><metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Test Book</dc:title><dc:identifier id="id">x</dc:identifier></metadata><manifest><item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/><item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/></manifest><spine toc="ncx"><itemref idref="c1"/><itemref idref="c2"/></spine></package>"#),
+            ("OEBPS/toc.ncx", r#"<?xml version="1.0"?><ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap><navPoint id="n1" playOrder="1"><navLabel><text>Opening</text></navLabel><content src="c1.xhtml"/></navPoint><navPoint id="n2" playOrder="2"><navLabel><text>The Middle</text></navLabel><content src="c2.xhtml#start"/></navPoint></navMap></ncx>"#),
+            ("OEBPS/c1.xhtml", "<html><body><p>It was a dark and stormy night.</p></body></html>"),
+            ("OEBPS/c2.xhtml", "<html><body><p>The lighthouse keeper waited.</p></body></html>"),
+        ];
+        for (name, content) in entries {
+            zip.start_file(name, opts).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_extract_epub_chapters() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("book.epub");
+        write_epub(&path);
+        let (title, chapters) = extract_epub_chapters(&path).unwrap();
+        assert_eq!(title, "Test Book");
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].0, "Opening");
+        assert!(chapters[0].1.contains("stormy night"));
+        assert_eq!(chapters[1].0, "The Middle");
+        assert!(chapters[1].1.contains("lighthouse"));
+    }
+}
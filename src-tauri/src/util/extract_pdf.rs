@@ -1,91 +1,363 @@
 use std::{
+    collections::HashMap,
     fs,
     hash::{Hash, Hasher},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use lopdf::{content::Content, Document, Object, ObjectId};
 use serde::{Deserialize, Serialize};
-use crate::util::pdfium_loader;
+use crate::{error::QuietError, util::pdfium_loader};
 use once_cell::sync::Lazy;
 use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 
-// Prefer pdfium-render for accurate Unicode extraction; fall back to lopdf if binding fails
-// or extraction encounters an error.
-pub fn extract_pdf_pages(path: &Path) -> Result<(String, Vec<(u32, String)>, String), String> {
-    match extract_with_pdfium(path) {
-        Ok((title, pages)) => Ok((title, pages, "pdfium".to_string())),
-        Err(_) => extract_with_lopdf(path).map(|(t, p)| (t, p, "lopdf".to_string())),
+// Passwords for encrypted PDFs, keyed by path. Held in memory only (never written to disk) and
+// shared by every extraction, including the fresh AppState a reindex runs with.
+static PDF_PASSWORDS: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Remember `password` for `path`; an empty password forgets it.
+pub fn set_pdf_password(path: &Path, password: &str) {
+    if let Ok(mut passwords) = PDF_PASSWORDS.lock() {
+        let key = path.to_string_lossy().to_string();
+        if password.is_empty() { passwords.remove(&key); } else { passwords.insert(key, password.to_string()); }
     }
 }
 
-fn extract_with_pdfium(path: &Path) -> Result<(String, Vec<(u32, String)>), String> {
-    let pdfium = pdfium_loader::bind_pdfium()?;
+fn pdf_password(path: &Path) -> Option<String> {
+    PDF_PASSWORDS.lock().ok()?.get(&*path.to_string_lossy()).cloned()
+}
+
+// Open `path` with pdfium, using its stored password if any. A missing or wrong password is
+// reported as `PasswordRequired` so callers don't mistake it for a damaged file.
+fn load_with_pdfium<'a>(pdfium: &'a pdfium_render::prelude::Pdfium, path: &Path, password: Option<&'a str>) -> Result<pdfium_render::prelude::PdfDocument<'a>, QuietError> {
+    use pdfium_render::prelude::{PdfiumError, PdfiumInternalError};
+    pdfium.load_pdf_from_file(path, password).map_err(|e| match e {
+        PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError) => QuietError::PasswordRequired(path.to_string_lossy().to_string()),
+        e => QuietError::Extraction(format!("load failed: {}", e)),
+    })
+}
+
+fn extract_with_pdfium(path: &Path, reuse: &PageReuse) -> Result<(PdfInfo, PageTexts, usize), QuietError> {
+    extract_with_pdfium_opts(path, false, reuse).map(|(info, pages, page_count, _)| (info, pages, page_count))
+}
+
+type PageTexts = Vec<(u32, String)>;
 
-    let doc = pdfium
-        .load_pdf_from_file(path, None)
-        .map_err(|e| format!("load failed: {}", e))?;
+// Document metadata from the PDF Info dictionary. Empty values are treated as absent.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PdfInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    // Creation date as YYYY-MM-DD (or a shorter prefix when the PDF gives less)
+    pub created: Option<String>,
+}
+
+// One extracted PDF: display title (metadata title, else the filename), metadata, page texts
+// and which extractor produced them.
+#[derive(Debug, Clone)]
+pub struct PdfDocument {
+    pub title: String,
+    pub info: PdfInfo,
+    // Pages with text, by page number; blank pages are left out
+    pub pages: PageTexts,
+    pub which: String,
+    // Pages in the document, blank ones included
+    pub total_pages: usize,
+    // Last page `pages` covers: the caller's page limit when it cut the document short, else
+    // `total_pages`
+    pub last_page: u32,
+}
+
+impl PdfDocument {
+    fn new(info: PdfInfo, pages: PageTexts, which: &str, total_pages: usize, path: &Path) -> Self {
+        PdfDocument { title: display_title(&info, path), info, pages, which: which.to_string(), total_pages, last_page: total_pages as u32 }
+    }
+
+    fn with_page_limit(mut self, max_pages: u32) -> Self {
+        self.last_page = max_pages.min(self.total_pages as u32);
+        self.pages.retain(|(page, _)| *page <= max_pages);
+        self
+    }
 
-    // Some versions of pdfium-render expose limited metadata APIs; keep it simple.
-    let title = path
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_string();
+    // Whether the page limit left pages out
+    pub fn is_truncated(&self) -> bool { (self.last_page as usize) < self.total_pages }
+}
+
+fn display_title(info: &PdfInfo, path: &Path) -> String {
+    info.title
+        .clone()
+        .unwrap_or_else(|| path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string())
+}
+
+// Pages with fewer non-whitespace characters than this are treated as scanned images.
+const OCR_MIN_CHARS: usize = 10;
+
+// Prefer pdfium-render for accurate Unicode extraction; fall back to lopdf if binding fails
+// or extraction encounters an error. With `ocr`, pages that yield (almost) no text are
+// rendered and run through OCR, and the extractor is reported as "ocr" when any page needed it.
+// Pages found in `reuse` keep their earlier text instead of being extracted again. Encrypted
+// files pdfium can't open are not retried with lopdf, which can't decrypt them either.
+fn extract_pdf_document(path: &Path, ocr: bool, reuse: &PageReuse) -> Result<PdfDocument, QuietError> {
+    let (info, pages, total_pages, which) = match extract_with_pdfium_opts(path, ocr, reuse) {
+        Ok((info, pages, total_pages, true)) => (info, pages, total_pages, "ocr"),
+        Ok((info, pages, total_pages, false)) => (info, pages, total_pages, "pdfium"),
+        Err(e @ QuietError::PasswordRequired(_)) => {
+            eprintln!("quietlibrary: pdf is encrypted, set a password to index it file={}", path.to_string_lossy());
+            return Err(e);
+        }
+        Err(_) => extract_with_lopdf(path).map(|(info, pages, total_pages)| (info, pages, total_pages, "lopdf")).map_err(QuietError::Extraction)?,
+    };
+    Ok(PdfDocument::new(info, pages, which, total_pages, path))
+}
+
+// (metadata, pages with text, page count, whether any page needed OCR), read on the pdfium
+// worker thread
+fn extract_with_pdfium_opts(path: &Path, ocr: bool, reuse: &PageReuse) -> Result<(PdfInfo, PageTexts, usize, bool), QuietError> {
+    let (path, reuse) = (path.to_path_buf(), reuse.clone());
+    pdfium_loader::with_pdfium(move |pdfium| read_with_pdfium(pdfium, &path, ocr, &reuse)).map_err(QuietError::PdfiumUnavailable)?
+}
+
+fn read_with_pdfium(pdfium: &pdfium_render::prelude::Pdfium, path: &Path, ocr: bool, reuse: &PageReuse) -> Result<(PdfInfo, PageTexts, usize, bool), QuietError> {
+    use pdfium_render::prelude::PdfDocumentMetadataTagType as Tag;
+    let password = pdf_password(path);
+    let doc = load_with_pdfium(pdfium, path, password.as_deref())?;
+
+    let metadata = doc.metadata();
+    let tag = |t: Tag| metadata.get(t).map(|v| v.value().to_string());
+    let info = PdfInfo {
+        title: non_empty(tag(Tag::Title)),
+        author: non_empty(tag(Tag::Author)),
+        created: tag(Tag::CreationDate).and_then(|d| parse_pdf_date(&d)),
+    };
 
     let pages = doc.pages();
     let page_count = pages.len() as usize;
     let mut out: Vec<(u32, String)> = Vec::with_capacity(page_count);
+    let mut used_ocr = false;
     for i in 0..page_count {
+        if let Some(text) = reuse.text_for(i) {
+            if !text.is_empty() { out.push(((i as u32) + 1, text.to_string())); }
+            used_ocr |= reuse.ocr;
+            continue;
+        }
         if let Ok(page) = pages.get(i as u16) {
             let text = page
                 .text()
                 .map(|t| t.all())
                 .unwrap_or_default();
-            let norm = normalize_ws_preserve_newlines(&sanitize_text(&text));
+            let mut norm = normalize_ws_preserve_newlines(&sanitize_text(&text));
+            if ocr && norm.chars().filter(|c| !c.is_whitespace()).count() < OCR_MIN_CHARS {
+                match ocr_page(&page) {
+                    Ok(recognized) => {
+                        let recognized = normalize_ws_preserve_newlines(&sanitize_text(&recognized));
+                        if !recognized.is_empty() { norm = recognized; used_ocr = true; }
+                    }
+                    Err(e) => eprintln!("quietlibrary: ocr page={} file={} error={}", i + 1, path.to_string_lossy(), e),
+                }
+            }
             if !norm.is_empty() {
                 out.push((((i as u32) + 1), norm));
             }
         }
     }
-    Ok((title, out))
+    Ok((info, out, page_count, used_ocr))
 }
 
-// Previous lopdf-based best-effort extraction retained as fallback
-fn extract_with_lopdf(path: &Path) -> Result<(String, Vec<(u32, String)>), String> {
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.map(|v| sanitize_text(&v).trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// PDF dates look like `D:20210314092653+01'00'`; keep the calendar date as YYYY-MM-DD,
+// or the year/month when that is all the PDF records.
+fn parse_pdf_date(raw: &str) -> Option<String> {
+    let s = raw.trim();
+    let s = s.strip_prefix("D:").unwrap_or(s);
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).take(8).collect();
+    match digits.len() {
+        8 => Some(format!("{}-{}-{}", &digits[..4], &digits[4..6], &digits[6..8])),
+        6 => Some(format!("{}-{}", &digits[..4], &digits[4..6])),
+        4 => Some(digits),
+        _ => None,
+    }
+}
+
+// Render the page at a resolution Tesseract reads well and recognize it (English).
+#[cfg(feature = "ocr")]
+fn ocr_page(page: &pdfium_render::prelude::PdfPage) -> Result<String, String> {
+    use pdfium_render::prelude::PdfRenderConfig;
+    let config = PdfRenderConfig::new().set_target_width(2000);
+    let bitmap = page.render_with_config(&config).map_err(|e| format!("render failed: {}", e))?;
+    let (width, height) = (bitmap.width(), bitmap.height());
+    let rgba = bitmap.as_rgba_bytes();
+    tesseract::ocr_from_frame(&rgba, width, height, 4, width * 4, "eng").map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "ocr"))]
+fn ocr_page(_page: &pdfium_render::prelude::PdfPage) -> Result<String, String> {
+    Err("built without the `ocr` feature".to_string())
+}
+
+// Bookmarks (document outline) as (level, title, 1-based page) in depth-first order.
+// Only pdfium exposes a reliable outline walk; without it the outline is empty.
+pub fn extract_pdf_outline(path: &Path) -> Result<Vec<(u32, String, Option<u32>)>, QuietError> {
+    let path = path.to_path_buf();
+    pdfium_loader::with_pdfium(move |pdfium| {
+        let password = pdf_password(&path);
+        let doc = load_with_pdfium(pdfium, &path, password.as_deref())?;
+        let mut out = Vec::new();
+        if let Some(first) = doc.bookmarks().root() {
+            walk_bookmarks(first, 1, &mut out);
+        }
+        Ok(out)
+    }).map_err(QuietError::PdfiumUnavailable)?
+}
+
+fn walk_bookmarks(first: pdfium_render::prelude::PdfBookmark, level: u32, out: &mut Vec<(u32, String, Option<u32>)>) {
+    let mut node = Some(first);
+    while let Some(bookmark) = node {
+        let title = sanitize_text(&bookmark.title().unwrap_or_default()).trim().to_string();
+        let page = bookmark
+            .destination()
+            .and_then(|d| d.page_index().ok())
+            .map(|i| i as u32 + 1);
+        if !title.is_empty() { out.push((level, title, page)); }
+        if let Some(child) = bookmark.first_child() {
+            walk_bookmarks(child, level + 1, out);
+        }
+        node = bookmark.next_sibling();
+    }
+}
+
+// Previous lopdf-based best-effort extraction retained as fallback: (metadata, pages with text,
+// page count)
+fn extract_with_lopdf(path: &Path) -> Result<(PdfInfo, PageTexts, usize), String> {
     let doc = Document::load(path).map_err(|e| e.to_string())?;
-    let title = doc
+    let info_dict = doc
         .trailer
         .get(b"Info")
         .ok()
         .and_then(|id| match id {
             Object::Reference(oid) => doc.get_dictionary(*oid).ok(),
+            Object::Dictionary(dict) => Some(dict),
             _ => None,
-        })
-        .and_then(|dict| dict.get(b"Title").ok())
-        .and_then(|obj| obj.as_str().ok())
-        .map(|s| String::from_utf8_lossy(s).to_string())
-        .unwrap_or_else(|| path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string());
+        });
+    let entry = |key: &[u8]| {
+        info_dict
+            .and_then(|dict| dict.get(key).ok())
+            .and_then(|obj| obj.as_str().ok())
+            .map(decode_pdf_string)
+    };
+    let info = PdfInfo {
+        title: non_empty(entry(b"Title")),
+        author: non_empty(entry(b"Author")),
+        created: entry(b"CreationDate").and_then(|d| parse_pdf_date(&d)),
+    };
 
     let mut pages_text: Vec<(u32, String)> = Vec::new();
     let pages = doc.get_pages(); // BTreeMap<u32, ObjectId>
+    let page_count = pages.len();
     for (page_num, page_id) in pages {
         let text = extract_page_text(&doc, page_id);
         if !text.trim().is_empty() {
             pages_text.push((page_num, text));
         }
     }
-    Ok((title, pages_text))
+    Ok((info, pages_text, page_count))
+}
+
+// Info strings are either PDFDocEncoding (close enough to Latin-1) or UTF-16BE with a BOM.
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+        return String::from_utf16_lossy(&units);
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
 }
 
+// Bumped whenever the cache layout changes; entries of another version are extracted again.
+const PDF_CACHE_VERSION: u32 = 2;
+
+// One cache file per PDF path. When the file changes, pages whose content hash is unchanged
+// keep their cached text and only the edited pages are extracted again.
 #[derive(Serialize, Deserialize)]
 struct PdfCacheFile {
+    #[serde(default)]
+    version: u32,
+    // The PDF this entry belongs to, so stale entries can be found by path
+    #[serde(default)]
+    path: String,
     title: String,
-    pages: Vec<(u32, String)>,
+    info: PdfInfo,
+    which: String,
     mtime_secs: u64,
     size: u64,
-    which: Option<String>,
+    // Content hash of every page (index = page number - 1); empty when lopdf can't read the file
+    page_hashes: Vec<u64>,
+    pages: PageTexts,
+    // Pages in the document, blank ones included; 0 in entries written before it was kept
+    #[serde(default)]
+    page_count: usize,
+}
+
+impl PdfCacheFile {
+    fn into_document(self) -> PdfDocument {
+        let last_with_text = self.pages.last().map_or(0, |(page, _)| *page as usize);
+        let total_pages = if self.page_count > 0 { self.page_count } else { self.page_hashes.len().max(last_with_text) };
+        PdfDocument { title: self.title, info: self.info, pages: self.pages, which: self.which, total_pages, last_page: total_pages as u32 }
+    }
+}
+
+// Text of unchanged pages carried over from an earlier extraction, looked up by content hash.
+// `hashes[i]` is the hash of page i + 1 of the current file.
+#[derive(Default, Clone)]
+struct PageReuse {
+    hashes: Vec<u64>,
+    texts: HashMap<u64, String>,
+    // The carried-over text came from OCR
+    ocr: bool,
+}
+
+impl PageReuse {
+    // Empty pages are cached as absent, so they come back as "" rather than None.
+    fn new(hashes: Vec<u64>, old: Option<&PdfCacheFile>) -> PageReuse {
+        let mut texts = HashMap::new();
+        let mut ocr = false;
+        if let Some(old) = old.filter(|o| o.which != "lopdf") {
+            let by_page: HashMap<u32, &str> = old.pages.iter().map(|(n, t)| (*n, t.as_str())).collect();
+            for (i, h) in old.page_hashes.iter().enumerate() {
+                let text = by_page.get(&(i as u32 + 1)).copied().unwrap_or("");
+                texts.insert(*h, text.to_string());
+            }
+            ocr = old.which == "ocr";
+        }
+        PageReuse { hashes, texts, ocr }
+    }
+
+    fn text_for(&self, page_index: usize) -> Option<&str> {
+        self.hashes.get(page_index).and_then(|h| self.texts.get(h)).map(|t| t.as_str())
+    }
+
+    fn reused_count(&self) -> usize {
+        self.hashes.iter().filter(|h| self.texts.contains_key(h)).count()
+    }
+}
+
+// Hash of each page's content streams, in page order. Returns an empty list when lopdf can't
+// parse the file; every page is then extracted afresh.
+fn page_content_hashes(path: &Path) -> Vec<u64> {
+    let doc = match Document::load(path) { Ok(d) => d, Err(_) => return vec![] };
+    doc.get_pages()
+        .values()
+        .map(|id| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            doc.get_page_content(*id).unwrap_or_default().hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
 }
 
 fn file_fingerprint(path: &Path) -> Result<(u64, u64), String> {
@@ -98,52 +370,128 @@ fn file_fingerprint(path: &Path) -> Result<(u64, u64), String> {
     Ok((mtime, size))
 }
 
-fn cache_key(path: &Path, mtime: u64, size: u64) -> String {
+fn cache_path(cache_dir: &Path, path: &Path) -> PathBuf {
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
     path.to_string_lossy().hash(&mut hasher);
-    mtime.hash(&mut hasher);
-    size.hash(&mut hasher);
-    format!("{:016x}", hasher.finish())
+    cache_dir.join(format!("pdf_{:016x}.json", hasher.finish()))
+}
+
+fn load_cache_file(cache_path: &Path) -> Option<PdfCacheFile> {
+    let bytes = fs::read(cache_path).ok()?;
+    serde_json::from_slice::<PdfCacheFile>(&bytes).ok().filter(|c| c.version == PDF_CACHE_VERSION)
+}
+
+fn store_cache_file(path: &Path, cache_path: &Path, doc: &PdfDocument, fingerprint: (u64, u64), page_hashes: Vec<u64>) {
+    let to_store = PdfCacheFile {
+        version: PDF_CACHE_VERSION,
+        path: cache_owner(path),
+        title: doc.title.clone(),
+        info: doc.info.clone(),
+        which: doc.which.clone(),
+        mtime_secs: fingerprint.0,
+        size: fingerprint.1,
+        page_hashes,
+        pages: doc.pages.clone(),
+        page_count: doc.total_pages,
+    };
+    if let Ok(bytes) = serde_json::to_vec(&to_store) { let _ = fs::write(cache_path, bytes); }
 }
 
 pub fn extract_pdf_pages_cached(
     path: &Path,
     cache_dir: &Path,
     max_pages: u32,
-) -> Result<(String, Vec<(u32, String)>, String), String> {
+) -> Result<(String, Vec<(u32, String)>, String), QuietError> {
+    extract_pdf_document_cached(path, cache_dir, max_pages).map(|d| (d.title, d.pages, d.which))
+}
+
+// Cached extraction including document metadata. The cache holds every page so a larger
+// `max_pages` later needs no re-extraction.
+pub fn extract_pdf_document_cached(path: &Path, cache_dir: &Path, max_pages: u32) -> Result<PdfDocument, QuietError> {
     // Opportunistic LRU pruning of cache to keep its size bounded.
     maybe_prune_cache(cache_dir).ok();
     fs::create_dir_all(cache_dir).ok();
-    let (mtime, size) = file_fingerprint(path)?;
-    let key = cache_key(path, mtime, size);
-    let cache_path = cache_dir.join(format!("pdf_{}.json", key));
-
-    if let Ok(bytes) = fs::read(&cache_path) {
-        if let Ok(mut cached) = serde_json::from_slice::<PdfCacheFile>(&bytes) {
-            if cached.mtime_secs == mtime && cached.size == size {
-                // If cache exists but was produced by a poorer extractor, try upgrading to Pdfium.
-                let which = cached.which.clone().unwrap_or_else(|| "cache".to_string());
-                if which != "pdfium" {
-                    if let Ok((title_new, mut pages_new)) = extract_with_pdfium(path) {
-                        if (pages_new.len() as u32) > max_pages { pages_new.truncate(max_pages as usize); }
-                        let to_store = PdfCacheFile { title: title_new.clone(), pages: pages_new.clone(), mtime_secs: mtime, size, which: Some("pdfium".to_string()) };
-                        if let Ok(bytes) = serde_json::to_vec(&to_store) { let _ = fs::write(&cache_path, bytes); }
-                        return Ok((title_new, pages_new, "pdfium".to_string()));
-                    }
+    let fingerprint = file_fingerprint(path).map_err(QuietError::Io)?;
+    let cache_path = cache_path(cache_dir, path);
+    let cached = match load_cache_file(&cache_path) {
+        Some(cached) if (cached.mtime_secs, cached.size) == fingerprint => {
+            // If cache exists but was produced by a poorer extractor, try upgrading to Pdfium.
+            if cached.which != "pdfium" && cached.which != "ocr" {
+                if let Ok((info, pages, total_pages)) = extract_with_pdfium(path, &PageReuse::default()) {
+                    let doc = PdfDocument::new(info, pages, "pdfium", total_pages, path);
+                    store_cache_file(path, &cache_path, &doc, fingerprint, cached.page_hashes);
+                    return Ok(doc.with_page_limit(max_pages));
                 }
-                if (cached.pages.len() as u32) > max_pages { cached.pages.truncate(max_pages as usize); }
-                return Ok((cached.title, cached.pages, which));
             }
+            return Ok(cached.into_document().with_page_limit(max_pages));
         }
-    }
+        other => other,
+    };
 
-    let (title, mut pages, which) = extract_pdf_pages(path)?;
-    if (pages.len() as u32) > max_pages { pages.truncate(max_pages as usize); }
-    let to_store = PdfCacheFile { title: title.clone(), pages: pages.clone(), mtime_secs: mtime, size, which: Some(which.clone()) };
-    if let Ok(bytes) = serde_json::to_vec(&to_store) { let _ = fs::write(&cache_path, bytes); }
+    // The file is new or changed: extract, keeping the text of pages whose content is unchanged.
+    // With the `ocr` feature, recognized text is cached too (which = "ocr") so OCR runs once per page version.
+    let reuse = PageReuse::new(page_content_hashes(path), cached.as_ref());
+    let doc = extract_pdf_document(path, cfg!(feature = "ocr"), &reuse)?;
+    let reused = reuse.reused_count();
+    if reused > 0 {
+        eprintln!("quietlibrary: pdf cache reused={} of {} pages file={}", reused, reuse.hashes.len(), path.to_string_lossy());
+    }
+    store_cache_file(path, &cache_path, &doc, fingerprint, reuse.hashes);
     // Trim again after writing to enforce budget eagerly
     maybe_prune_cache(cache_dir).ok();
-    Ok((title, pages, which))
+    Ok(doc.with_page_limit(max_pages))
+}
+
+// The cached extraction of `path` when it's current, without extracting anything.
+pub fn cached_pdf_document(path: &Path, cache_dir: &Path) -> Option<PdfDocument> {
+    let fingerprint = file_fingerprint(path).ok()?;
+    load_cache_file(&cache_path(cache_dir, path)).filter(|c| (c.mtime_secs, c.size) == fingerprint).map(PdfCacheFile::into_document)
+}
+
+// Text of one page (1-based), "" for a blank one, from the extraction cache when it's current.
+// Otherwise the whole file is extracted (and cached) first, so the first page asked for from a
+// changed or uncached long PDF costs as much as indexing it.
+pub fn pdf_page_text_cached(path: &Path, cache_dir: &Path, page: u32) -> Result<String, QuietError> {
+    let doc = extract_pdf_document_cached(path, cache_dir, u32::MAX)?;
+    if page == 0 || page as usize > doc.total_pages {
+        return Err(QuietError::InvalidInput(format!("page {} out of range: {} has {} pages", page, path.to_string_lossy(), doc.total_pages)));
+    }
+    // Pages with text are stored in order from 1, so page n is at n - 1 unless a blank page
+    // came before it
+    match doc.pages.get(page as usize - 1) {
+        Some((n, text)) if *n == page => Ok(text.clone()),
+        _ => Ok(doc.pages.iter().find(|(n, _)| *n == page).map(|(_, text)| text.clone()).unwrap_or_default()),
+    }
+}
+
+fn cache_owner(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+// Delete the cached extraction of `path`, plus any other entry recording it as its owner (e.g.
+// written before the file was moved back, or under an older key). Returns how many were removed.
+pub fn remove_cached_document(path: &Path, cache_dir: &Path) -> Result<usize, String> {
+    #[derive(Deserialize)]
+    struct Owner {
+        #[serde(default)]
+        path: String,
+    }
+    let keyed = cache_path(cache_dir, path);
+    let mut removed = 0;
+    if keyed.exists() {
+        fs::remove_file(&keyed).map_err(|e| e.to_string())?;
+        removed += 1;
+    }
+    if !cache_dir.exists() { return Ok(removed); }
+    let owner = cache_owner(path);
+    for entry in fs::read_dir(cache_dir).map_err(|e| e.to_string())?.flatten() {
+        let p = entry.path();
+        let name = p.file_name().and_then(|s| s.to_str()).unwrap_or("");
+        if !name.starts_with("pdf_") || !name.ends_with(".json") { continue; }
+        let stored = fs::read(&p).ok().and_then(|b| serde_json::from_slice::<Owner>(&b).ok());
+        if stored.is_some_and(|o| o.path == owner) && fs::remove_file(&p).is_ok() { removed += 1; }
+    }
+    Ok(removed)
 }
 
 // ---------------- Cache maintenance (LRU-ish) -----------------
@@ -333,6 +681,172 @@ mod tests {
         assert!(parts[1].contains("New para line"));
     }
 
+    #[test]
+    fn test_parse_pdf_date() {
+        assert_eq!(parse_pdf_date("D:20210314092653+01'00'").as_deref(), Some("2021-03-14"));
+        assert_eq!(parse_pdf_date("D:199907").as_deref(), Some("1999-07"));
+        assert_eq!(parse_pdf_date("2004").as_deref(), Some("2004"));
+        assert_eq!(parse_pdf_date("D:"), None);
+    }
+
+    #[test]
+    fn test_decode_pdf_string_utf16() {
+        let bytes = [0xFE, 0xFF, 0x00, b'K', 0x00, 0x6E, 0x00, 0xFC, 0x00, b'h'];
+        assert_eq!(decode_pdf_string(&bytes), "Knüh");
+        assert_eq!(decode_pdf_string(b"Donald Knuth"), "Donald Knuth");
+    }
+
+    #[test]
+    fn test_lopdf_reads_info_dictionary() {
+        use lopdf::dictionary;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("meta.pdf");
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let content = doc.add_object(lopdf::Stream::new(dictionary! {}, b"BT ET".to_vec()));
+        let page_id = doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => content });
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => vec![page_id.into()], "Count" => 1 }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("The Art of Computer Programming"),
+            "Author" => Object::string_literal("Donald Knuth"),
+            "CreationDate" => Object::string_literal("D:19680101000000Z"),
+        });
+        doc.trailer.set("Root", catalog);
+        doc.trailer.set("Info", info);
+        doc.save(&path).unwrap();
+
+        let (info, _pages, _) = extract_with_lopdf(&path).unwrap();
+        assert_eq!(info.title.as_deref(), Some("The Art of Computer Programming"));
+        assert_eq!(info.author.as_deref(), Some("Donald Knuth"));
+        assert_eq!(info.created.as_deref(), Some("1968-01-01"));
+        assert_eq!(display_title(&PdfInfo::default(), &path), "meta.pdf");
+    }
+
+    // A PDF whose pages show the given strings, one page each.
+    fn write_pdf(path: &Path, pages: &[&str]) {
+        use lopdf::dictionary;
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let kids: Vec<Object> = pages
+            .iter()
+            .map(|text| {
+                let content = doc.add_object(lopdf::Stream::new(dictionary! {}, format!("BT ({}) Tj ET", text).into_bytes()));
+                doc.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id, "Contents" => content }).into()
+            })
+            .collect();
+        let count = kids.len() as i64;
+        doc.objects.insert(pages_id, Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }));
+        let catalog = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog);
+        doc.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_unchanged_pages_are_reused_after_edit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.pdf");
+        write_pdf(&path, &["alpha", "beta", "gamma"]);
+        let old = PdfCacheFile {
+            version: PDF_CACHE_VERSION,
+            path: cache_owner(&path),
+            title: "book.pdf".into(),
+            info: PdfInfo::default(),
+            which: "pdfium".into(),
+            mtime_secs: 0,
+            size: 0,
+            page_hashes: page_content_hashes(&path),
+            // Page 3 was empty when extracted
+            pages: vec![(1, "cached alpha".into()), (2, "cached beta".into())],
+            page_count: 3,
+        };
+        // Edit page 2 and insert a page before the last one
+        write_pdf(&path, &["alpha", "BETA", "new", "gamma"]);
+        let reuse = PageReuse::new(page_content_hashes(&path), Some(&old));
+        assert_eq!(reuse.text_for(0), Some("cached alpha"));
+        assert_eq!(reuse.text_for(1), None);
+        assert_eq!(reuse.text_for(2), None);
+        assert_eq!(reuse.text_for(3), Some(""));
+        assert_eq!(reuse.reused_count(), 2);
+        // lopdf text is cheap to redo, so it is never carried over
+        let lopdf = PdfCacheFile { which: "lopdf".into(), ..old };
+        assert_eq!(PageReuse::new(page_content_hashes(&path), Some(&lopdf)).reused_count(), 0);
+    }
+
+    #[test]
+    fn test_cache_is_keyed_by_path_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let path = dir.path().join("notes.pdf");
+        write_pdf(&path, &["first page"]);
+        let doc = extract_pdf_document_cached(&path, &cache, 10).unwrap();
+        assert_eq!(doc.total_pages, 1);
+        let cached = load_cache_file(&cache_path(&cache, &path)).unwrap();
+        assert_eq!(cached.page_hashes.len(), 1);
+        // Another file's entry stays; a stale copy of this file's entry under another name goes
+        let other = dir.path().join("other.pdf");
+        write_pdf(&other, &["second file"]);
+        extract_pdf_document_cached(&other, &cache, 10).unwrap();
+        fs::copy(cache_path(&cache, &path), cache.join("pdf_00000000deadbeef.json")).unwrap();
+        assert_eq!(remove_cached_document(&path, &cache).unwrap(), 2);
+        assert!(!cache_path(&cache, &path).exists());
+        assert!(!cache.join("pdf_00000000deadbeef.json").exists());
+        assert!(cache_path(&cache, &other).exists());
+    }
+
+    #[test]
+    fn test_page_limit_counts_blank_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let path = dir.path().join("scan.pdf");
+        write_pdf(&path, &["", "second page", "third page", "fourth page"]);
+        let doc = extract_pdf_document_cached(&path, &cache, 2).unwrap();
+        let pages: Vec<u32> = doc.pages.iter().map(|(page, _)| *page).collect();
+        assert_eq!(pages, [2]);
+        assert_eq!((doc.last_page, doc.total_pages), (2, 4));
+        assert!(doc.is_truncated());
+        // From the cache, and without a limit
+        let doc = extract_pdf_document_cached(&path, &cache, u32::MAX).unwrap();
+        assert_eq!((doc.pages.len(), doc.last_page, doc.total_pages), (3, 4, 4));
+        assert!(!doc.is_truncated());
+    }
+
+    #[test]
+    fn test_page_text_read_from_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let path = dir.path().join("book.pdf");
+        write_pdf(&path, &["first page", "second page", "third page"]);
+        assert!(pdf_page_text_cached(&path, &cache, 2).unwrap().contains("second page"));
+        // Served from the cache entry, not the file
+        let mut cached = load_cache_file(&cache_path(&cache, &path)).unwrap();
+        cached.pages[1].1 = "cached text".into();
+        fs::write(cache_path(&cache, &path), serde_json::to_vec(&cached).unwrap()).unwrap();
+        assert_eq!(pdf_page_text_cached(&path, &cache, 2).unwrap(), "cached text");
+        for page in [0, 4] { assert!(matches!(pdf_page_text_cached(&path, &cache, page), Err(QuietError::InvalidInput(_)))); }
+    }
+
+    #[test]
+    fn test_blank_page_text_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let path = dir.path().join("gap.pdf");
+        write_pdf(&path, &["first page", "", "third page"]);
+        assert_eq!(pdf_page_text_cached(&path, &cache, 2).unwrap(), "");
+        assert!(pdf_page_text_cached(&path, &cache, 3).unwrap().contains("third page"));
+        assert!(matches!(pdf_page_text_cached(&path, &cache, 4), Err(QuietError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_pdf_password_is_remembered_until_cleared() {
+        let path = Path::new("/lib/locked-statement.pdf");
+        assert_eq!(pdf_password(path), None);
+        set_pdf_password(path, "hunter2");
+        assert_eq!(pdf_password(path).as_deref(), Some("hunter2"));
+        set_pdf_password(path, "");
+        assert_eq!(pdf_password(path), None);
+    }
+
     #[test]
     fn test_bytes_to_text_latin_fallback() {
         // invalid UTF-8, should not panic
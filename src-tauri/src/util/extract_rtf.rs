@@ -0,0 +1,182 @@
+use encoding_rs::{Encoding, WINDOWS_1252};
+
+// Groups starting with one of these hold formatting, metadata or embedded objects rather than
+// document text. Any destination marked `\*` is skipped too.
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "fonttbl", "colortbl", "stylesheet", "info", "pict", "object", "header", "headerl", "headerr", "headerf",
+    "footer", "footerl", "footerr", "footerf", "listtable", "listoverridetable", "rsidtbl", "generator",
+    "xmlnstbl", "themedata", "colorschememapping", "datastore", "latentstyles", "filetbl", "revtbl", "fldinst",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Dest {
+    Text,
+    Title,
+    Skip,
+}
+
+#[derive(Clone, Copy)]
+struct Group {
+    dest: Dest,
+    // Fallback characters following each `\uN`
+    uc: usize,
+}
+
+struct RtfReader {
+    text: String,
+    title: String,
+    encoding: &'static Encoding,
+    // `\'xx` bytes not yet decoded; a multi-byte code page character spans several escapes
+    pending: Vec<u8>,
+    // Fallback characters of the last `\uN` still to drop
+    skip: usize,
+}
+
+impl RtfReader {
+    fn flush(&mut self, dest: Dest) {
+        if self.pending.is_empty() { return; }
+        let (decoded, _, _) = self.encoding.decode(&self.pending);
+        let decoded = decoded.into_owned();
+        self.pending.clear();
+        self.push(dest, &decoded);
+    }
+
+    fn push(&mut self, dest: Dest, s: &str) {
+        match dest {
+            Dest::Text => self.text.push_str(s),
+            Dest::Title => self.title.push_str(s),
+            Dest::Skip => {}
+        }
+    }
+}
+
+// Plain text and `\title` of an RTF document. Control words and skipped destinations (font
+// tables, pictures, ...) are dropped; paragraph breaks become newlines. `\'xx` escapes are
+// decoded with the document's `\ansicpg` code page (Windows-1252 when absent or unknown) and
+// `\uN` escapes as Unicode, dropping their fallback characters. Unbalanced braces, e.g. from a
+// file cut short by the read limit, are tolerated.
+pub fn rtf_to_text(raw: &str) -> (Option<String>, String) {
+    let mut reader = RtfReader { text: String::new(), title: String::new(), encoding: WINDOWS_1252, pending: Vec::new(), skip: 0 };
+    let mut stack: Vec<Group> = Vec::new();
+    let mut group = Group { dest: Dest::Text, uc: 1 };
+    // No text or control word seen yet in the current group, so a destination word may follow
+    let mut group_start = false;
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                reader.flush(group.dest);
+                stack.push(group);
+                group_start = true;
+                continue;
+            }
+            '}' => {
+                reader.flush(group.dest);
+                group = stack.pop().unwrap_or(Group { dest: Dest::Text, uc: 1 });
+                reader.skip = 0;
+            }
+            '\r' | '\n' => continue,
+            '\\' => match chars.next() {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    let mut word = String::from(c);
+                    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphabetic()) { word.push(c); chars.next(); }
+                    let mut digits = String::new();
+                    if chars.peek() == Some(&'-') { digits.push('-'); chars.next(); }
+                    while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) { digits.push(c); chars.next(); }
+                    let param: Option<i64> = digits.parse().ok();
+                    // A space ends the control word and is part of it
+                    if chars.peek() == Some(&' ') { chars.next(); }
+                    if group_start {
+                        // `\title` sits inside the skipped `\info` group
+                        if word == "title" { group.dest = Dest::Title; }
+                        else if SKIPPED_DESTINATIONS.contains(&word.as_str()) { group.dest = Dest::Skip; }
+                    }
+                    match word.as_str() {
+                        "bin" => {
+                            // Raw binary data of the given length
+                            for _ in 0..param.unwrap_or(0).max(0) { chars.next(); }
+                        }
+                        "u" => {
+                            reader.flush(group.dest);
+                            let code = param.unwrap_or(0);
+                            let code = if code < 0 { code + 65536 } else { code };
+                            if let Some(ch) = u32::try_from(code).ok().and_then(char::from_u32) { reader.push(group.dest, ch.encode_utf8(&mut [0; 4])); }
+                            reader.skip = group.uc;
+                        }
+                        "uc" => group.uc = param.unwrap_or(1).max(0) as usize,
+                        "ansicpg" => {
+                            let label = format!("windows-{}", param.unwrap_or(1252));
+                            reader.encoding = Encoding::for_label(label.as_bytes()).unwrap_or(WINDOWS_1252);
+                        }
+                        word => {
+                            let emitted = match word {
+                                "par" | "line" | "sect" | "page" | "row" => "\n",
+                                "tab" => "\t",
+                                "cell" => " ",
+                                "emdash" => "\u{2014}",
+                                "endash" => "\u{2013}",
+                                "bullet" => "\u{2022}",
+                                "lquote" => "\u{2018}",
+                                "rquote" => "\u{2019}",
+                                "ldblquote" => "\u{201C}",
+                                "rdblquote" => "\u{201D}",
+                                _ => "",
+                            };
+                            if !emitted.is_empty() {
+                                reader.flush(group.dest);
+                                reader.push(group.dest, emitted);
+                            }
+                        }
+                    }
+                }
+                Some('\'') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    if reader.skip > 0 { reader.skip -= 1; }
+                    else if let Ok(byte) = u8::from_str_radix(&hex, 16) { reader.pending.push(byte); }
+                }
+                Some('*') => {
+                    if group_start { group.dest = Dest::Skip; }
+                    continue;
+                }
+                Some('\r' | '\n') => { reader.flush(group.dest); reader.push(group.dest, "\n"); }
+                Some('~') => { reader.flush(group.dest); reader.push(group.dest, "\u{00A0}"); }
+                Some('_') => { reader.flush(group.dest); reader.push(group.dest, "-"); }
+                Some(c @ ('\\' | '{' | '}')) => { reader.flush(group.dest); reader.push(group.dest, c.encode_utf8(&mut [0; 4])); }
+                // `\-` (optional hyphen) and unknown control symbols
+                _ => {}
+            },
+            c => {
+                if reader.skip > 0 { reader.skip -= 1; }
+                else { reader.flush(group.dest); reader.push(group.dest, c.encode_utf8(&mut [0; 4])); }
+            }
+        }
+        group_start = false;
+    }
+    reader.flush(group.dest);
+    let title = reader.title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (Some(title).filter(|t| !t.is_empty()), reader.text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtf_text_title_and_escapes() {
+        let rtf = r"{\rtf1\ansi\ansicpg1252\deff0{\fonttbl{\f0\fswiss Helvetica;}}{\colortbl;\red255\green0\blue0;}
+{\info{\title Menu du caf\'e9}{\author Anne}}
+{\*\generator Writer 1.0;}\f0\pard Un {\b caf\'e9} cr\u232\'e8me\par
+Prix\tab 3\'80 \{net\}\par}";
+        let (title, text) = rtf_to_text(rtf);
+        assert_eq!(title.as_deref(), Some("Menu du café"));
+        assert_eq!(text, "Un café crème\nPrix\t3€ {net}\n");
+    }
+
+    #[test]
+    fn test_rtf_unicode_fallback_and_unbalanced_groups() {
+        // \uc2 drops two fallback characters; the missing closing braces are ignored
+        let (title, text) = rtf_to_text(r"{\rtf1{\uc2 \u26085??\u26412??} text {\b cut");
+        assert_eq!(title, None);
+        assert_eq!(text, "日本 text cut");
+    }
+}
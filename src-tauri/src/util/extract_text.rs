@@ -1,16 +1,60 @@
 use std::{fs, io::Read, path::Path};
-use pulldown_cmark::{Event, Options, Parser};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use super::extract_zip::MAX_ARCHIVE_TEXT_BYTES;
+
+// Default cap on the bytes read from one text file
+pub const DEFAULT_MAX_TEXT_BYTES: usize = 2 * 1024 * 1024;
 
 fn read_prefix(path: &Path, max_bytes: usize) -> Result<String, String> {
     let mut f = fs::File::open(path).map_err(|e| e.to_string())?;
-    let mut buf = Vec::with_capacity(max_bytes);
+    let len = f.metadata().map(|m| m.len()).unwrap_or(0);
+    if len > max_bytes as u64 {
+        eprintln!("quietlibrary: truncated file={} read={} of {} bytes (raise maxTextBytes to index the rest)", path.to_string_lossy(), max_bytes, len);
+    }
+    let mut buf = Vec::with_capacity(len.min(max_bytes as u64) as usize);
     let _ = (&mut f).take(max_bytes as u64).read_to_end(&mut buf);
-    // Try UTF-8; fall back to lossily decoding using the original bytes
-    let text = String::from_utf8(buf).unwrap_or_else(|e| {
-        let bytes = e.into_bytes();
-        String::from_utf8_lossy(&bytes).into_owned()
-    });
-    Ok(text)
+    Ok(decode_text(buf))
+}
+
+// UTF-8 when the bytes are valid UTF-8 (ignoring a character cut off by the prefix limit).
+// Otherwise the encoding is guessed (Windows-1252, Latin-1, Shift_JIS, ...). When the guess is
+// inconclusive (not confident, or the result contains control characters, i.e. binary data)
+// the bytes are decoded as lossy UTF-8.
+pub fn decode_text(buf: Vec<u8>) -> String {
+    if let Some(text) = decode_utf16_bom(&buf) { return text; }
+    let buf = match String::from_utf8(buf) {
+        Ok(text) => return text,
+        Err(e) if e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            return String::from_utf8(bytes).unwrap_or_default();
+        }
+        Err(e) => e.into_bytes(),
+    };
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(&buf, true);
+    let (encoding, confident) = detector.guess_assess(None, false);
+    if confident {
+        let (text, _, had_errors) = encoding.decode(&buf);
+        let binary = text.chars().any(|c| c.is_control() && !c.is_whitespace());
+        if !had_errors && !binary { return text.into_owned(); }
+    }
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+// UTF-16 text starting with a byte order mark (FF FE little endian, FE FF big endian). A
+// trailing odd byte or an unpaired high surrogate, left by the prefix limit, is dropped.
+fn decode_utf16_bom(buf: &[u8]) -> Option<String> {
+    let from_bytes: fn([u8; 2]) -> u16 = match buf {
+        [0xFF, 0xFE, ..] => u16::from_le_bytes,
+        [0xFE, 0xFF, ..] => u16::from_be_bytes,
+        _ => return None,
+    };
+    let mut units: Vec<u16> = buf[2..].chunks_exact(2).map(|c| from_bytes([c[0], c[1]])).collect();
+    if units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) { units.pop(); }
+    Some(String::from_utf16_lossy(&units))
 }
 
 fn markdown_to_text(input: &str) -> String {
@@ -39,12 +83,190 @@ fn normalize_ws(s: &str) -> String {
     out.trim().to_string()
 }
 
-pub fn extract_title_and_text(path: &Path) -> Result<(String, String), String> {
-    let max_bytes = 2 * 1024 * 1024; // 2MB cap for MVP
-    let raw = read_prefix(path, max_bytes)?;
+// At most MAX_ARCHIVE_TEXT_BYTES of one member: a crafted document can inflate to far more
+// than its size on disk.
+fn read_zip_entry(archive: &mut zip::ZipArchive<fs::File>, name: &str) -> Option<String> {
+    let entry = archive.by_name(name).ok()?;
+    let mut buf = Vec::new();
+    entry.take(MAX_ARCHIVE_TEXT_BYTES).read_to_end(&mut buf).ok()?;
+    Some(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn xml_unescape(s: &str) -> String {
+    if !s.contains('&') { return s.to_string(); }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let semi = match rest.find(';') { Some(i) if i <= 10 => i, _ => { out.push('&'); rest = &rest[1..]; continue; } };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(ch) => { out.push(ch); rest = &rest[semi + 1..]; }
+            None => { out.push('&'); rest = &rest[1..]; }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// Text content of the first <tag>...</tag> element, e.g. dc:title in docProps/core.xml.
+fn xml_element_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}", tag);
+    let start = xml.find(&open)?;
+    let after = &xml[start + open.len()..];
+    if !after.starts_with('>') && !after.starts_with(' ') { return None; }
+    let body_start = after.find('>')? + 1;
+    let end = after.find(&format!("</{}>", tag))?;
+    if end < body_start { return None; }
+    Some(xml_unescape(after[body_start..end].trim()))
+}
+
+// Concatenate <w:t> runs; paragraphs become blank-line separated so snippets split on them.
+// Stops once past `max_bytes`.
+fn docx_document_text(xml: &str, max_bytes: usize) -> String {
+    let mut out = String::new();
+    let mut rest = xml;
+    while let Some(lt) = rest.find('<') {
+        let gt = match rest[lt..].find('>') { Some(i) => lt + i, None => break };
+        let tag = &rest[lt + 1..gt];
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        rest = &rest[gt + 1..];
+        match name {
+            "w:t" if !tag.ends_with('/') => {
+                let end = rest.find("</w:t>").unwrap_or(rest.len());
+                out.push_str(&xml_unescape(&rest[..end]));
+                rest = &rest[end..];
+            }
+            "w:tab" => out.push(' '),
+            "w:br" | "w:cr" => out.push('\n'),
+            "" if tag == "/w:p" => out.push_str("\n\n"),
+            _ => {}
+        }
+        if out.len() > max_bytes { break; }
+    }
+    out
+}
+
+// DOCX is a zip, so it can't be prefix-read; the whole file is opened and the extracted plain
+// text is capped at `max_bytes` instead.
+fn extract_docx(path: &Path, name: &str, max_bytes: usize) -> Result<(String, String), String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let xml = read_zip_entry(&mut archive, "word/document.xml").ok_or_else(|| "docx: missing word/document.xml".to_string())?;
+    let raw = docx_document_text(&xml, max_bytes);
+    if raw.len() > max_bytes {
+        eprintln!("quietlibrary: truncated file={} text={} bytes (raise maxTextBytes to index the rest)", path.to_string_lossy(), max_bytes);
+    }
+    let mut text = raw.trim().to_string();
+    truncate_text(&mut text, max_bytes);
+    let title = read_zip_entry(&mut archive, "docProps/core.xml")
+        .and_then(|core| xml_element_text(&core, "dc:title"))
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| name.to_string());
+    Ok((title, text))
+}
+
+fn truncate_text(text: &mut String, max_bytes: usize) {
+    if text.len() <= max_bytes { return; }
+    let mut end = max_bytes;
+    while end > 0 && !text.is_char_boundary(end) { end -= 1; }
+    text.truncate(end);
+}
+
+// Text of the <text:p> paragraphs and <text:h> headings (spans included) in an ODT's
+// content.xml, blank-line separated like DOCX. Stops once past `max_bytes`.
+fn odt_document_text(xml: &str, max_bytes: usize) -> String {
+    let mut out = String::new();
+    // Styles and declarations come before the body and hold no text
+    let mut rest = xml.find("<office:body").map_or(xml, |i| &xml[i..]);
+    // Open paragraphs; a note inside a paragraph nests another
+    let mut depth = 0usize;
+    while let Some(lt) = rest.find('<') {
+        if depth > 0 { out.push_str(&xml_unescape(&rest[..lt])); }
+        let gt = match rest[lt..].find('>') { Some(i) => lt + i, None => break };
+        let tag = &rest[lt + 1..gt];
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        rest = &rest[gt + 1..];
+        match name {
+            "text:p" | "text:h" if !tag.ends_with('/') => depth += 1,
+            "" if (tag == "/text:p" || tag == "/text:h") && depth > 0 => {
+                depth -= 1;
+                if depth == 0 { out.push_str("\n\n"); }
+            }
+            // <text:s text:c="3"/> stands for that many spaces
+            "text:s" => {
+                let count = tag.split("text:c=\"").nth(1).and_then(|c| c.split('"').next()).and_then(|c| c.parse().ok()).unwrap_or(1usize);
+                out.push_str(&" ".repeat(count.min(100)));
+            }
+            "text:tab" => out.push(' '),
+            "text:line-break" => out.push('\n'),
+            _ => {}
+        }
+        if out.len() > max_bytes { break; }
+    }
+    out
+}
+
+// ODT is a zip like DOCX: text from content.xml, capped at `max_bytes`, and the title from
+// meta.xml.
+fn extract_odt(path: &Path, name: &str, max_bytes: usize) -> Result<(String, String), String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let xml = read_zip_entry(&mut archive, "content.xml").ok_or_else(|| "odt: missing content.xml".to_string())?;
+    let mut text = odt_document_text(&xml, max_bytes);
+    truncate_text(&mut text, max_bytes);
+    let text = text.trim().to_string();
+    let title = read_zip_entry(&mut archive, "meta.xml")
+        .and_then(|meta| xml_element_text(&meta, "dc:title"))
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| name.to_string());
+    Ok((title, text))
+}
+
+// Whether extracting `path` with `max_bytes` (giving `text_len` bytes of text) left some out:
+// the file is longer for formats read as a prefix, the text reached its cap for zipped ones.
+pub fn text_truncated(path: &Path, max_bytes: usize, text_len: usize) -> bool {
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "docx" | "odt" => text_len >= max_bytes,
+        _ => fs::metadata(path).is_ok_and(|m| m.len() > max_bytes as u64),
+    }
+}
+
+// Reads at most `max_bytes` of the file (see Settings::text_limit).
+pub fn extract_title_and_text(path: &Path, max_bytes: usize) -> Result<(String, String), String> {
     let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string();
     let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
-    if ext == "html" || ext == "htm" {
+    if ext == "docx" { return extract_docx(path, &name, max_bytes); }
+    if ext == "odt" { return extract_odt(path, &name, max_bytes); }
+    let raw = read_prefix(path, max_bytes)?;
+    if let Some(extracted) = plain_title_and_text(&name, &raw) { return Ok(extracted); }
+    // Code and config files (user-added extensions): the first line is rarely a title
+    let stem = path.file_stem().and_then(|s| s.to_str()).filter(|s| !s.is_empty()).unwrap_or(&name).to_string();
+    Ok((stem, normalize_ws(&raw)))
+}
+
+// Title and text of a plain text, Markdown, HTML or RTF file named `name` (which gives the
+// format and the fallback title) read as `raw`; None for other formats.
+pub fn plain_title_and_text(name: &str, raw: &str) -> Option<(String, String)> {
+    let ext = Path::new(name).extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    if ext == "rtf" {
+        let (title, text) = super::extract_rtf::rtf_to_text(raw);
+        Some((title.unwrap_or_else(|| name.to_string()), normalize_ws(&text)))
+    } else if ext == "html" || ext == "htm" {
         let text = html2text::from_read(raw.as_bytes(), 80);
         // naive <title> extraction
         let title = raw
@@ -52,29 +274,171 @@ pub fn extract_title_and_text(path: &Path) -> Result<(String, String), String> {
             .find("<title>")
             .and_then(|start| raw[start + 7..].find("</title>").map(|end| raw[start + 7..start + 7 + end].trim().to_string()))
             .filter(|t| !t.is_empty())
-            .unwrap_or_else(|| name.clone());
-        Ok((title, text))
-    } else if ext == "md" || ext == "markdown" {
-        let text = markdown_to_text(&raw);
-        let title = raw
-            .lines()
-            .map(|l| l.trim())
-            .find(|l| l.starts_with('#'))
-            .map(|l| l.trim_start_matches('#').trim().to_string())
-            .filter(|t| !t.is_empty())
-            .unwrap_or_else(|| name.clone());
-        Ok((title, text))
+            .unwrap_or_else(|| name.to_string());
+        Some((title, text))
+    } else if is_markdown(&ext) {
+        Some((markdown_title(raw, name), markdown_to_text(raw)))
+    } else if ext == "txt" {
+        let title = raw.lines().next().map(|l| l.trim().to_string()).filter(|t| !t.is_empty()).unwrap_or_else(|| name.to_string());
+        Some((title, normalize_ws(raw)))
     } else {
-        // treat as plain text
-        let title = raw.lines().next().map(|l| l.trim().to_string()).filter(|t| !t.is_empty()).unwrap_or_else(|| name.clone());
-        Ok((title, normalize_ws(&raw)))
+        None
+    }
+}
+
+// (heading, text) pairs of one document, in source order
+pub type Sections = Vec<(Option<String>, String)>;
+
+// Like `extract_title_and_text`, but Markdown comes back split into (heading, text) sections
+// (see `markdown_sections`); any other file is one section without a heading.
+pub fn extract_title_and_sections(path: &Path, max_bytes: usize) -> Result<(String, Sections), String> {
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    if !is_markdown(&ext) {
+        let (title, text) = extract_title_and_text(path, max_bytes)?;
+        return Ok((title, vec![(None, text)]));
+    }
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("").to_string();
+    let raw = read_prefix(path, max_bytes)?;
+    Ok((markdown_title(&raw, &name), markdown_sections(&raw)))
+}
+
+fn is_markdown(ext: &str) -> bool { ext == "md" || ext == "markdown" }
+
+// The first `#` line, else the file name
+fn markdown_title(raw: &str, name: &str) -> String {
+    raw.lines()
+        .map(|l| l.trim())
+        .find(|l| l.starts_with('#'))
+        .map(|l| l.trim_start_matches('#').trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| name.to_string())
+}
+
+// Headings up to this level (`#` and `##`) start a section; deeper ones stay in their section's text
+const SECTION_MAX_LEVEL: u32 = 2;
+
+// Markdown text split at each `#`/`##` heading, paired with the heading it falls under, in
+// source order. Text before the first heading has no heading. Each section's text starts with
+// the heading itself; unlike `markdown_to_text`, blocks are kept apart by blank lines so
+// snippets split at paragraphs. Blank text before the first heading is dropped.
+pub fn markdown_sections(input: &str) -> Sections {
+    let mut out = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut text = String::new();
+    // Text of a section heading being read
+    let mut pending: Option<String> = None;
+    for event in Parser::new_ext(input, Options::empty()) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) if level as u32 <= SECTION_MAX_LEVEL => {
+                if !text.trim().is_empty() { out.push((heading.take(), std::mem::take(&mut text))); }
+                text.clear();
+                pending = Some(String::new());
+            }
+            Event::Text(t) | Event::Code(t) => {
+                if let Some(h) = pending.as_mut() { h.push_str(&t); }
+                text.push_str(&t);
+            }
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(h) = pending.take() { heading = Some(normalize_ws(&h)).filter(|h| !h.is_empty()); }
+                text.push_str("\n\n");
+            }
+            Event::End(TagEnd::Paragraph | TagEnd::Item | TagEnd::CodeBlock) => text.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    if !text.trim().is_empty() { out.push((heading, text)); }
+    out.into_iter().map(|(h, t)| (h, t.trim().to_string())).collect()
+}
+
+// Collect (level, heading text, 1-based line) for every Markdown heading in source order.
+fn markdown_headings(input: &str) -> Vec<(u32, String, u32)> {
+    let mut out = Vec::new();
+    let mut current: Option<(u32, String, u32)> = None;
+    for (event, range) in Parser::new_ext(input, Options::empty()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let line = input[..range.start].matches('\n').count() as u32 + 1;
+                current = Some((level as u32, String::new(), line));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, title, _)) = current.as_mut() { title.push_str(&text); }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, title, line)) = current.take() {
+                    let title = normalize_ws(&title);
+                    if !title.is_empty() { out.push((level, title, line)); }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+// Naive <h1>..<h6> scan, in the same spirit as the <title> extraction above.
+fn html_headings(raw: &str) -> Vec<(u32, String, u32)> {
+    // ASCII lowercasing keeps byte offsets aligned with `raw`
+    let lower = raw.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while let Some(rel) = lower[pos..].find("<h") {
+        let start = pos + rel;
+        pos = start + 2;
+        let level = match bytes.get(start + 2) { Some(b @ b'1'..=b'6') => (b - b'0') as u32, _ => continue };
+        if !matches!(bytes.get(start + 3), Some(b'>') | Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r')) { continue; }
+        let open_end = match lower[start..].find('>') { Some(i) => start + i + 1, None => break };
+        let close = format!("</h{}", level);
+        let close_start = match lower[open_end..].find(&close) { Some(i) => open_end + i, None => break };
+        // Drop inline markup, then let html2text decode entities
+        let inner = strip_tags(&raw[open_end..close_start]);
+        let title = normalize_ws(&html2text::from_read(inner.as_bytes(), usize::MAX));
+        if !title.is_empty() {
+            let line = raw[..start].matches('\n').count() as u32 + 1;
+            out.push((level, title, line));
+        }
+        pos = close_start;
     }
+    out
+}
+
+fn strip_tags(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut in_tag = false;
+    for ch in s.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(ch),
+            _ => {}
+        }
+    }
+    out
+}
+
+// Headings of Markdown/HTML documents as (level, text, line), from the first `max_bytes`;
+// plain text has none.
+pub fn extract_headings(path: &Path, max_bytes: usize) -> Result<Vec<(u32, String, u32)>, String> {
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "md" | "markdown" => Ok(markdown_headings(&read_prefix(path, max_bytes)?)),
+        "html" | "htm" => Ok(html_headings(&read_prefix(path, max_bytes)?)),
+        _ => Ok(vec![]),
+    }
+}
+
+// Built-in text formats plus `extra_exts` (lowercase, without the dot), e.g. source code.
+pub fn is_text_file(path: &Path, extra_exts: &[String]) -> bool {
+    if is_supported_text(path) { return true; }
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    !ext.is_empty() && extra_exts.contains(&ext)
 }
 
 pub fn is_supported_text(path: &Path) -> bool {
     if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
         match ext.to_ascii_lowercase().as_str() {
-            "txt" | "md" | "markdown" | "html" | "htm" => true,
+            "txt" | "md" | "markdown" | "html" | "htm" | "docx" | "odt" | "rtf" => true,
             _ => false,
         }
     } else { false }
@@ -105,20 +469,54 @@ mod tests {
     #[test]
     fn test_is_supported_text() {
         let cases = [
-            ("a.txt", true), ("b.md", true), ("c.markdown", true), ("d.html", true), ("e.htm", true), ("f.pdf", false)
+            ("a.txt", true), ("b.md", true), ("c.markdown", true), ("d.html", true), ("e.htm", true), ("g.docx", true), ("h.RTF", true), ("i.odt", true), ("f.pdf", false)
         ];
         for (name, want) in cases {
             assert_eq!(is_supported_text(Path::new(name)), want, "{}", name);
         }
     }
 
+    #[test]
+    fn test_extract_respects_max_bytes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("long.txt");
+        std::fs::write(&path, "Head\nearly words\nlate tailword").unwrap();
+        let (_, text) = extract_title_and_text(&path, 16).unwrap();
+        assert!(text.contains("early"));
+        assert!(!text.contains("tailword"));
+        let (_, text) = extract_title_and_text(&path, usize::MAX).unwrap();
+        assert!(text.contains("tailword"));
+    }
+
+    #[test]
+    fn test_extract_rtf() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("menu.rtf");
+        std::fs::write(&path, r"{\rtf1\ansi{\fonttbl{\f0 Times;}}{\info{\title Carte}}\f0 Un caf\'e9 {\i noir}\par Merci}").unwrap();
+        let (title, text) = extract_title_and_text(&path, usize::MAX).unwrap();
+        assert_eq!(title, "Carte");
+        assert_eq!(text, "Un café noir Merci");
+        std::fs::write(&path, r"{\rtf1 plain}").unwrap();
+        assert_eq!(extract_title_and_text(&path, usize::MAX).unwrap().0, "menu.rtf");
+    }
+
+    #[test]
+    fn test_is_text_file_extra_exts() {
+        let extra = vec!["py".to_string(), "toml".to_string()];
+        assert!(is_text_file(Path::new("a.PY"), &extra));
+        assert!(is_text_file(Path::new("Cargo.toml"), &extra));
+        assert!(is_text_file(Path::new("notes.md"), &[]));
+        assert!(!is_text_file(Path::new("a.py"), &[]));
+        assert!(!is_text_file(Path::new("Makefile"), &extra));
+    }
+
     #[test]
     fn test_extract_plain_title_and_text() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("note.txt");
         let content = "My Title\nThis is the body.";
         std::fs::write(&path, content).unwrap();
-        let (title, text) = extract_title_and_text(&path).unwrap();
+        let (title, text) = extract_title_and_text(&path, DEFAULT_MAX_TEXT_BYTES).unwrap();
         assert_eq!(title, "My Title");
         assert!(text.contains("This is the body."));
     }
@@ -129,33 +527,159 @@ mod tests {
         let path = dir.path().join("doc.md");
         let content = "# Heading\n\nParagraph text";
         std::fs::write(&path, content).unwrap();
-        let (title, text) = extract_title_and_text(&path).unwrap();
+        let (title, text) = extract_title_and_text(&path, DEFAULT_MAX_TEXT_BYTES).unwrap();
         assert_eq!(title, "Heading");
         assert!(text.contains("Heading"));
         assert!(text.contains("Paragraph text"));
     }
 
+    #[test]
+    fn test_markdown_sections_follow_top_headings() {
+        let md = "Intro line\n\n# Setup\n\nInstall it.\n\n### Details\n\nMore setup.\n\n## Usage\n\nRun `cargo` daily.\n\n## Empty\n";
+        let sections = markdown_sections(md);
+        let headings: Vec<Option<&str>> = sections.iter().map(|(h, _)| h.as_deref()).collect();
+        assert_eq!(headings, vec![None, Some("Setup"), Some("Usage"), Some("Empty")]);
+        assert_eq!(sections[0].1, "Intro line");
+        // Deeper headings stay inside the section they belong to
+        assert!(sections[1].1.starts_with("Setup") && sections[1].1.contains("Details") && sections[1].1.contains("More setup."));
+        assert!(sections[2].1.contains("Run cargo daily"));
+
+        let dir = tempdir().unwrap();
+        let txt = dir.path().join("plain.txt");
+        std::fs::write(&txt, "Title\n# not a heading").unwrap();
+        let (_, sections) = extract_title_and_sections(&txt, DEFAULT_MAX_TEXT_BYTES).unwrap();
+        assert_eq!(sections, vec![(None, "Title # not a heading".to_string())]);
+    }
+
     #[test]
     fn test_extract_html_title() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("page.html");
         let content = r#"<html><head><title>Test Title</title></head><body><p>Hello</p></body></html>"#;
         std::fs::write(&path, content).unwrap();
-        let (title, text) = extract_title_and_text(&path).unwrap();
+        let (title, text) = extract_title_and_text(&path, DEFAULT_MAX_TEXT_BYTES).unwrap();
         assert_eq!(title, "Test Title");
         assert!(text.contains("Hello"));
     }
 
+    #[test]
+    fn test_extract_docx() {
+        use std::io::Write;
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("report.docx");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let opts = zip::write::SimpleFileOptions::default();
+        zip.start_file("word/document.xml", opts).unwrap();
+        zip.write_all(br#"<w:document><w:body><w:p><w:r><w:t>Quarterly </w:t></w:r><w:r><w:t xml:space="preserve">results &amp; plans</w:t></w:r></w:p><w:p><w:r><w:t>Second paragraph</w:t></w:r></w:p></w:body></w:document>"#).unwrap();
+        zip.start_file("docProps/core.xml", opts).unwrap();
+        zip.write_all(br#"<cp:coreProperties><dc:title>Q3 Report</dc:title></cp:coreProperties>"#).unwrap();
+        zip.finish().unwrap();
+        let (title, text) = extract_title_and_text(&path, DEFAULT_MAX_TEXT_BYTES).unwrap();
+        assert_eq!(title, "Q3 Report");
+        assert_eq!(text, "Quarterly results & plans\n\nSecond paragraph");
+        // The configured limit applies as it does to other text
+        let (_, text) = extract_title_and_text(&path, 9).unwrap();
+        assert_eq!(text, "Quarterly");
+        assert!(text_truncated(&path, 9, text.len()));
+    }
+
+    #[test]
+    fn test_extract_odt() {
+        use std::io::Write;
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("letter.odt");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let opts = zip::write::SimpleFileOptions::default();
+        zip.start_file("content.xml", opts).unwrap();
+        zip.write_all(br#"<office:document-content><office:automatic-styles><style:style style:name="P1"/></office:automatic-styles><office:body><office:text><text:h text:outline-level="1">Dear caf&#233;</text:h><text:p>Thanks for <text:span text:style-name="T1">the</text:span><text:s text:c="2"/>beans.</text:p><text:p/><text:p>See you</text:p></office:text></office:body></office:document-content>"#).unwrap();
+        zip.start_file("meta.xml", opts).unwrap();
+        zip.write_all(br#"<office:document-meta><office:meta><dc:title>Letter</dc:title></office:meta></office:document-meta>"#).unwrap();
+        zip.finish().unwrap();
+        let (title, text) = extract_title_and_text(&path, DEFAULT_MAX_TEXT_BYTES).unwrap();
+        assert_eq!(title, "Letter");
+        assert_eq!(text, "Dear café\n\nThanks for the  beans.\n\nSee you");
+        // Capped at the byte limit
+        let (_, text) = extract_title_and_text(&path, 12).unwrap();
+        assert_eq!(text, "Dear café");
+    }
+
+    #[test]
+    fn test_xml_unescape() {
+        assert_eq!(xml_unescape("a &lt;b&gt; &#233;&#x41; &bogus; &"), "a <b> éA &bogus; &");
+    }
+
     #[test]
     fn test_read_prefix_lossy_non_utf8() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("bin.txt");
-        // Invalid UTF-8 sequence
-        let bytes = b"Title\n\xFF\xFE\xFA";
+        // Invalid UTF-8 in binary-looking data, which no legacy encoding explains
+        let bytes = b"Title\n\x00\x01\xFF\xFE\xFA";
         std::fs::write(&path, bytes).unwrap();
         let s = read_prefix(&path, 1024).unwrap();
         // Replacement char appears
         assert!(s.contains("Title"));
         assert!(s.chars().any(|c| c == '\u{FFFD}'));
     }
+
+    #[test]
+    fn test_read_prefix_windows_1252() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("menu.txt");
+        std::fs::write(&path, b"Le caf\xE9 est tr\xE8s bon. Cr\xE8me br\xFBl\xE9e et g\xE2teau \xE0 la carte.\n").unwrap();
+        let s = read_prefix(&path, 1024).unwrap();
+        assert!(s.contains("café"), "{}", s);
+        assert!(s.contains("crème brûlée") || s.contains("Crème brûlée"));
+        assert!(!s.contains('\u{FFFD}'));
+    }
+
+    #[test]
+    fn test_read_prefix_keeps_utf8_cut_at_limit() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("utf8.txt");
+        std::fs::write(&path, "abcé".as_bytes()).unwrap();
+        // 'é' is two bytes; the limit splits it
+        assert_eq!(read_prefix(&path, 4).unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_read_prefix_utf16_bom() {
+        let dir = tempdir().unwrap();
+        let text = "Notes\ncafé 😀";
+        let le: Vec<u8> = [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(|u| u.to_le_bytes())).collect();
+        let be: Vec<u8> = [0xFE, 0xFF].into_iter().chain(text.encode_utf16().flat_map(|u| u.to_be_bytes())).collect();
+        let (le_path, be_path) = (dir.path().join("le.txt"), dir.path().join("be.txt"));
+        std::fs::write(&le_path, &le).unwrap();
+        std::fs::write(&be_path, &be).unwrap();
+        assert_eq!(read_prefix(&le_path, 1024).unwrap(), text);
+        assert_eq!(read_prefix(&be_path, 1024).unwrap(), text);
+        // Cut inside the emoji's surrogate pair, and at an odd byte
+        assert_eq!(read_prefix(&le_path, le.len() - 2).unwrap(), "Notes\ncafé ");
+        assert_eq!(read_prefix(&le_path, le.len() - 3).unwrap(), "Notes\ncafé ");
+    }
+
+    #[test]
+    fn test_markdown_headings_levels_and_lines() {
+        let md = "# Intro\n\ntext\n\n## Setup `cargo`\n\n### Details\nmore\n";
+        let hs = markdown_headings(md);
+        assert_eq!(hs, vec![
+            (1, "Intro".to_string(), 1),
+            (2, "Setup cargo".to_string(), 5),
+            (3, "Details".to_string(), 7),
+        ]);
+    }
+
+    #[test]
+    fn test_html_headings() {
+        let html = "<html><body>\n<h1>Top</h1>\n<p>x</p>\n<H2 class=\"a\">Sub <em>part</em></H2>\n<hr>\n</body></html>";
+        let hs = html_headings(html);
+        assert_eq!(hs, vec![(1, "Top".to_string(), 2), (2, "Sub part".to_string(), 4)]);
+    }
+
+    #[test]
+    fn test_extract_headings_plain_text_is_empty() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("note.txt");
+        std::fs::write(&path, "# not a heading in plain text").unwrap();
+        assert!(extract_headings(&path, DEFAULT_MAX_TEXT_BYTES).unwrap().is_empty());
+    }
 }
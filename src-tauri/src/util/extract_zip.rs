@@ -0,0 +1,123 @@
+use std::{fs, io::Read, path::Path};
+
+use super::extract_text::{decode_text, plain_title_and_text};
+
+// Between an archive's path and a member's name in the path of an indexed member, e.g.
+// `/notes/bundle.zip!/2024/plan.md`
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+// Text read from all members of one archive together. Compressed members can inflate to far
+// more than the archive's size (zip bombs), so reading stops once this much has been read.
+pub const MAX_ARCHIVE_TEXT_BYTES: u64 = 64 * 1024 * 1024;
+
+// Member formats read from archives; DOCX, ODT and nested archives are left out
+const MEMBER_EXTS: &[&str] = &["txt", "md", "markdown", "html", "htm", "rtf"];
+
+// One text member of an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMember {
+    // Path inside the archive, e.g. `2024/plan.md`
+    pub name: String,
+    pub title: String,
+    pub text: String,
+}
+
+pub fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip"))
+}
+
+// Path under which `member` of the archive at `archive` is indexed.
+pub fn member_path(archive: &str, member: &str) -> String {
+    format!("{}{}{}", archive, MEMBER_SEPARATOR, member)
+}
+
+// The archive holding `path` when it's a member path (`bundle.zip!/plan.md`).
+pub fn archive_of(path: &str) -> Option<&str> {
+    let (archive, member) = path.split_once(MEMBER_SEPARATOR)?;
+    (!member.is_empty() && is_zip(Path::new(archive))).then_some(archive)
+}
+
+// Text, Markdown, HTML and RTF members of a ZIP archive in archive order, each read up to
+// `max_member_bytes` and extracted like the same file on disk. Directories, other formats and
+// members that look binary (a NUL byte) are skipped, and members past `max_total_bytes` of
+// text read in all are left out.
+pub fn extract_zip_members(path: &Path, max_member_bytes: usize, max_total_bytes: u64) -> Result<Vec<ArchiveMember>, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut members = Vec::new();
+    let mut budget = max_total_bytes;
+    for i in 0..archive.len() {
+        let Ok(mut entry) = archive.by_index(i) else { continue };
+        let name = entry.name().to_string();
+        let ext = Path::new(&name).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        if entry.is_dir() || !MEMBER_EXTS.contains(&ext.as_str()) { continue; }
+        if budget == 0 {
+            eprintln!("quietlibrary: archive text limit reached file={} skipped from={}", path.to_string_lossy(), name);
+            break;
+        }
+        let mut buf = Vec::new();
+        if (&mut entry).take((max_member_bytes as u64).min(budget)).read_to_end(&mut buf).is_err() { continue; }
+        budget -= buf.len() as u64;
+        if buf.contains(&0) { continue; }
+        let file_name = name.rsplit('/').next().unwrap_or(&name);
+        let Some((title, text)) = plain_title_and_text(file_name, &decode_text(buf)) else { continue };
+        members.push(ArchiveMember { name, title, text });
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, data) in entries {
+            if name.ends_with('/') { zip.add_directory(*name, SimpleFileOptions::default()).unwrap(); continue; }
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_zip_text_members_only() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("bundle.zip");
+        write_zip(&path, &[
+            ("notes/", b""),
+            ("notes/plan.md", b"# Plan\n\nship the *heron* release"),
+            ("notes/todo.txt", b"Todo\nfeed the cat"),
+            ("image.png", b"\x89PNG\r\n"),
+            ("fake.txt", b"MZ\0\0binary"),
+        ]);
+        let members = extract_zip_members(&path, 1024, MAX_ARCHIVE_TEXT_BYTES).unwrap();
+        let names: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["notes/plan.md", "notes/todo.txt"]);
+        assert_eq!(members[0].title, "Plan");
+        assert!(members[0].text.contains("ship the heron release"));
+        assert_eq!(members[1].title, "Todo");
+    }
+
+    #[test]
+    fn test_zip_total_text_is_capped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("big.zip");
+        let big = "word ".repeat(1000);
+        write_zip(&path, &[("a.txt", big.as_bytes()), ("b.txt", big.as_bytes()), ("c.txt", big.as_bytes())]);
+        let members = extract_zip_members(&path, usize::MAX, 6000).unwrap();
+        let read: usize = members.iter().map(|m| m.text.len()).sum();
+        assert_eq!(members.len(), 2);
+        assert!(read <= 6000, "{}", read);
+    }
+
+    #[test]
+    fn test_archive_of_member_path() {
+        assert_eq!(archive_of(&member_path("/docs/bundle.zip", "notes/plan.md")), Some("/docs/bundle.zip"));
+        assert_eq!(archive_of("/docs/bundle.ZIP!/a.txt"), Some("/docs/bundle.ZIP"));
+        assert_eq!(archive_of("/docs/wow!/a.txt"), None);
+        assert_eq!(archive_of("/docs/plain.md"), None);
+    }
+}
@@ -1,6 +1,14 @@
+pub mod code_tokenizer;
+pub mod extract;
 pub mod extract_pdf;
+pub mod extract_rtf;
 pub mod extract_epub;
 pub mod extract_text;
+pub mod extract_zip;
+pub mod outline;
 pub mod pdfium_loader;
+pub mod recent_list;
+pub mod retry;
 pub mod snippet;
 pub mod tantivy_index;
+pub mod watcher;
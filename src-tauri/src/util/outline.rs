@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use crate::{error::QuietError, models::OutlineEntry};
+use crate::util::{extract_pdf::extract_pdf_outline, extract_text::extract_headings};
+
+// Outline for Markdown/HTML (headings in the first `max_text_bytes`) and PDF (bookmarks).
+// Other types yield an empty outline.
+pub fn document_outline(path: &Path, max_text_bytes: usize) -> Result<Vec<OutlineEntry>, QuietError> {
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    let flat: Vec<OutlineEntry> = if ext == "pdf" {
+        // Without pdfium or the PDF's password the outline can't be read, which the UI reports;
+        // a PDF pdfium can't load or without bookmarks simply has no outline
+        let outline = match extract_pdf_outline(path) {
+            Err(e @ (QuietError::PdfiumUnavailable(_) | QuietError::PasswordRequired(_))) => return Err(e),
+            other => other.unwrap_or_default(),
+        };
+        outline
+            .into_iter()
+            .map(|(level, title, page)| OutlineEntry { title, level, line: None, page, children: vec![] })
+            .collect()
+    } else {
+        extract_headings(path, max_text_bytes).map_err(QuietError::Extraction)?
+            .into_iter()
+            .map(|(level, title, line)| OutlineEntry { title, level, line: Some(line), page: None, children: vec![] })
+            .collect()
+    };
+    Ok(build_tree(flat))
+}
+
+// Nest a flat, document-ordered list by level: each entry becomes a child of the nearest
+// preceding entry with a smaller level. Skipped levels (h1 > h3) nest directly.
+fn build_tree(flat: Vec<OutlineEntry>) -> Vec<OutlineEntry> {
+    let mut roots: Vec<OutlineEntry> = Vec::new();
+    // Stack of open entries, outermost first
+    let mut stack: Vec<OutlineEntry> = Vec::new();
+    for entry in flat {
+        while stack.last().map(|top| top.level >= entry.level).unwrap_or(false) {
+            let done = stack.pop().unwrap();
+            attach(&mut stack, &mut roots, done);
+        }
+        stack.push(entry);
+    }
+    while let Some(done) = stack.pop() {
+        attach(&mut stack, &mut roots, done);
+    }
+    roots
+}
+
+fn attach(stack: &mut [OutlineEntry], roots: &mut Vec<OutlineEntry>, entry: OutlineEntry) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(entry),
+        None => roots.push(entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::extract_text::DEFAULT_MAX_TEXT_BYTES;
+    use tempfile::tempdir;
+
+    fn entry(level: u32, title: &str) -> OutlineEntry {
+        OutlineEntry { title: title.to_string(), level, line: None, page: None, children: vec![] }
+    }
+
+    #[test]
+    fn test_build_tree_nests_levels() {
+        let flat = vec![entry(1, "A"), entry(2, "A.1"), entry(3, "A.1.a"), entry(2, "A.2"), entry(1, "B"), entry(3, "B.x")];
+        let tree = build_tree(flat);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].title, "A");
+        assert_eq!(tree[0].children.len(), 2);
+        assert_eq!(tree[0].children[0].children[0].title, "A.1.a");
+        assert_eq!(tree[0].children[1].title, "A.2");
+        // h1 > h3 without an h2 nests directly under the h1
+        assert_eq!(tree[1].children[0].title, "B.x");
+    }
+
+    #[test]
+    fn test_document_outline_markdown() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("doc.md");
+        std::fs::write(&path, "# One\n\n## Two\n\n# Three\n").unwrap();
+        let outline = document_outline(&path, DEFAULT_MAX_TEXT_BYTES).unwrap();
+        assert_eq!(outline.len(), 2);
+        assert_eq!(outline[0].children[0].title, "Two");
+        assert_eq!(outline[0].children[0].line, Some(3));
+        assert_eq!(outline[1].title, "Three");
+    }
+
+    #[test]
+    fn test_document_outline_without_headings_is_empty() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("flat.md");
+        std::fs::write(&path, "just a paragraph").unwrap();
+        assert!(document_outline(&path, DEFAULT_MAX_TEXT_BYTES).unwrap().is_empty());
+    }
+}
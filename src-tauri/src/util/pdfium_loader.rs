@@ -1,14 +1,76 @@
 use once_cell::sync::Lazy;
 use pdfium_render::prelude::*;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+
+use crate::models::PdfiumStatus;
 
 static INIT_GUARD: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 pub static PDFIUM_SOURCE: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
 pub static PDFIUM_TRIED: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static PDFIUM_ATTEMPTED: AtomicBool = AtomicBool::new(false);
+
+// A document operation for the pdfium worker, given the bound library or why binding failed
+type PdfiumJob = Box<dyn FnOnce(Result<&Pdfium, &str>) + Send>;
+
+// Queue of the running pdfium worker, if any
+static WORKER: Lazy<Mutex<Option<mpsc::Sender<PdfiumJob>>>> = Lazy::new(|| Mutex::new(None));
+
+// Run `f` with pdfium on the pdfium worker thread and wait for its result.
+//
+// Pdfium is not thread-safe: the library keeps global state, so documents loaded and read from
+// several threads at once (or through several bound instances) can crash or come back garbled.
+// One worker thread therefore owns the only `Pdfium` instance and runs document operations one
+// at a time, while extraction of every other format stays parallel. The worker binds on its
+// first job; when binding fails it answers the jobs already queued with the error and exits, so
+// the next call binds afresh (e.g. after PDFium was installed).
+pub fn with_pdfium<T: Send + 'static>(f: impl FnOnce(&Pdfium) -> T + Send + 'static) -> Result<T, String> {
+    let (tx, rx) = mpsc::channel();
+    let job: PdfiumJob = Box::new(move |pdfium| { let _ = tx.send(pdfium.map(f).map_err(str::to_string)); });
+    let sent = {
+        let mut worker = WORKER.lock().unwrap_or_else(|e| e.into_inner());
+        let sent = worker.get_or_insert_with(spawn_worker).send(job);
+        if sent.is_err() { *worker = None; }
+        sent
+    };
+    if sent.is_err() { return Err("pdfium worker stopped".to_string()); }
+    rx.recv().map_err(|_| "pdfium worker stopped".to_string())?
+}
+
+fn spawn_worker() -> mpsc::Sender<PdfiumJob> {
+    let (tx, rx) = mpsc::channel::<PdfiumJob>();
+    let spawned = std::thread::Builder::new().name("pdfium".into()).spawn(move || {
+        let pdfium = match bind_pdfium() {
+            Ok(pdfium) => pdfium,
+            Err(e) => {
+                *WORKER.lock().unwrap_or_else(|e| e.into_inner()) = None;
+                for job in rx { job(Err(&e)); }
+                return;
+            }
+        };
+        for job in rx {
+            // A panicking job loses only its own result (its caller sees the worker as stopped)
+            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(Ok(&pdfium))));
+        }
+    });
+    if let Err(e) = spawned { eprintln!("quietlibrary: failed to start pdfium worker: {}", e); }
+    tx
+}
 
-pub fn bind_pdfium() -> Result<Pdfium, String> {
+// What the last bind found, binding once first if nothing has tried yet.
+pub fn pdfium_status() -> PdfiumStatus {
+    if !PDFIUM_ATTEMPTED.load(Ordering::SeqCst) { let _ = with_pdfium(|_| ()); }
+    let source = PDFIUM_SOURCE.lock().map(|s| s.clone()).unwrap_or(None);
+    let tried = PDFIUM_TRIED.lock().map(|t| t.clone()).unwrap_or_default();
+    PdfiumStatus { loaded: source.is_some(), source, tried }
+}
+
+fn bind_pdfium() -> Result<Pdfium, String> {
     let _guard = INIT_GUARD.lock().unwrap();
+    PDFIUM_ATTEMPTED.store(true, Ordering::SeqCst);
+    // A failed bind must not report the source of an earlier success
+    *PDFIUM_SOURCE.lock().unwrap() = None;
     // Strategy: prefer app-bundled PDFium first, then PDFIUM_PATH, then system library.
     let mut candidates: Vec<PathBuf> = Vec::new();
     let mut preferred: Vec<PathBuf> = Vec::new();
@@ -112,7 +174,24 @@ pub fn bind_pdfium() -> Result<Pdfium, String> {
         Ok(bindings) => { *PDFIUM_SOURCE.lock().unwrap() = Some("system".to_string()); Ok(Pdfium::new(bindings)) },
         Err(e) => {
             eprintln!("quietlibrary: pdfium not found; tried: {:?}", tried);
+            *PDFIUM_TRIED.lock().unwrap() = preferred.iter().chain(candidates.iter()).map(|p| p.to_string_lossy().to_string()).collect();
             Err(format!("bind pdfium: {:?}", e))
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_callers_all_get_answers() {
+        // Whether or not PDFium is installed here, every caller gets the same kind of answer
+        let handles: Vec<_> = (0..8).map(|i| std::thread::spawn(move || with_pdfium(move |_| i))).collect();
+        let results: Vec<Result<i32, String>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        match &results[0] {
+            Ok(_) => assert_eq!(results.into_iter().map(|r| r.unwrap()).collect::<Vec<_>>(), (0..8).collect::<Vec<_>>()),
+            Err(_) => assert!(results.iter().all(|r| r.is_err())),
+        }
+    }
+}
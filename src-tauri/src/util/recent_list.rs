@@ -0,0 +1,49 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::QuietError;
+
+// A newest-first list of at most `cap` entries, kept as the JSON file `file` in the app dir:
+// the search history and the recent documents.
+pub struct RecentList {
+    pub file: &'static str,
+    pub cap: usize,
+}
+
+impl RecentList {
+    fn path(&self, app_dir: &Path) -> PathBuf { app_dir.join(self.file) }
+
+    // Most recent first; empty when the file is missing or unreadable
+    pub fn read<T: DeserializeOwned>(&self, app_dir: &Path) -> Vec<T> {
+        fs::read(self.path(app_dir)).ok().and_then(|b| serde_json::from_slice(&b).ok()).unwrap_or_default()
+    }
+
+    fn write<T: Serialize>(&self, app_dir: &Path, list: &[T]) -> Result<(), QuietError> {
+        fs::create_dir_all(app_dir)?;
+        let bytes = serde_json::to_vec_pretty(list)?;
+        Ok(fs::write(self.path(app_dir), bytes)?)
+    }
+
+    // `entry` first, then `all` up to the cap; older entries fall off the end.
+    pub fn push_front<T>(&self, mut all: Vec<T>, entry: T) -> Vec<T> {
+        all.insert(0, entry);
+        all.truncate(self.cap);
+        all
+    }
+
+    // Rewrite the file with `change` applied to its entries. Best effort: a failed write is
+    // logged and never fails the search or open that triggered it.
+    pub fn update<T: Serialize + DeserializeOwned>(&self, app_dir: &Path, change: impl FnOnce(Vec<T>) -> Vec<T>) {
+        let all = change(self.read(app_dir));
+        if let Err(e) = self.write(app_dir, &all) {
+            eprintln!("quietlibrary: {} not saved error={}", self.file, e);
+        }
+    }
+
+    pub fn clear(&self, app_dir: &Path) -> Result<(), QuietError> {
+        let p = self.path(app_dir);
+        if p.exists() { fs::remove_file(p)?; }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use std::{fs, io, io::Read, path::{Path, PathBuf}, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+// Retry schedule for file reads that can fail transiently (network drives, cloud-synced
+// folders whose files are still being downloaded). Delays double up to `max_backoff_ms`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self { RetryPolicy { attempts: 3, initial_backoff_ms: 200, max_backoff_ms: 2000 } }
+}
+
+// Run `op` until it succeeds, fails with a non-transient error, or attempts run out.
+// Returns the final result together with the number of attempts made.
+pub fn retry_with_backoff<T, E>(
+    policy: &RetryPolicy,
+    is_transient: impl Fn(&E) -> bool,
+    mut op: impl FnMut() -> Result<T, E>,
+) -> (Result<T, E>, u32) {
+    let attempts = policy.attempts.max(1);
+    let mut delay = policy.initial_backoff_ms;
+    let mut n = 0;
+    loop {
+        n += 1;
+        match op() {
+            Err(e) if n < attempts && is_transient(&e) => {
+                std::thread::sleep(Duration::from_millis(delay));
+                delay = (delay * 2).min(policy.max_backoff_ms);
+            }
+            res => return (res, n),
+        }
+    }
+}
+
+// Errors worth retrying: timeouts/interruptions, files locked by a sync client, and the
+// Windows cloud-files errors returned while a placeholder is being hydrated.
+pub fn is_transient_io(e: &io::Error) -> bool {
+    if matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) {
+        return true;
+    }
+    if cfg!(windows) {
+        // ERROR_SHARING_VIOLATION, ERROR_LOCK_VIOLATION, ERROR_CLOUD_FILE_* (362..=434)
+        if let Some(code) = e.raw_os_error() {
+            return code == 32 || code == 33 || (362..=434).contains(&code);
+        }
+    }
+    false
+}
+
+// Open the file and read its first bytes. For cloud placeholders this is what triggers
+// hydration, so doing it under retry gives the sync client time to fetch the content.
+pub fn probe_readable(path: &Path) -> io::Result<()> {
+    let mut f = fs::File::open(path)?;
+    let mut buf = [0u8; 512];
+    let _ = f.read(&mut buf)?;
+    Ok(())
+}
+
+// Files the OS reports as not present locally and that must not be read during indexing.
+#[cfg(windows)]
+pub fn is_offline_placeholder(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    fs::metadata(path).map(|m| m.file_attributes() & FILE_ATTRIBUTE_OFFLINE != 0).unwrap_or(false)
+}
+
+#[cfg(not(windows))]
+pub fn is_offline_placeholder(_path: &Path) -> bool { false }
+
+// iCloud keeps evicted files as `.Name.pdf.icloud` stubs; return the real file's path.
+pub fn icloud_stub_target(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let real = name.strip_prefix('.')?.strip_suffix(".icloud")?;
+    if real.is_empty() { return None; }
+    Some(path.with_file_name(real))
+}
+
+// Ask the OS to download an evicted iCloud file; it will be picked up by the next index run.
+pub fn request_hydration(path: &Path) {
+    #[cfg(target_os = "macos")]
+    {
+        let _ = std::process::Command::new("brctl").arg("download").arg(path).spawn();
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = path;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn fast() -> RetryPolicy { RetryPolicy { attempts: 4, initial_backoff_ms: 1, max_backoff_ms: 2 } }
+
+    #[test]
+    fn test_transient_failure_succeeds_on_retry() {
+        let calls = Cell::new(0);
+        let (res, attempts) = retry_with_backoff(&fast(), is_transient_io, || {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 { Err(io::Error::new(io::ErrorKind::TimedOut, "not yet downloaded")) } else { Ok("content") }
+        });
+        assert_eq!(res.unwrap(), "content");
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn test_permanent_failure_is_not_retried() {
+        let (res, attempts) = retry_with_backoff(&fast(), is_transient_io, || -> Result<(), io::Error> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "gone"))
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_gives_up_after_attempts() {
+        let (res, attempts) = retry_with_backoff(&fast(), is_transient_io, || -> Result<(), io::Error> {
+            Err(io::Error::new(io::ErrorKind::Interrupted, "flaky"))
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts, 4);
+    }
+
+    #[test]
+    fn test_icloud_stub_target() {
+        let p = Path::new("/lib/.Paper.pdf.icloud");
+        assert_eq!(icloud_stub_target(p), Some(PathBuf::from("/lib/Paper.pdf")));
+        assert_eq!(icloud_stub_target(Path::new("/lib/Paper.pdf")), None);
+    }
+}
@@ -1,37 +1,223 @@
+use std::cell::RefCell;
+
+use tantivy::tokenizer::{AsciiFoldingFilter, RawTokenizer, TextAnalyzer, TokenStream};
+
+use crate::models::SnippetParts;
+
+// Snippet length used when a search doesn't ask for one
+pub const DEFAULT_SNIPPET_LEN: usize = 400;
+
+// Snippets per document when a search doesn't say
+pub const DEFAULT_MAX_SNIPPETS_PER_DOC: usize = 3;
+
 pub fn make_snippet(text: &str, query: &str, max_len: usize) -> String {
     if text.is_empty() || query.trim().is_empty() { return String::new(); }
-    let lc_text = text.to_lowercase();
-    let lc_query = query.to_lowercase();
-    if let Some(pos) = lc_text.find(&lc_query) {
+    if let Some(&(pos, match_end)) = match_ranges(text, query).first() {
         // center around first match, clamped to valid char boundaries
         let raw_start = pos.saturating_sub(max_len / 2);
-        let raw_end = (pos + lc_query.len() + max_len / 2).min(text.len());
+        let raw_end = (match_end + max_len / 2).min(text.len());
         let start = prev_char_boundary(text, raw_start);
         let end = next_char_boundary(text, raw_end);
         let end = end.max(start).min(text.len());
-        let snippet = &text[start..end];
-        trim_to_word_boundaries(snippet)
+        fit_window(text, start, end, (pos, match_end), max_len)
     } else {
         // fallback to head
         let end = next_char_boundary(text, max_len.min(text.len()));
-        trim_to_word_boundaries(&text[..end])
+        fit_window(text, 0, end, (0, 0), max_len)
     }
 }
 
+// Snippets for the paragraphs matching `query`. A single-word query gives a window around each
+// match; a longer one matches its words anywhere in a paragraph, giving each paragraph that
+// holds any of them one snippet around its densest cluster of words, paragraphs holding the
+// most distinct words first.
 pub fn make_snippets(text: &str, query: &str, max_len: usize) -> Vec<String> {
     if text.is_empty() || query.trim().is_empty() { return vec![]; }
-    let lc_query = query.to_lowercase();
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    if terms.len() > 1 { return make_multi_term_snippets(text, &terms, max_len); }
     let mut snippets = Vec::new();
     for paragraph in text.split("\n\n") {
-        let lc_paragraph = paragraph.to_lowercase();
-        if lc_paragraph.contains(&lc_query) {
-            let snippet = make_snippet(paragraph, query, max_len);
-            snippets.push(snippet);
+        snippets.extend(make_snippet_windows(paragraph, query, max_len));
+    }
+    snippets
+}
+
+fn make_multi_term_snippets(text: &str, terms: &[&str], max_len: usize) -> Vec<String> {
+    let mut scored: Vec<(usize, String)> = Vec::new();
+    for paragraph in text.split("\n\n") {
+        // (start, end, term) of every term match, in text order
+        let mut hits: Vec<(usize, usize, usize)> = terms.iter().enumerate().flat_map(|(t, term)| match_ranges(paragraph, term).into_iter().map(move |(a, b)| (a, b, t))).collect();
+        if hits.is_empty() { continue; }
+        hits.sort_unstable();
+        let distinct = hits.iter().map(|h| h.2).collect::<std::collections::HashSet<_>>().len();
+        let (from, to) = densest_cluster(&hits, max_len);
+        let pad = max_len.saturating_sub(to - from) / 2;
+        let start = prev_char_boundary(paragraph, from.saturating_sub(pad));
+        let end = next_char_boundary(paragraph, to + pad);
+        scored.push((distinct, fit_window(paragraph, start, end, (from, to), max_len)));
+    }
+    // Stable, so paragraphs with as many words keep text order
+    scored.sort_by_key(|&(distinct, _)| std::cmp::Reverse(distinct));
+    scored.into_iter().map(|(_, snippet)| snippet).collect()
+}
+
+// Span of the run of `hits` fitting in `max_len` bytes with the most distinct terms, then the
+// most matches, then the earliest.
+fn densest_cluster(hits: &[(usize, usize, usize)], max_len: usize) -> (usize, usize) {
+    let mut best = (0, 0, (hits[0].0, hits[0].1));
+    for (i, &(from, _, _)) in hits.iter().enumerate() {
+        let run: Vec<&(usize, usize, usize)> = hits[i..].iter().take_while(|h| h.1 - from <= max_len).collect();
+        // The first hit always counts, even when it alone is longer than `max_len`
+        let to = run.iter().map(|h| h.1).max().unwrap_or(hits[i].1);
+        let distinct = run.iter().map(|h| h.2).collect::<std::collections::HashSet<_>>().len();
+        if (distinct, run.len()) > (best.0, best.1) { best = (distinct, run.len(), (from, to)); }
+    }
+    best.2
+}
+
+// `text` split at each of the sorted, non-overlapping `ranges`: the match with up to
+// `max_len / 2` bytes of context on each side, stopping at the neighbouring matches. Unlike
+// `make_snippet`, not trimmed to words or sentences.
+pub fn split_at_matches(text: &str, ranges: &[(usize, usize)], max_len: usize) -> Vec<SnippetParts> {
+    let mut parts = Vec::with_capacity(ranges.len());
+    for (i, &(start, end)) in ranges.iter().enumerate() {
+        let (start, end) = (prev_char_boundary(text, start), next_char_boundary(text, end));
+        let lo = if i == 0 { 0 } else { next_char_boundary(text, ranges[i - 1].1) };
+        let hi = ranges.get(i + 1).map_or(text.len(), |r| prev_char_boundary(text, r.0));
+        let from = prev_char_boundary(text, start.saturating_sub(max_len / 2)).max(lo).min(start);
+        let to = next_char_boundary(text, end.saturating_add(max_len / 2)).min(hi).max(end);
+        parts.push(SnippetParts { before: text[from..start].to_string(), matched: text[start..end].to_string(), after: text[end..to].to_string() });
+    }
+    parts
+}
+
+// One window of context around each match in `text`. Windows that touch or overlap are
+// merged, so a term repeated close together yields one snippet while matches far apart
+// each get their own.
+pub fn make_snippet_windows(text: &str, query: &str, max_len: usize) -> Vec<String> {
+    snippet_windows(text, match_ranges(text, query), max_len)
+}
+
+fn snippet_windows(text: &str, ranges: Vec<(usize, usize)>, max_len: usize) -> Vec<String> {
+    // (window, span of the matches inside it)
+    let mut windows: Vec<((usize, usize), (usize, usize))> = Vec::new();
+    for (start, end) in ranges {
+        let w_start = prev_char_boundary(text, start.saturating_sub(max_len / 2));
+        let w_end = next_char_boundary(text, end + max_len / 2);
+        match windows.last_mut() {
+            Some((last, keep)) if w_start <= last.1 => {
+                last.1 = last.1.max(w_end);
+                keep.1 = keep.1.max(end);
+            }
+            _ => windows.push(((w_start, w_end), (start, end))),
         }
     }
+    windows.into_iter().map(|((a, b), keep)| fit_window(text, a, b, keep, max_len)).collect()
+}
+
+// A snippet plus the byte ranges within `text` where the query matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    pub highlights: Vec<(usize, usize)>,
+}
+
+// Same snippets as `make_snippets`, each with every (case-insensitive) occurrence of the
+// query's words marked so the frontend can bold matches without scanning again.
+pub fn make_snippets_highlighted(text: &str, query: &str, max_len: usize) -> Vec<Snippet> {
+    make_snippets(text, query, max_len)
+        .into_iter()
+        .map(|s| { let highlights = term_ranges(&s, query); Snippet { text: s, highlights } })
+        .collect()
+}
+
+// Matches of each word of `query` in `text` like `match_ranges`, in text order. Where words
+// overlap (`net` in `network`) the earlier, then longer, match wins.
+pub fn term_ranges(text: &str, query: &str) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = query.split_whitespace().flat_map(|term| match_ranges(text, term)).collect();
+    ranges.sort_by_key(|&(a, b)| (a, std::cmp::Reverse(b)));
+    let mut out: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for r in ranges {
+        if out.last().is_none_or(|last| r.0 >= last.1) { out.push(r); }
+    }
+    out
+}
+
+// The `n` best of one document's snippets: those containing the most distinct words of
+// `query`, then the most highlighted matches, then the earliest.
+pub fn top_snippets(mut snippets: Vec<Snippet>, query: &str, n: usize) -> Vec<Snippet> {
+    if snippets.len() <= n { return snippets; }
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    let rank = |s: &Snippet| {
+        let found = terms.iter().filter(|t| !match_ranges(&s.text, t).is_empty()).count();
+        std::cmp::Reverse((found, s.highlights.len()))
+    };
+    // Stable, so equal snippets keep document order
+    snippets.sort_by_cached_key(rank);
+    snippets.truncate(n);
     snippets
 }
 
+// Snippets around every match of a regular expression, one set per paragraph like
+// `make_snippets`, with the matches inside each snippet highlighted. Empty matches are skipped.
+pub fn make_regex_snippets(text: &str, re: &regex::Regex, max_len: usize) -> Vec<Snippet> {
+    let ranges = |t: &str| -> Vec<(usize, usize)> { re.find_iter(t).filter(|m| !m.is_empty()).map(|m| (m.start(), m.end())).collect() };
+    text.split("\n\n")
+        .flat_map(|paragraph| snippet_windows(paragraph, ranges(paragraph), max_len))
+        .map(|s| { let highlights = ranges(&s); Snippet { text: s, highlights } })
+        .collect()
+}
+
+// Non-overlapping matches of `query` in `text`, ignoring case and accents like the index
+// does, as byte ranges of `text`. Matching happens on the folded text, so offsets are mapped
+// back through `orig_at` (folding changes byte lengths) and always land on char boundaries.
+pub fn match_ranges(text: &str, query: &str) -> Vec<(usize, usize)> {
+    let folded_query = fold(query.trim());
+    if folded_query.is_empty() { return vec![]; }
+    let mut folded = String::with_capacity(text.len());
+    let mut orig_at: Vec<usize> = Vec::with_capacity(text.len() + 1);
+    for (i, c) in text.char_indices() {
+        fold_char(c, &mut folded);
+        orig_at.resize(folded.len(), i);
+    }
+    orig_at.push(text.len());
+    let mut ranges = Vec::new();
+    let mut from = 0;
+    while let Some(pos) = folded[from..].find(&folded_query) {
+        let start = from + pos;
+        let end = start + folded_query.len();
+        ranges.push((orig_at[start], next_char_boundary(text, orig_at[end])));
+        from = end;
+    }
+    ranges
+}
+
+thread_local! {
+    static FOLDER: RefCell<TextAnalyzer> = RefCell::new(
+        TextAnalyzer::builder(RawTokenizer::default()).filter(AsciiFoldingFilter).build()
+    );
+}
+
+// Lowercase and strip accents (é -> e, ß -> ss) using the index's folding table.
+fn fold_char(c: char, out: &mut String) {
+    if c.is_ascii() { out.push(c.to_ascii_lowercase()); return; }
+    let lower: String = c.to_lowercase().collect();
+    FOLDER.with(|a| {
+        let mut analyzer = a.borrow_mut();
+        let mut stream = analyzer.token_stream(&lower);
+        match stream.next() {
+            Some(tok) => out.push_str(&tok.text),
+            None => out.push_str(&lower),
+        }
+    });
+}
+
+fn fold(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() { fold_char(c, &mut out); }
+    out
+}
+
 fn prev_char_boundary(s: &str, mut idx: usize) -> usize {
     if idx > s.len() { idx = s.len(); }
     while idx > 0 && !s.is_char_boundary(idx) { idx -= 1; }
@@ -44,8 +230,82 @@ fn next_char_boundary(s: &str, mut idx: usize) -> usize {
     idx
 }
 
-fn trim_to_word_boundaries(s: &str) -> String {
-    s.trim().to_string()
+// Share of `max_len` a window may grow by on each side to reach a sentence boundary
+const SENTENCE_MARGIN_DIVISOR: usize = 4;
+
+// The snippet for window `start..end` of `text`, which must keep the matches in `keep`. Each
+// edge moves to the nearest sentence boundary within a quarter of `max_len` of it, outward or
+// inward as far as `keep` allows; an edge left mid-sentence (no boundary close enough) is
+// marked with "…". The text's own start and end count as boundaries.
+fn fit_window(text: &str, start: usize, end: usize, keep: (usize, usize), max_len: usize) -> String {
+    let margin = max_len / SENTENCE_MARGIN_DIVISOR;
+    let (start, clean_start) = if start == 0 {
+        (0, true)
+    } else {
+        sentence_breaks(text, start.saturating_sub(margin), keep.0)
+            .into_iter()
+            .map(|(_, next)| next)
+            .filter(|&next| next <= keep.0 && next.abs_diff(start) <= margin)
+            .min_by_key(|&next| next.abs_diff(start))
+            .map_or((start, false), |next| (next, true))
+    };
+    let (end, clean_end) = if end >= text.len() {
+        (text.len(), true)
+    } else {
+        sentence_breaks(text, end.saturating_sub(margin).max(keep.1).saturating_sub(1), (end + margin).min(text.len()))
+            .into_iter()
+            .map(|(stop, _)| stop)
+            .filter(|&stop| stop >= keep.1)
+            .min_by_key(|&stop| stop.abs_diff(end))
+            .map_or((end, false), |stop| (stop, true))
+    };
+    let body = trim_to_word_boundaries(text, start, end.max(start), keep).to_string();
+    if body.is_empty() { return body; }
+    format!("{}{}{}", if clean_start { "" } else { "…" }, body, if clean_end { "" } else { "…" })
+}
+
+// Sentence ends starting within `from..to`: `.`, `?` or `!` followed by whitespace or the end
+// of the text, or a blank line. Each is (end of the sentence, start of the next after the
+// whitespace). Only ASCII bytes are compared, so both offsets are char boundaries.
+fn sentence_breaks(text: &str, from: usize, to: usize) -> Vec<(usize, usize)> {
+    let bytes = text.as_bytes();
+    let mut breaks = Vec::new();
+    let mut i = from;
+    while i < to.min(bytes.len()) {
+        let followed_by_space_or_end = bytes.get(i + 1).is_none_or(|c| c.is_ascii_whitespace());
+        let stop = if matches!(bytes[i], b'.' | b'?' | b'!') && followed_by_space_or_end {
+            i + 1
+        } else if bytes[i] == b'\n' && bytes.get(i + 1) == Some(&b'\n') {
+            i
+        } else {
+            i += 1;
+            continue;
+        };
+        let mut next = stop;
+        while next < bytes.len() && bytes[next].is_ascii_whitespace() { next += 1; }
+        breaks.push((stop, next));
+        i = next;
+    }
+    breaks
+}
+
+// `text[start..end]` without a word the window cut through at either edge (unless the match
+// in `keep` is in it), then trimmed of whitespace.
+fn trim_to_word_boundaries(text: &str, mut start: usize, mut end: usize, keep: (usize, usize)) -> &str {
+    let inside_word = |i: usize| {
+        text[..i].chars().next_back().is_some_and(|c| !c.is_whitespace()) && text[i..].chars().next().is_some_and(|c| !c.is_whitespace())
+    };
+    if inside_word(start) {
+        if let Some(space) = text[start..end].find(char::is_whitespace) {
+            if start + space <= keep.0 { start += space; }
+        }
+    }
+    if inside_word(end) {
+        if let Some(space) = text[start..end].rfind(char::is_whitespace) {
+            if start + space >= keep.1 { end = start + space; }
+        }
+    }
+    text[start..end].trim()
 }
 
 #[cfg(test)]
@@ -77,6 +337,165 @@ mod tests {
         assert!(all[1].to_lowercase().contains("apple"));
     }
 
+    #[test]
+    fn test_highlights_every_occurrence() {
+        let snips = make_snippets_highlighted("Rust and rust and RUST", "rust", 100);
+        assert_eq!(snips.len(), 1);
+        let s = &snips[0];
+        assert_eq!(s.highlights, vec![(0, 4), (9, 13), (18, 22)]);
+        for (a, b) in &s.highlights { assert_eq!(s.text[*a..*b].to_lowercase(), "rust"); }
+    }
+
+    #[test]
+    fn test_regex_snippets_highlight_matches() {
+        let re = regex::RegexBuilder::new(r"RFC\s?\d{4}").case_insensitive(true).build().unwrap();
+        let snips = make_regex_snippets("See RFC 2616 and rfc7230.\n\nNothing here.", &re, 100);
+        assert_eq!(snips.len(), 1);
+        let s = &snips[0];
+        let matched: Vec<&str> = s.highlights.iter().map(|(a, b)| &s.text[*a..*b]).collect();
+        assert_eq!(matched, vec!["RFC 2616", "rfc7230"]);
+        // A pattern that can match nothing yields no snippets
+        assert!(make_regex_snippets("abc", &regex::Regex::new("x*").unwrap(), 100).is_empty());
+    }
+
+    #[test]
+    fn test_highlight_ranges_on_char_boundaries() {
+        // 'İ' lowercases to two chars, shifting lowercase offsets relative to the original
+        let text = "İstanbul café, Café au lait";
+        let ranges = match_ranges(text, "café");
+        assert_eq!(ranges.len(), 2);
+        for (a, b) in ranges {
+            assert!(text.is_char_boundary(a) && text.is_char_boundary(b));
+            assert_eq!(text[a..b].to_lowercase(), "café");
+        }
+    }
+
+    #[test]
+    fn test_tiny_snippet_len_is_boundary_safe() {
+        let text = "ünïcödé wörds äround the térm ñear here";
+        for len in [0, 1, 3, 10] {
+            let snip = make_snippet(text, "térm", len);
+            assert!(snip.contains("térm"));
+        }
+        let head = make_snippet(text, "missing", 3);
+        assert!(text.starts_with(head.trim_end_matches('…')));
+    }
+
+    #[test]
+    fn test_long_snippet_len_returns_whole_text() {
+        let text = "short paragraph with a needle in it";
+        assert_eq!(make_snippet(text, "needle", 10_000), text);
+        assert_eq!(make_snippets(text, "needle", 10_000), vec![text.to_string()]);
+    }
+
+    #[test]
+    fn test_snippet_windows_split_distant_matches() {
+        let gap = "x ".repeat(100);
+        let text = format!("alpha{}alpha{}alpha", gap, gap);
+        let windows = make_snippet_windows(&text, "alpha", 20);
+        assert_eq!(windows.len(), 3);
+        assert!(windows.iter().all(|w| w.contains("alpha")));
+    }
+
+    #[test]
+    fn test_snippet_windows_merge_overlaps() {
+        let text = "beta one beta two beta";
+        let windows = make_snippet_windows(text, "beta", 20);
+        assert_eq!(windows, vec![text.to_string()]);
+        assert!(make_snippet_windows(text, "gamma", 20).is_empty());
+    }
+
+    #[test]
+    fn test_snippet_extends_to_sentence_boundaries() {
+        let text = "Before it. One two three needle four five six. After that it ends here.";
+        // The window around "needle" stops mid-sentence on both sides; it grows to the whole sentence
+        assert_eq!(make_snippet(text, "needle", 24), "One two three needle four five six.");
+        // Too far to reach a boundary: the cut edges are marked
+        let long = format!("{} needle {}", "word ".repeat(50), "word ".repeat(50));
+        let snip = make_snippet(&long, "needle", 40);
+        assert!(snip.starts_with('…') && snip.ends_with('…'), "{}", snip);
+        assert!(snip.contains("needle"));
+        // A question or blank line ends a sentence too; the text's own edges need no marks
+        assert_eq!(make_snippet("Why now? The needle moved.", "needle", 12), "The needle moved.");
+        assert_eq!(make_snippet("Intro\n\nA needle.", "needle", 8), "A needle.");
+    }
+
+    #[test]
+    fn test_snippet_drops_words_cut_by_the_window() {
+        let text = "alpha bravo charlie delta needle echo foxtrot golf hotel";
+        // The 20-byte window cuts "charlie" and "foxtrot"; both halves are dropped
+        assert_eq!(make_snippet(text, "needle", 20), "…delta needle echo…");
+        // A cut word holding the match is kept
+        assert_eq!(make_snippet("xxxxneedle yyyy", "needle", 2), "…xneedle…");
+        assert_eq!(make_snippet("aaaa needlezzzz", "needle", 2), "…needlez…");
+        // Cuts that land between words lose nothing
+        assert_eq!(make_snippet("one two needle six four", "needle", 8), "…two needle six…");
+    }
+
+    #[test]
+    fn test_top_snippets_prefer_more_query_terms() {
+        let snippet = |text: &str| Snippet { text: text.to_string(), highlights: vec![(0, 1)] };
+        let snippets = vec![snippet("red only"), snippet("red and blue"), snippet("blue only"), snippet("red, blue and green")];
+        let top: Vec<String> = top_snippets(snippets.clone(), "red blue green", 2).into_iter().map(|s| s.text).collect();
+        assert_eq!(top, vec!["red, blue and green", "red and blue"]);
+        // Ties keep document order
+        let top: Vec<String> = top_snippets(snippets.clone(), "only", 1).into_iter().map(|s| s.text).collect();
+        assert_eq!(top, vec!["red only"]);
+        assert_eq!(top_snippets(snippets, "red", 10).len(), 4);
+    }
+
+    #[test]
+    fn test_multi_term_snippets_match_words_apart_and_out_of_order() {
+        let text = "A network of roads.\n\nThe network grew; its neural layers came later.\n\nNothing here.\n\nNeural only.";
+        let snippets = make_snippets_highlighted(text, "neural network", 200);
+        let texts: Vec<&str> = snippets.iter().map(|s| s.text.as_str()).collect();
+        // Both words first, then paragraphs with one, in text order
+        assert_eq!(texts, vec!["The network grew; its neural layers came later.", "A network of roads.", "Neural only."]);
+        let words: Vec<&str> = snippets[0].highlights.iter().map(|&(a, b)| &snippets[0].text[a..b]).collect();
+        assert_eq!(words, vec!["network", "neural"]);
+    }
+
+    #[test]
+    fn test_multi_term_snippet_centers_on_densest_cluster() {
+        let filler = "word ".repeat(40);
+        let text = format!("alpha {} alpha beta gamma {} gamma", filler, filler);
+        let snippets = make_snippets(&text, "alpha beta gamma", 30);
+        assert_eq!(snippets.len(), 1);
+        assert!(snippets[0].contains("alpha beta gamma"), "{}", snippets[0]);
+        // One word keeps the per-match windows
+        assert_eq!(make_snippets(&text, "alpha", 30).len(), 2);
+    }
+
+    #[test]
+    fn test_snippet_parts_split_around_match() {
+        let text = "Der Kaffee im Café ist heiß";
+        let parts = split_at_matches(text, &term_ranges(text, "cafe"), 10);
+        assert_eq!(parts, [SnippetParts { before: "e im ".into(), matched: "Café".into(), after: " ist ".into() }]);
+        // Context cut mid-character moves to the nearest boundary outside it
+        let text = "ééé needle ééé";
+        let parts = split_at_matches(text, &term_ranges(text, "needle"), 4);
+        assert_eq!((parts[0].before.as_str(), parts[0].after.as_str()), ("é ", " é"));
+        assert!(split_at_matches("nothing here", &[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_split_at_matches_stops_at_neighbours() {
+        let text = "one fox two fox three";
+        let parts = split_at_matches(text, &term_ranges(text, "fox"), 100);
+        assert_eq!(parts.len(), 2);
+        assert_eq!((parts[0].before.as_str(), parts[0].after.as_str()), ("one ", " two "));
+        assert_eq!((parts[1].before.as_str(), parts[1].after.as_str()), (" two ", " three"));
+    }
+
+    #[test]
+    fn test_accent_folded_matching() {
+        assert_eq!(match_ranges("un café", "cafe"), vec![(3, 8)]);
+        assert_eq!(match_ranges("a cafe", "café"), vec![(2, 6)]);
+        assert_eq!(match_ranges("Naïve Art", "naive").len(), 1);
+        assert_eq!(match_ranges("so naive", "naïve").len(), 1);
+        assert!(make_snippet("le résumé final", "resume", 100).contains("résumé"));
+    }
+
     #[test]
     fn test_char_boundary_helpers() {
         let s = "A😊B"; // multi-byte in middle
@@ -1,13 +1,21 @@
-use std::{fs, path::{Path, PathBuf}};
+use std::{fs, io::Read, path::{Path, PathBuf}, sync::{atomic::{AtomicUsize, Ordering}, mpsc, Mutex}, time::{Duration, Instant}};
 
 use tantivy::{
-    schema::{Schema, SchemaBuilder, Field, TextOptions, TextFieldIndexing, IndexRecordOption, STORED, STRING, INDEXED},
+    schema::{Schema, SchemaBuilder, Field, TextOptions, TextFieldIndexing, IndexRecordOption, FAST, STORED, STRING, INDEXED},
     Index, TantivyDocument, doc
 };
 use tantivy::schema::Value; // bring as_str/as_u64 helpers into scope
-use crate::{AppState, commands::library, util::{extract_text::{extract_title_and_text, is_supported_text}, extract_pdf::extract_pdf_pages_cached}, models::SearchResult};
+use tantivy::directory::MmapDirectory;
+use tantivy::tokenizer::{AsciiFoldingFilter, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer, StopWordFilter, TextAnalyzer};
+use crate::{AppState, error::QuietError, commands::{library, settings}, util::{extract::Extractors, extract_pdf::remove_cached_document}, models::{ExtensionStats, FacetCount, IndexFileError, IndexPreview, PageHit, IndexReport, IndexUpdateStats, ReindexProgress, SearchField, SearchOptions, SearchResponse, SearchResult, TruncatedFile}};
+use crate::util::code_tokenizer::CodeTokenizer;
+use crate::util::extract_zip::{is_zip, member_path};
+use crate::util::retry::{self, RetryPolicy};
+use crate::util::snippet::{make_regex_snippets, make_snippet, make_snippets_highlighted, term_ranges, top_snippets, Snippet};
 use rayon::prelude::*;
 use rayon::ThreadPoolBuilder;
+use levenshtein_automata::{Distance, LevenshteinAutomatonBuilder, DFA, SINK_STATE};
+use once_cell::sync::Lazy;
 
 pub struct IndexHandles {
     pub index: Index,
@@ -21,97 +29,591 @@ pub struct IndexFields {
     pub page: Field,
     pub section: Field,
     pub body: Field,
+    pub folder: Field,
+    pub author: Field,
+    pub created: Field,
+    pub mtime: Field,
+    pub size: Field,
+    pub code: Field,
+    pub extractor: Field,
+    pub body_cs: Field,
 }
 
-fn schema() -> (Schema, IndexFields) {
+// Field handles. Field ids don't depend on tokenizers, so this works for any index we built.
+fn schema() -> (Schema, IndexFields) { schema_with_body_tokenizer(FOLDED_TOKENIZER) }
+
+fn schema_with_body_tokenizer(body_tokenizer: &str) -> (Schema, IndexFields) {
     let mut sb = SchemaBuilder::default();
-    let text_indexing = TextFieldIndexing::default()
-        .set_tokenizer("default")
-        .set_index_option(IndexRecordOption::WithFreqsAndPositions);
-    let text_opts = TextOptions::default().set_stored().set_indexing_options(text_indexing);
-    let title = sb.add_text_field("title", text_opts.clone());
-    let path = sb.add_text_field("path", STRING | STORED);
+    let text_opts = |tokenizer: &str| {
+        let indexing = TextFieldIndexing::default()
+            .set_tokenizer(tokenizer)
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions);
+        TextOptions::default().set_stored().set_indexing_options(indexing)
+    };
+    let title = sb.add_text_field("title", text_opts(FOLDED_TOKENIZER));
+    // Fast so searches can count matching files without loading documents
+    let path = sb.add_text_field("path", STRING | STORED | FAST);
     let page = sb.add_u64_field("page", STORED | INDEXED);
     let section = sb.add_text_field("section", STRING | STORED);
-    let body = sb.add_text_field("body", text_opts);
+    let body = sb.add_text_field("body", text_opts(body_tokenizer));
+    // Watched folder the file was found under, for folder-restricted searches
+    let folder = sb.add_text_field("folder", STRING);
+    // Document metadata (PDF Info dictionary); `author:knuth` searches it
+    let author = sb.add_text_field("author", text_opts(FOLDED_TOKENIZER));
+    let created = sb.add_text_field("created", STRING | STORED);
+    // Source file modification time (seconds since the epoch)
+    let mtime = sb.add_u64_field("mtime", STORED | INDEXED);
+    // Source file size in bytes; every page of a PDF carries the whole file's size
+    let size = sb.add_u64_field("size", STORED | INDEXED);
+    // Bodies of source files again, split into identifier words (see `CodeTokenizer`); not stored
+    let code_indexing = TextFieldIndexing::default().set_tokenizer(CODE_TOKENIZER).set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    let code = sb.add_text_field("code", TextOptions::default().set_indexing_options(code_indexing));
+    // PDF text extractor that produced a page ("pdfium", "lopdf", "ocr")
+    let extractor = sb.add_text_field("extractor", STRING | STORED);
+    // Bodies again with their words as written, for case-sensitive searches; not stored. Every
+    // body's words and positions are indexed twice, which adds about as much to the index as
+    // the `body` postings themselves
+    let cased_indexing = TextFieldIndexing::default().set_tokenizer(CASED_TOKENIZER).set_index_option(IndexRecordOption::WithFreqsAndPositions);
+    let body_cs = sb.add_text_field("body_cs", TextOptions::default().set_indexing_options(cased_indexing));
     let schema = sb.build();
-    (schema, IndexFields { title, path, page, section, body })
+    (schema, IndexFields { title, path, page, section, body, folder, author, created, mtime, size, code, extractor, body_cs })
+}
+
+// Lowercased, accent-folded words so `cafe` finds `café` and vice versa. Tokenizers live
+// in the Index, not on disk, so every open/create must go through `register_tokenizers`.
+const FOLDED_TOKENIZER: &str = "folded";
+// Identifiers split at underscores and case changes, for the `code` field
+const CODE_TOKENIZER: &str = "code";
+// Words as written, for the `body_cs` field
+const CASED_TOKENIZER: &str = "cased";
+// Body tokenizer with stemming is named `stem_<language>`, e.g. `stem_english`, and gets a
+// `_stop` suffix when stop words are removed (`folded_stop`, `stem_english_stop`).
+const STEM_TOKENIZER_PREFIX: &str = "stem_";
+const STOP_TOKENIZER_SUFFIX: &str = "_stop";
+
+// Written next to the index at build time. The body tokenizer is chosen from settings when
+// the index is created, and queries must be tokenized the same way until the next rebuild.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexMeta {
+    body_tokenizer: String,
+    // Words dropped from bodies when the tokenizer name ends in `_stop`
+    #[serde(default)]
+    stop_words: Vec<String>,
+}
+
+fn load_index_meta(dir: &Path) -> IndexMeta {
+    fs::read(dir.join("index_meta.json"))
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        // Indexes from before stemming existed
+        .unwrap_or_else(|| IndexMeta { body_tokenizer: FOLDED_TOKENIZER.to_string(), stop_words: vec![] })
+}
+
+fn save_index_meta(dir: &Path, meta: &IndexMeta) -> Result<(), String> {
+    let bytes = serde_json::to_vec_pretty(meta).map_err(|e| e.to_string())?;
+    fs::write(dir.join("index_meta.json"), bytes).map_err(|e| e.to_string())
+}
+
+fn stem_language(name: &str) -> Option<Language> {
+    Some(match name.to_ascii_lowercase().as_str() {
+        "arabic" => Language::Arabic,
+        "danish" => Language::Danish,
+        "dutch" => Language::Dutch,
+        "english" => Language::English,
+        "finnish" => Language::Finnish,
+        "french" => Language::French,
+        "german" => Language::German,
+        "greek" => Language::Greek,
+        "hungarian" => Language::Hungarian,
+        "italian" => Language::Italian,
+        "norwegian" => Language::Norwegian,
+        "portuguese" => Language::Portuguese,
+        "romanian" => Language::Romanian,
+        "russian" => Language::Russian,
+        "spanish" => Language::Spanish,
+        "swedish" => Language::Swedish,
+        "tamil" => Language::Tamil,
+        "turkish" => Language::Turkish,
+        _ => return None,
+    })
+}
+
+// Body tokenizer name for the configured stemming language ("" disables stemming).
+fn body_tokenizer_for(language: &str) -> Result<String, String> {
+    let language = language.trim().to_ascii_lowercase();
+    if language.is_empty() { return Ok(FOLDED_TOKENIZER.to_string()); }
+    if stem_language(&language).is_none() { return Err(format!("unsupported stemming language: {}", language)); }
+    Ok(format!("{}{}", STEM_TOKENIZER_PREFIX, language))
+}
+
+fn register_tokenizers(index: &Index, meta: &IndexMeta) {
+    let folded = || TextAnalyzer::builder(SimpleTokenizer::default())
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+        .filter(AsciiFoldingFilter);
+    index.tokenizers().register(FOLDED_TOKENIZER, folded().build());
+    let code = TextAnalyzer::builder(CodeTokenizer::default()).filter(RemoveLongFilter::limit(40)).filter(LowerCaser).filter(AsciiFoldingFilter);
+    index.tokenizers().register(CODE_TOKENIZER, code.build());
+    index.tokenizers().register(CASED_TOKENIZER, TextAnalyzer::builder(SimpleTokenizer::default()).filter(RemoveLongFilter::limit(40)).build());
+    let (base, stop) = match meta.body_tokenizer.strip_suffix(STOP_TOKENIZER_SUFFIX) {
+        Some(base) => (base, true),
+        None => (meta.body_tokenizer.as_str(), false),
+    };
+    let lang = base.strip_prefix(STEM_TOKENIZER_PREFIX).and_then(stem_language);
+    if lang.is_none() && !stop { return; }
+    let mut body = folded().dynamic();
+    // Stop words are matched after folding but before stemming, as the user wrote them.
+    // Remaining tokens keep their original positions, so phrases still line up.
+    if stop { body = body.filter_dynamic(StopWordFilter::remove(meta.stop_words.iter().map(|w| w.to_lowercase()))); }
+    if let Some(lang) = lang { body = body.filter_dynamic(Stemmer::new(lang)); }
+    index.tokenizers().register(&meta.body_tokenizer, body.build());
+}
+
+// Both open and create go through `MmapDirectory` (what `Index::open_in_dir` uses too), so
+// segment files are mapped rather than read and the OS page cache keeps a warm index resident
+// between launches. Memory mapping works on every desktop target; the directory must exist.
+fn mmap_dir(dir: &Path) -> Result<MmapDirectory, String> {
+    MmapDirectory::open(dir).map_err(|e| format!("cannot open index directory {}: {}", dir.to_string_lossy(), e))
+}
+
+fn open_index(dir: &Path) -> Result<Index, String> {
+    let index = Index::open(mmap_dir(dir)?).map_err(|e| e.to_string())?;
+    register_tokenizers(&index, &load_index_meta(dir));
+    Ok(index)
+}
+
+// Tokenizer choice for a new index, from the current settings.
+fn meta_from_settings(state: &AppState) -> Result<IndexMeta, String> {
+    let s = settings::read_settings(state);
+    let mut body_tokenizer = body_tokenizer_for(&s.stem_language)?;
+    if !s.remove_stop_words { return Ok(IndexMeta { body_tokenizer, stop_words: vec![] }); }
+    body_tokenizer.push_str(STOP_TOKENIZER_SUFFIX);
+    Ok(IndexMeta { body_tokenizer, stop_words: s.stop_words })
+}
+
+fn create_index(dir: &Path, meta: &IndexMeta) -> Result<Index, String> {
+    let (sch, _) = schema_with_body_tokenizer(&meta.body_tokenizer);
+    let index = Index::create(mmap_dir(dir)?, sch, tantivy::IndexSettings::default()).map_err(|e| e.to_string())?;
+    save_index_meta(dir, meta)?;
+    register_tokenizers(&index, meta);
+    Ok(index)
 }
 
 fn index_dir(state: &AppState) -> PathBuf { state.app_dir.join("index") }
 
-// Cap the number of pages we index per PDF to avoid extremely large
-// indexing jobs on massive documents. This balances speed and memory.
-const MAX_PDF_PAGES_INDEX: u32 = 300;
+// A rebuild is written here and only swapped in for `index/` after its final commit, so a
+// crash mid-rebuild leaves the previous index searchable.
+fn staging_dir(state: &AppState) -> PathBuf { state.app_dir.join("index.tmp") }
 
-pub fn rebuild_index(state: &AppState) -> Result<(), String> {
-    let dir = index_dir(state);
-    if dir.exists() { fs::remove_dir_all(&dir).map_err(|e| e.to_string())?; }
-    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+// The previous index between the two renames of a swap (a directory can't be renamed over a
+// non-empty one)
+fn retired_dir(state: &AppState) -> PathBuf { state.app_dir.join("index.old") }
+
+// Replace the live index with the committed staging index.
+fn swap_in_staged_index(state: &AppState) -> Result<(), QuietError> {
+    let (live, staged, retired) = (index_dir(state), staging_dir(state), retired_dir(state));
+    // Open readers hold the old files (and on Windows, block the rename)
+    drop_cached_index(state);
+    if retired.exists() { fs::remove_dir_all(&retired)?; }
+    if live.exists() { fs::rename(&live, &retired)?; }
+    fs::rename(&staged, &live)?;
+    // A search or warm-up during the renames may have cached the old index again
+    drop_cached_index(state);
+    if let Err(e) = fs::remove_dir_all(&retired) { eprintln!("quietlibrary: could not remove {}: {}", retired.to_string_lossy(), e); }
+    Ok(())
+}
+
+// Clean up after a rebuild that was interrupted, at startup. A staging index next to a retired
+// one but no live index was fully committed and mid-swap, so the swap is finished; a lone
+// retired index is put back; any other staging index is a partial build and is discarded.
+pub fn recover_interrupted_rebuild(state: &AppState) {
+    let (live, staged, retired) = (index_dir(state), staging_dir(state), retired_dir(state));
+    let result: std::io::Result<()> = (|| {
+        if !live.exists() && retired.exists() {
+            let from = if staged.exists() { &staged } else { &retired };
+            eprintln!("quietlibrary: recovering index from {}", from.to_string_lossy());
+            fs::rename(from, &live)?;
+        }
+        if staged.exists() {
+            eprintln!("quietlibrary: discarding unfinished rebuild in {}", staged.to_string_lossy());
+            fs::remove_dir_all(&staged)?;
+        }
+        if retired.exists() { fs::remove_dir_all(&retired)?; }
+        Ok(())
+    })();
+    if let Err(e) = result { eprintln!("quietlibrary: index recovery failed: {}", e); }
+}
+
+#[cfg(test)]
+pub fn rebuild_index(state: &AppState) -> Result<(), QuietError> {
+    rebuild_index_with_progress(state, &|_| {})
+}
 
-    let (sch, fields) = schema();
-    let index = Index::create_in_dir(&dir, sch).map_err(|e| e.to_string())?;
-    let mut writer = index.writer(128 * 1024 * 1024).map_err(|e| e.to_string())?; // 128MB heap
+// Rebuild the whole index, reporting each extracted file to `on_progress`. Calls come from the
+// extraction pool and are throttled to PROGRESS_INTERVAL; the last file is always reported.
+pub fn rebuild_index_with_progress(state: &AppState, on_progress: &(dyn Fn(ReindexProgress) + Sync)) -> Result<(), QuietError> {
+    // Built aside and swapped in at the end; the live index keeps serving searches meanwhile
+    let dir = staging_dir(state);
+    let meta = meta_from_settings(state)?;
+    if dir.exists() { fs::remove_dir_all(&dir)?; }
+    fs::create_dir_all(&dir)?;
+
+    let (_, fields) = schema();
+    let index = create_index(&dir, &meta)?;
+    let mut writer: tantivy::IndexWriter = index.writer(writer_heap_limit(state))?;
 
     // Collect all files to index
-    let mut all_files: Vec<PathBuf> = Vec::new();
     let folders = library::watched_folders(state);
-    for folder in folders {
-        let root = PathBuf::from(&folder);
-        gather_files(&root, &mut all_files)?;
-    }
+    let (all_files, unreachable) = gather_library(state, &folders)?;
 
     // Extract contents in parallel (with bounded parallelism)
-    let cache_root = state.app_dir.join("cache");
-    // Choose a conservative thread count to reduce I/O/CPU thrash
+    let config = ExtractConfig::from_state(state);
+    let (pool, pdf_pool) = extraction_pools(state)?;
+    // Taken before extraction, so a file that changes meanwhile is read again by the next update
+    let mut fps = fingerprint_files(&all_files, settings::read_settings(state).content_hash_fingerprints, &pool);
+    let throttle = ProgressThrottle::new(all_files.len(), PROGRESS_INTERVAL);
+    let budget = ByteBudget::new(settings::read_settings(state).extract_buffer_bytes);
+    let on_file = |path: &Path| if let Some(p) = throttle.file_done(path) { on_progress(p); };
+    let (mut errors, mut truncated, empty) = extract_streaming(&all_files, &config, &pool, &pdf_pool, &budget, &on_file, &mut |d| {
+        let _ = writer.add_document(make_doc(&fields, d, &folders));
+    });
+    eprintln!("quietlibrary: rebuild files={} peak_buffered_bytes={}", all_files.len(), budget.peak());
+    // Files finish in any order
+    errors.sort_by(|a, b| a.path.cmp(&b.path));
+    truncated.sort_by(|a, b| a.path.cmp(&b.path));
+    // Leave failed files without a fingerprint so the next update retries them
+    for e in &errors {
+        fps.entries.remove(&e.path);
+        fps.hashes.remove(&e.path);
+    }
+    fps.empty = empty;
+
+    // Offline folders keep what the live index has from them
+    if !unreachable.is_empty() && index_dir(state).exists() {
+        let live = open_index(&index_dir(state))?;
+        for folder in &unreachable {
+            let kept = carry_over_folder(&live, &writer, &fields, folder)?;
+            eprintln!("quietlibrary: kept documents of unreachable folder={} docs={}", folder, kept);
+        }
+        truncated.extend(load_report(state).truncated.into_iter().filter(|t| unreachable.iter().any(|f| Path::new(&t.path).starts_with(f))));
+        let prev = load_fingerprints(&index_dir(state));
+        let offline = |k: &String| unreachable.iter().any(|f| Path::new(k).starts_with(f));
+        fps.entries.extend(prev.entries.into_iter().filter(|(k, _)| offline(k)));
+        fps.hashes.extend(prev.hashes.into_iter().filter(|(k, _)| offline(k)));
+        fps.empty.extend(prev.empty.into_iter().filter(|(k, _)| offline(k)));
+    }
+
+    writer.commit()?;
+    drop(writer);
+    drop(index);
+    // The next update only re-reads files that changed since
+    save_fingerprints(&dir, &fps);
+    swap_in_staged_index(state)?;
+    save_report(state, &IndexReport { errors, truncated, unreachable });
+    Ok(())
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+// Extracted files waiting for the writer during a rebuild
+const EXTRACT_QUEUE: usize = 64;
+
+// Counts files finished by the extraction pool and decides which completions get reported.
+struct ProgressThrottle {
+    total: usize,
+    done: AtomicUsize,
+    interval: Duration,
+    last_emit: Mutex<Option<Instant>>,
+}
+
+impl ProgressThrottle {
+    fn new(total: usize, interval: Duration) -> Self {
+        ProgressThrottle { total, done: AtomicUsize::new(0), interval, last_emit: Mutex::new(None) }
+    }
+
+    fn file_done(&self, path: &Path) -> Option<ReindexProgress> {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut last = self.last_emit.lock().ok()?;
+        let due = last.is_none_or(|t| t.elapsed() >= self.interval);
+        if !due && done < self.total { return None; }
+        *last = Some(Instant::now());
+        Some(ReindexProgress { done, total: self.total, file: path.to_string_lossy().to_string() })
+    }
+}
+
+// One index document: a whole text file, an EPUB chapter or a PDF page.
+#[derive(Debug, Clone, Default)]
+struct IndexDoc {
+    title: String,
+    path: String,
+    page: Option<u32>,
+    section: Option<String>,
+    body: String,
+    author: Option<String>,
+    created: Option<String>,
+    mtime: Option<u64>,
+    size: Option<u64>,
+    // Source code, also indexed in the `code` field
+    code: bool,
+    extractor: Option<String>,
+    // Body also indexed in `body_cs`
+    cased: bool,
+}
+
+fn make_doc(fields: &IndexFields, d: IndexDoc, folders: &[String]) -> TantivyDocument {
+    let mut doc = doc!(fields.title=>d.title, fields.path=>d.path.clone());
+    if d.code { doc.add_text(fields.code, &d.body); }
+    if d.cased { doc.add_text(fields.body_cs, &d.body); }
+    doc.add_text(fields.body, d.body);
+    if let Some(p) = d.page { doc.add_u64(fields.page, p as u64); }
+    if let Some(sec) = d.section { doc.add_text(fields.section, sec); }
+    if let Some(folder) = library::owning_folder(Path::new(&d.path), folders) { doc.add_text(fields.folder, folder); }
+    if let Some(author) = d.author { doc.add_text(fields.author, author); }
+    if let Some(created) = d.created { doc.add_text(fields.created, created); }
+    if let Some(mtime) = d.mtime { doc.add_u64(fields.mtime, mtime); }
+    if let Some(size) = d.size { doc.add_u64(fields.size, size); }
+    if let Some(extractor) = d.extractor { doc.add_text(fields.extractor, extractor); }
+    doc
+}
+
+// The documents of one file, and whether its page limit cut it short.
+#[derive(Default)]
+struct ExtractedFile {
+    docs: Vec<IndexDoc>,
+    truncated: Option<TruncatedFile>,
+    // Path and fingerprint of a file that extracted to no text (an image-only PDF, an empty note)
+    empty: Option<(String, (u64, u64))>,
+}
+
+// path -> (mtime, size) at which the file extracted to no text
+type EmptyFiles = std::collections::HashMap<String, (u64, u64)>;
+
+impl ExtractedFile {
+    // Text held in memory, for the rebuild's buffer budget
+    fn text_bytes(&self) -> usize { self.docs.iter().map(|d| d.title.len() + d.body.len()).sum() }
+}
+
+// Settings that apply to every file of one index run.
+// Also used by the fallback scan in commands/search.rs.
+pub(crate) struct ExtractConfig {
+    policy: RetryPolicy,
+    pub(crate) extractors: Extractors,
+}
+
+impl ExtractConfig {
+    pub(crate) fn from_state(state: &AppState) -> Self { Self::with_pdf_pages(state, None) }
+
+    // Reading at most `max_pdf_pages` of each PDF instead of the configured limit
+    pub(crate) fn with_pdf_pages(state: &AppState, max_pdf_pages: Option<u32>) -> Self {
+        let settings = settings::read_settings(state);
+        let max_pdf_pages = max_pdf_pages.unwrap_or(settings.max_pdf_pages);
+        let extractors = Extractors::standard(&settings, library::extra_text_exts(state), &state.app_dir.join("cache"), max_pdf_pages);
+        ExtractConfig { policy: settings.read_retry, extractors }
+    }
+
+    fn is_indexable(&self, path: &Path) -> bool { self.extractors.supports(path) }
+}
+
+// Extract the indexable documents of one file. Reads that fail transiently (network drives,
+// cloud placeholders being hydrated) are retried per the configured policy; files that still
+// cannot be read or extracted are returned as errors for the index report.
+fn extract_file(path: &Path, config: &ExtractConfig) -> Result<ExtractedFile, IndexFileError> {
+    let key = path.to_string_lossy().to_string();
+    let fail = |error: String, attempts: u32| IndexFileError { path: key.clone(), error, attempts };
+    if let Some(real) = retry::icloud_stub_target(path) {
+        if config.is_indexable(&real) {
+            retry::request_hydration(path);
+            return Err(IndexFileError { path: real.to_string_lossy().to_string(), error: "cloud placeholder not downloaded yet".into(), attempts: 0 });
+        }
+        return Ok(ExtractedFile::default());
+    }
+    let Some(extractor) = config.extractors.for_path(path) else { return Ok(ExtractedFile::default()) };
+    if retry::is_offline_placeholder(path) {
+        return Err(fail("file is offline (cloud placeholder)".into(), 0));
+    }
+    let (probe, attempts) = retry::retry_with_backoff(&config.policy, retry::is_transient_io, || retry::probe_readable(path));
+    if let Err(e) = probe { return Err(fail(e.to_string(), attempts)); }
+    let fp = file_fp(path);
+
+    let extracted = extractor.extract(path).map_err(|e| fail(e.to_string(), attempts))?;
+    let truncated = extracted
+        .total_pages
+        .zip(extracted.last_page)
+        .filter(|&(total, last)| total > last as usize)
+        .map(|(total_pages, last_indexed_page)| TruncatedFile { path: key.clone(), last_indexed_page, total_pages });
+    // File-level attributes are repeated on every page/chapter/section document
+    let docs = extracted
+        .parts
+        .into_iter()
+        .map(|part| IndexDoc {
+            title: part.title.unwrap_or_else(|| extracted.title.clone()),
+            path: part.member.map_or_else(|| key.clone(), |member| member_path(&key, &member)),
+            page: part.page,
+            section: part.section,
+            body: part.body,
+            author: extracted.author.clone(),
+            created: extracted.created.clone(),
+            mtime: fp.map(|(mtime, _)| mtime),
+            size: fp.map(|(_, size)| size),
+            code: extracted.code,
+            extractor: extracted.which.clone(),
+            cased: true,
+        })
+        .collect::<Vec<IndexDoc>>();
+    let empty = fp.filter(|_| docs.iter().all(|d| d.body.trim().is_empty())).map(|fp| (key.clone(), fp));
+    Ok(ExtractedFile { docs, truncated, empty })
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()).unwrap_or("").eq_ignore_ascii_case("pdf")
+}
+
+// Pools for extraction: one for most files, with a conservative thread count to reduce I/O/CPU
+// thrash, and a small one for PDFs (Settings::pdf_threads)
+fn extraction_pools(state: &AppState) -> Result<(rayon::ThreadPool, rayon::ThreadPool), String> {
     let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
     let num_threads = threads.min(8).max(2);
+    let pdf_threads = settings::read_settings(state).pdf_threads.clamp(1, 8);
     let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().map_err(|e| e.to_string())?;
-    let docs: Vec<(String, String, Option<u32>, Option<String>, String)> = pool.install(|| {
-        all_files
-            .par_iter()
-            .flat_map_iter(|path| {
-                let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
-                if is_supported_text(path) {
-                    if let Ok((title, text)) = extract_title_and_text(path) {
-                        return vec![(title, path.to_string_lossy().to_string(), None, None, text)];
-                    }
-                } else if ext == "pdf" {
-                    if let Ok((title, pages, which)) = extract_pdf_pages_cached(path, &cache_root, MAX_PDF_PAGES_INDEX) {
-                        return pages
-                            .into_iter()
-                            .map(|(page, body)| (title.clone(), path.to_string_lossy().to_string(), Some(page), Some(which.clone()), body))
-                            .collect();
-                    }
-                }
-                Vec::new()
-            })
-            .collect()
+    let pdf_pool = ThreadPoolBuilder::new().num_threads(pdf_threads).build().map_err(|e| e.to_string())?;
+    Ok((pool, pdf_pool))
+}
+
+// Map `f` over `files` with PDFs on `pdf_pool` and everything else on `pool`, both at once, so
+// PDFs waiting their turn at pdfium never hold up text files, which keep all of `pool`. Results keep the order of `files` within each kind, non-PDFs first.
+fn map_by_type<T: Send>(
+    files: &[PathBuf],
+    pool: &rayon::ThreadPool,
+    pdf_pool: &rayon::ThreadPool,
+    f: &(dyn Fn(&PathBuf) -> T + Sync),
+) -> Vec<T> {
+    let (pdfs, others): (Vec<&PathBuf>, Vec<&PathBuf>) = files.iter().partition(|p| is_pdf(p));
+    let (mut out, pdf_out) = std::thread::scope(|scope| {
+        let pdf_out = scope.spawn(|| pdf_pool.install(|| pdfs.par_iter().map(|p| f(p)).collect::<Vec<T>>()));
+        let out = pool.install(|| others.par_iter().map(|p| f(p)).collect::<Vec<T>>());
+        (out, pdf_out.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+    });
+    out.extend(pdf_out);
+    out
+}
+
+// Extract `files` on `pool` (PDFs on `pdf_pool`) and hand their documents to `add` on the calling thread as each file
+// finishes. The queue is bounded in files and `budget` in extracted bytes, so extraction waits
+// when `add` falls behind instead of piling up documents. Files finish in any order.
+fn extract_streaming(
+    files: &[PathBuf],
+    config: &ExtractConfig,
+    pool: &rayon::ThreadPool,
+    pdf_pool: &rayon::ThreadPool,
+    budget: &ByteBudget,
+    on_file: &(dyn Fn(&Path) + Sync),
+    add: &mut dyn FnMut(IndexDoc),
+) -> (Vec<IndexFileError>, Vec<TruncatedFile>, EmptyFiles) {
+    let (tx, rx) = mpsc::sync_channel::<(Result<ExtractedFile, IndexFileError>, usize)>(EXTRACT_QUEUE);
+    let mut errors = Vec::new();
+    let mut truncated = Vec::new();
+    let mut empty = EmptyFiles::new();
+    std::thread::scope(|scope| {
+        scope.spawn(move || map_by_type(files, pool, pdf_pool, &|path| {
+            let res = extract_file(path, config);
+            on_file(path);
+            let bytes = res.as_ref().map(|f| f.text_bytes()).unwrap_or(0);
+            budget.acquire(bytes);
+            let _ = tx.send((res, bytes));
+        }));
+        for (res, bytes) in rx {
+            for d in record_extracted(res, &mut errors, &mut truncated, &mut empty) { add(d); }
+            budget.release(bytes);
+        }
     });
+    (errors, truncated, empty)
+}
 
-    // Add to index serially
-    for (title, path, page, section, body) in docs {
-        if let Some(p) = page {
-            if let Some(sec) = section {
-                let _ = writer.add_document(doc!(fields.title=>title, fields.path=>path, fields.page=>p as u64, fields.section=>sec, fields.body=>body));
-            } else {
-                let _ = writer.add_document(doc!(fields.title=>title, fields.path=>path, fields.page=>p as u64, fields.body=>body));
-            }
-        } else {
-            if let Some(sec) = section {
-                let _ = writer.add_document(doc!(fields.title=>title, fields.path=>path, fields.section=>sec, fields.body=>body));
-            } else {
-                let _ = writer.add_document(doc!(fields.title=>title, fields.path=>path, fields.body=>body));
+// Bytes of extracted text buffered between the extraction threads and the writer, held under
+// `limit` (Settings::extract_buffer_bytes). A file bigger than the whole budget still goes
+// through, alone. Each extraction thread may hold one more file while it waits.
+struct ByteBudget {
+    limit: usize,
+    used: Mutex<usize>,
+    freed: std::sync::Condvar,
+    peak: AtomicUsize,
+}
+
+impl ByteBudget {
+    fn new(limit: usize) -> Self {
+        ByteBudget { limit, used: Mutex::new(0), freed: std::sync::Condvar::new(), peak: AtomicUsize::new(0) }
+    }
+
+    fn acquire(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        while *used > 0 && *used + bytes > self.limit {
+            used = self.freed.wait(used).unwrap_or_else(|e| e.into_inner());
+        }
+        *used += bytes;
+        self.peak.fetch_max(*used, Ordering::SeqCst);
+    }
+
+    fn release(&self, bytes: usize) {
+        *self.used.lock().unwrap_or_else(|e| e.into_inner()) -= bytes;
+        self.freed.notify_all();
+    }
+
+    // Most bytes buffered at once so far
+    fn peak(&self) -> usize { self.peak.load(Ordering::SeqCst) }
+}
+
+fn split_extracted(extracted: Vec<Result<ExtractedFile, IndexFileError>>) -> (Vec<IndexDoc>, Vec<IndexFileError>, Vec<TruncatedFile>, EmptyFiles) {
+    let mut docs = Vec::new();
+    let mut errors = Vec::new();
+    let mut truncated = Vec::new();
+    let mut empty = EmptyFiles::new();
+    for r in extracted {
+        docs.extend(record_extracted(r, &mut errors, &mut truncated, &mut empty));
+    }
+    (docs, errors, truncated, empty)
+}
+
+// The documents of one extraction result; a failure, page truncation or lack of text is logged and noted.
+fn record_extracted(r: Result<ExtractedFile, IndexFileError>, errors: &mut Vec<IndexFileError>, truncated: &mut Vec<TruncatedFile>, empty: &mut EmptyFiles) -> Vec<IndexDoc> {
+    match r {
+        Ok(file) => {
+            empty.extend(file.empty);
+            if let Some(t) = file.truncated {
+                eprintln!("quietlibrary: index truncated file={} through page={} of {}", t.path, t.last_indexed_page, t.total_pages);
+                truncated.push(t);
             }
+            file.docs
+        }
+        Err(e) => {
+            eprintln!("quietlibrary: index skipped file={} attempts={} error={}", e.path, e.attempts, e.error);
+            errors.push(e);
+            Vec::new()
         }
     }
+}
 
-    writer.commit().map_err(|e| e.to_string())?;
-    Ok(())
+fn report_path(state: &AppState) -> PathBuf { state.app_dir.join("index_report.json") }
+
+// Outcome of the most recent rebuild/incremental update (files that could not be indexed).
+pub fn load_report(state: &AppState) -> IndexReport {
+    fs::read(report_path(state)).ok().and_then(|b| serde_json::from_slice(&b).ok()).unwrap_or_default()
+}
+
+fn save_report(state: &AppState, report: &IndexReport) {
+    let _ = fs::write(report_path(state), serde_json::to_vec_pretty(report).unwrap_or_default());
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Default)]
-struct Fingerprints { entries: std::collections::HashMap<String, (u64, u64)> } // path -> (mtime,size)
+struct Fingerprints {
+    entries: std::collections::HashMap<String, (u64, u64)>, // path -> (mtime,size)
+    // path -> `content_hash`, for files seen while `Settings::content_hash_fingerprints` was on
+    #[serde(default)]
+    hashes: std::collections::HashMap<String, u64>,
+    // Files that extracted to no text. Updates skip them while their (mtime, size) stays the
+    // same; a rebuild reads them again.
+    #[serde(default)]
+    empty: EmptyFiles,
+}
 
 fn load_fingerprints(dir: &Path) -> Fingerprints {
     let p = dir.join("fingerprints.json");
@@ -123,28 +625,111 @@ fn load_fingerprints(dir: &Path) -> Fingerprints {
 
 fn save_fingerprints(dir: &Path, fp: &Fingerprints) { let _ = fs::write(dir.join("fingerprints.json"), serde_json::to_vec(fp).unwrap_or_default()); }
 
-fn file_fp(path: &Path) -> Option<(u64, u64)> {
+// (mtime secs, size bytes)
+pub(crate) fn file_fp(path: &Path) -> Option<(u64, u64)> {
     let meta = fs::metadata(path).ok()?;
     let size = meta.len();
     let mtime = meta.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs();
     Some((mtime, size))
 }
 
-fn open_or_create_index(dir: &Path) -> Result<Index, String> {
-    let (sch, _fields) = schema();
-    if dir.exists() { Index::open_in_dir(dir).map_err(|e| e.to_string()) }
-    else { fs::create_dir_all(dir).ok(); Index::create_in_dir(dir, sch).map_err(|e| e.to_string()) }
+// Bytes of each file covered by `content_hash`. A change beyond them that keeps the size and
+// only the mtime reveals goes unnoticed, the price of not reading whole files.
+const FINGERPRINT_HASH_BYTES: u64 = 64 * 1024;
+
+// Hash of a file's first FINGERPRINT_HASH_BYTES and its size. Stored hashes outlive the
+// build that wrote them, so this is FNV-1a rather than std's `DefaultHasher`, whose output
+// may change between Rust releases.
+fn content_hash(path: &Path) -> Option<u64> {
+    let file = fs::File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    let mut prefix = Vec::new();
+    file.take(FINGERPRINT_HASH_BYTES).read_to_end(&mut prefix).ok()?;
+    Some(fnv1a(prefix.iter().chain(&size.to_le_bytes())))
+}
+
+// 64-bit FNV-1a
+fn fnv1a<'a>(bytes: impl Iterator<Item = &'a u8>) -> u64 {
+    bytes.fold(0xcbf2_9ce4_8422_2325, |h, b| (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3))
+}
+
+// (mtime, size) and, with `hashing`, `content_hash` of each file that can be read.
+fn fingerprint_files(files: &[PathBuf], hashing: bool, pool: &rayon::ThreadPool) -> Fingerprints {
+    let found = pool.install(|| files.par_iter().map(|p| (p.to_string_lossy().to_string(), file_fp(p), if hashing { content_hash(p) } else { None })).collect::<Vec<_>>());
+    let mut fps = Fingerprints::default();
+    for (key, fp, hash) in found {
+        let Some(fp) = fp else { continue };
+        if let Some(h) = hash { fps.hashes.insert(key.clone(), h); }
+        fps.entries.insert(key, fp);
+    }
+    fps
+}
+
+fn open_or_create_index(dir: &Path, state: &AppState) -> Result<Index, String> {
+    if dir.exists() { open_index(dir) }
+    else { fs::create_dir_all(dir).ok(); create_index(dir, &meta_from_settings(state)?) }
+}
+
+// Smallest writer heap Tantivy accepts (one indexing thread)
+const MIN_WRITER_HEAP: usize = 15_000_000;
+// Tantivy rejects a per-thread arena near 4 GB; far beyond what any library here needs
+const MAX_WRITER_HEAP: usize = 2 * 1024 * 1024 * 1024;
+
+// Heap of a full rebuild, and the most an incremental writer grows to (Settings::writer_heap_bytes).
+// `Index::writer` divides it between its threads, using fewer threads when each would get
+// less than the minimum.
+fn writer_heap_limit(state: &AppState) -> usize {
+    settings::read_settings(state).writer_heap_bytes.clamp(MIN_WRITER_HEAP, MAX_WRITER_HEAP)
+}
+
+// Writer heap for re-adding `bytes` of source files, at most `limit`. Extracted text is rarely
+// bigger than the file and Tantivy's arena needs a small multiple of the text, so small
+// updates (the watcher saving one note) stay at the minimum.
+fn update_heap(bytes: u64, limit: usize) -> usize {
+    usize::try_from(bytes.saturating_mul(4)).unwrap_or(usize::MAX).clamp(MIN_WRITER_HEAP, limit.max(MIN_WRITER_HEAP))
 }
 
-pub fn incremental_update(state: &AppState) -> Result<(), String> {
+// The live index's writer, kept in `AppState` between updates.
+pub struct CachedWriter {
+    writer: tantivy::IndexWriter,
+    heap: usize,
+}
+
+// Tantivy allows a single writer per index, so every write to the live index goes through
+// the one cached here. It is opened on first use and replaced only when an update needs a
+// bigger heap than it has; `drop_cached_index` closes it (and releases its lock file).
+fn cached_writer<'a>(state: &'a AppState, dir: &Path, heap: usize) -> Result<std::sync::MutexGuard<'a, Option<CachedWriter>>, QuietError> {
+    let mut guard = lock_cache(&state.writer);
+    if guard.as_ref().is_some_and(|w| w.heap >= heap) { return Ok(guard); }
+    // Release the old writer's lock before opening its replacement
+    *guard = None;
+    let index = {
+        let mut idx_lock = lock_cache(&state.index);
+        if idx_lock.is_none() { *idx_lock = Some(open_or_create_index(dir, state)?); }
+        idx_lock.as_ref().ok_or(QuietError::IndexMissing)?.clone()
+    };
+    *guard = Some(CachedWriter { writer: index.writer(heap)?, heap });
+    Ok(guard)
+}
+
+// Commit the cached writer. A writer whose commit failed is discarded so the next update
+// starts from the last good commit.
+fn commit_cached(guard: &mut Option<CachedWriter>) -> Result<(), QuietError> {
+    let Some(cached) = guard.as_mut() else { return Err(QuietError::IndexMissing) };
+    if let Err(e) = cached.writer.commit() {
+        *guard = None;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+pub fn incremental_update(state: &AppState) -> Result<IndexUpdateStats, QuietError> {
     let dir = index_dir(state);
-    let index = open_or_create_index(&dir)?;
     let (_, fields) = schema();
 
     // Collect current files
-    let mut all_files: Vec<PathBuf> = Vec::new();
-    let folders = crate::commands::library::watched_folders(state);
-    for folder in folders { gather_files(&PathBuf::from(folder), &mut all_files)?; }
+    let folders = library::watched_folders(state);
+    let (all_files, unreachable) = gather_library(state, &folders)?;
 
     let mut current_fp: std::collections::HashMap<String, (u64, u64)> = std::collections::HashMap::new();
     let mut changed: Vec<PathBuf> = Vec::new();
@@ -152,209 +737,2094 @@ pub fn incremental_update(state: &AppState) -> Result<(), String> {
         if let Some((mt, sz)) = file_fp(p) { current_fp.insert(p.to_string_lossy().to_string(), (mt, sz)); }
     }
     let prev = load_fingerprints(&dir);
+    let hashing = settings::read_settings(state).content_hash_fingerprints;
+    let mut hashes: std::collections::HashMap<String, u64> = std::collections::HashMap::new();
+    let mut empty = EmptyFiles::new();
+    let mut stats = IndexUpdateStats::default();
     for p in &all_files {
         let key = p.to_string_lossy().to_string();
         let cur = current_fp.get(&key).copied();
         let old = prev.entries.get(&key).copied();
-        if cur != old { changed.push(p.clone()); }
+        let old_hash = prev.hashes.get(&key).copied();
+        // Unchanged files that had no text aren't read again
+        let known_empty = cur.is_some() && prev.empty.get(&key).copied() == cur;
+        if let (true, Some(fp)) = (known_empty, cur) { empty.insert(key.clone(), fp); }
+        if cur == old || known_empty {
+            // Files indexed before hashing was turned on get their hash now
+            if let Some(h) = old_hash.or_else(|| if hashing { content_hash(p) } else { None }) { hashes.insert(key, h); }
+            continue;
+        }
+        // mtime and size are the quick check; the hash is only read when they differ
+        let hash = if hashing { content_hash(p) } else { None };
+        if let Some(h) = hash { hashes.insert(key, h); }
+        // A new mtime on the same size and content is a rewrite, not a change
+        if hash.is_some() && hash == old_hash && old.map(|(_, size)| size) == cur.map(|(_, size)| size) { continue; }
+        if old.is_some() { stats.changed += 1; } else { stats.added += 1; }
+        changed.push(p.clone());
     }
-    // Deleted files
+    // Deleted files. Files under a folder that could not be scanned are kept, fingerprint and
+    // all, until the folder is back and shows whether they are really gone.
     let mut deleted: Vec<String> = Vec::new();
-    for (k, _) in prev.entries.iter() {
-        if !current_fp.contains_key(k) { deleted.push(k.clone()); }
+    for (k, fp) in prev.entries.iter() {
+        if current_fp.contains_key(k) { continue; }
+        if unreachable.iter().any(|root| Path::new(k).starts_with(root)) {
+            current_fp.insert(k.clone(), *fp);
+            if let Some(h) = prev.hashes.get(k) { hashes.insert(k.clone(), *h); }
+            if let Some(fp) = prev.empty.get(k) { empty.insert(k.clone(), *fp); }
+        }
+        else { deleted.push(k.clone()); }
     }
+    // Renamed or moved files keep their indexed documents, re-filed under the new path
+    let added: Vec<String> = changed.iter().map(|p| p.to_string_lossy().to_string()).filter(|k| !prev.entries.contains_key(k)).collect();
+    let renames = detect_renames(&deleted, &added, &prev.entries, &current_fp);
+    let moved = if renames.is_empty() { vec![] } else { renamed_docs(&cached_reader(state, &dir)?.searcher(), &fields, &renames, &folders)? };
+    stats.renamed = moved.len();
+    stats.added -= moved.len();
+    stats.deleted = deleted.len() - moved.len();
+    // Every path whose documents must go before re-adding: removed files plus new and changed
+    // ones (a file the last rebuild failed to fingerprint may still have documents)
+    let stale: Vec<String> = deleted.into_iter().chain(changed.iter().map(|p| p.to_string_lossy().to_string())).collect();
+    let moved_to: std::collections::HashSet<&str> = moved.iter().map(|(to, _)| to.as_str()).collect();
+    changed.retain(|p| !moved_to.contains(p.to_string_lossy().as_ref()));
 
     // Extract changed in parallel
-    let cache_root = state.app_dir.join("cache");
-    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
-    let num_threads = threads.min(8).max(2);
-    let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().map_err(|e| e.to_string())?;
-    let docs: Vec<(String, String, Option<u32>, Option<String>, String)> = pool.install(|| {
-        changed
-            .par_iter()
-            .flat_map_iter(|path| {
-                let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
-                if is_supported_text(path) {
-                    if let Ok((title, text)) = extract_title_and_text(path) {
-                        return vec![(title, path.to_string_lossy().to_string(), None, None, text)];
-                    }
-                } else if ext == "pdf" {
-                    if let Ok((title, pages, which)) = extract_pdf_pages_cached(path, &cache_root, MAX_PDF_PAGES_INDEX) {
-                        return pages
-                            .into_iter()
-                            .map(|(page, body)| (title.clone(), path.to_string_lossy().to_string(), Some(page), Some(which.clone()), body))
-                            .collect();
-                    }
-                }
-                Vec::new()
-            })
-            .collect()
-    });
+    let config = ExtractConfig::from_state(state);
+    let (pool, pdf_pool) = extraction_pools(state)?;
+    let extracted = map_by_type(&changed, &pool, &pdf_pool, &|path| extract_file(path, &config));
+    let (docs, errors, mut truncated, new_empty) = split_extracted(extracted);
+    empty.extend(new_empty);
+    // Truncation found by earlier runs still holds for files this run didn't touch
+    let touched: std::collections::HashSet<&String> = stale.iter().collect();
+    for t in load_report(state).truncated {
+        match renames.iter().find(|(from, to)| *from == t.path && moved_to.contains(to.as_str())) {
+            Some((_, to)) => truncated.push(TruncatedFile { path: to.clone(), ..t }),
+            None if !touched.contains(&t.path) => truncated.push(t),
+            None => {}
+        }
+    }
+    truncated.sort_by(|a, b| a.path.cmp(&b.path));
+    // Leave failed files without a fingerprint so the next update retries them
+    for e in &errors { current_fp.remove(&e.path); }
 
     // Apply to index
-    let mut writer = index.writer(128 * 1024 * 1024).map_err(|e| e.to_string())?;
-    // Delete removed or changed paths before re-adding
-    for k in deleted.iter().chain(changed.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>().iter()) {
-        let term = tantivy::Term::from_field_text(fields.path, k);
-        writer.delete_term(term);
-    }
-    for (title, path, page, section, body) in docs {
-        if let Some(p) = page {
-            if let Some(sec) = section {
-                let _ = writer.add_document(doc!(fields.title=>title, fields.path=>path, fields.page=>p as u64, fields.section=>sec, fields.body=>body));
-            } else {
-                let _ = writer.add_document(doc!(fields.title=>title, fields.path=>path, fields.page=>p as u64, fields.body=>body));
-            }
-        } else {
-            if let Some(sec) = section {
-                let _ = writer.add_document(doc!(fields.title=>title, fields.path=>path, fields.section=>sec, fields.body=>body));
-            } else {
-                let _ = writer.add_document(doc!(fields.title=>title, fields.path=>path, fields.body=>body));
-            }
+    let changed_bytes: u64 = changed.iter().filter_map(|p| current_fp.get(p.to_string_lossy().as_ref())).map(|(_, size)| size).sum();
+    let mut guard = cached_writer(state, &dir, update_heap(changed_bytes, writer_heap_limit(state)))?;
+    if let Some(cached) = guard.as_mut() {
+        for k in &stale { delete_file_docs(&cached.writer, &fields, k); }
+        // Indexes built before the `code`, `extractor` and `body_cs` fields existed would reject the whole document
+        let schema = cached.writer.index().schema();
+        let (has_code, has_extractor, has_cased) = (schema.get_field("code").is_ok(), schema.get_field("extractor").is_ok(), schema.get_field("body_cs").is_ok());
+        for mut d in docs {
+            d.code &= has_code;
+            d.cased &= has_cased;
+            if !has_extractor { d.extractor = None; }
+            let _ = cached.writer.add_document(make_doc(&fields, d, &folders));
+        }
+        for document in moved.into_iter().flat_map(|(_, documents)| documents) {
+            let _ = cached.writer.add_document(document);
         }
     }
-    writer.commit().map_err(|e| e.to_string())?;
+    commit_cached(&mut guard)?;
+    drop(guard);
 
     // Save new fingerprint set
-    save_fingerprints(&dir, &Fingerprints { entries: current_fp });
-    // Drop cached index/reader to pick up new segments
-    drop_cached_index(state);
+    hashes.retain(|k, _| current_fp.contains_key(k));
+    empty.retain(|k, _| current_fp.contains_key(k));
+    save_fingerprints(&dir, &Fingerprints { entries: current_fp, hashes, empty });
+    save_report(state, &IndexReport { errors, truncated, unreachable });
+    // Cached readers pick up the new segments on their next reload
+    Ok(stats)
+}
+
+// Pairs (old path, new path) of files that look renamed or moved: a vanished file and a new
+// one with the same extension and (mtime, size), which a rename keeps. A fingerprint shared by
+// several vanished or several new files (copies) is ambiguous and left to delete and re-add.
+fn detect_renames(deleted: &[String], added: &[String], old_fp: &std::collections::HashMap<String, (u64, u64)>, new_fp: &std::collections::HashMap<String, (u64, u64)>) -> Vec<(String, String)> {
+    type Candidates<'a> = std::collections::HashMap<(Option<String>, (u64, u64)), Vec<&'a String>>;
+    fn group<'a>(paths: &'a [String], fps: &std::collections::HashMap<String, (u64, u64)>) -> Candidates<'a> {
+        let mut out = Candidates::new();
+        for p in paths {
+            let ext = Path::new(p).extension().map(|e| e.to_string_lossy().to_ascii_lowercase());
+            if let Some(fp) = fps.get(p) { out.entry((ext, *fp)).or_default().push(p); }
+        }
+        out
+    }
+    let gone = group(deleted, old_fp);
+    let mut renames: Vec<(String, String)> = group(added, new_fp)
+        .into_iter()
+        .filter_map(|(key, to)| {
+            let from = gone.get(&key)?;
+            (from.len() == 1 && to.len() == 1).then(|| (from[0].clone(), to[0].clone()))
+        })
+        .collect();
+    renames.sort();
+    renames
+}
+
+// The indexed documents of each renamed file remade under its new path, keyed by that path.
+// `folder` follows the move, and a title that was the file name (or stem) becomes the new
+// one. Renames with nothing in the index are left out, so those files get extracted.
+fn renamed_docs(searcher: &tantivy::Searcher, fields: &IndexFields, renames: &[(String, String)], folders: &[String]) -> Result<Vec<(String, Vec<TantivyDocument>)>, QuietError> {
+    let mut out = Vec::new();
+    for (from, to) in renames {
+        let query = tantivy::query::TermQuery::new(tantivy::Term::from_field_text(fields.path, from), IndexRecordOption::Basic);
+        let mut documents = Vec::new();
+        for address in searcher.search(&query, &tantivy::collector::DocSetCollector)? {
+            let old: TantivyDocument = searcher.doc(address)?;
+            let mut document = TantivyDocument::default();
+            for (field, value) in old.field_values() {
+                if field != fields.path && field != fields.title { document.add_field_value(field, value); }
+            }
+            let title = old.get_first(fields.title).and_then(|v| v.as_str()).unwrap_or_default();
+            document.add_text(fields.title, renamed_title(title, Path::new(from), Path::new(to)));
+            document.add_text(fields.path, to);
+            if let Some(folder) = library::owning_folder(Path::new(to), folders) { document.add_text(fields.folder, folder); }
+            documents.push(document);
+        }
+        if !documents.is_empty() { out.push((to.clone(), documents)); }
+    }
+    Ok(out)
+}
+
+fn renamed_title(title: &str, from: &Path, to: &Path) -> String {
+    let name = |p: &Path| p.file_name().map(|n| n.to_string_lossy().to_string());
+    let stem = |p: &Path| p.file_stem().map(|n| n.to_string_lossy().to_string());
+    if name(from).as_deref() == Some(title) { return name(to).unwrap_or_default(); }
+    if stem(from).as_deref() == Some(title) { return stem(to).unwrap_or_default(); }
+    title.to_string()
+}
+
+// Delete the documents of the file at `path`, for an archive those of its members.
+fn delete_file_docs(writer: &tantivy::IndexWriter, fields: &IndexFields, path: &str) {
+    writer.delete_term(tantivy::Term::from_field_text(fields.path, path));
+    if is_zip(Path::new(path)) {
+        let members = format!("{}.*", regex::escape(&member_path(path, "")));
+        match tantivy::query::RegexQuery::from_pattern(&members, fields.path) {
+            Ok(query) => { let _ = writer.delete_query(Box::new(query)); }
+            Err(e) => eprintln!("quietlibrary: could not remove archive members file={} err={}", path, e),
+        }
+    }
+}
+
+// Drop one file from the index, its fingerprint and its PDF extraction cache, e.g. after it
+// was deleted or moved, without a full reindex.
+pub fn remove_path(state: &AppState, path: &str) -> Result<(), QuietError> {
+    let dir = index_dir(state);
+    if !dir.exists() { return Err(QuietError::IndexMissing); }
+    let (_, fields) = schema();
+    let mut guard = cached_writer(state, &dir, MIN_WRITER_HEAP)?;
+    if let Some(cached) = guard.as_mut() { delete_file_docs(&cached.writer, &fields, path); }
+    commit_cached(&mut guard)?;
+    drop(guard);
+    let mut fps = load_fingerprints(&dir);
+    let had_entry = fps.entries.remove(path).is_some();
+    if fps.empty.remove(path).is_some() || had_entry {
+        fps.hashes.remove(path);
+        save_fingerprints(&dir, &fps);
+    }
+    if is_pdf(Path::new(path)) {
+        let _ = remove_cached_document(Path::new(path), &state.app_dir.join("cache"));
+    }
     Ok(())
 }
 
-fn gather_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
-    if !dir.exists() { return Ok(()); }
+// Sort gathered files and keep one path per file. Nested watched folders list the same path
+// twice, and overlapping roots reached through symlinks list one file under different paths,
+// so duplicates are found by canonical path; the first path in sorted order is kept.
+fn dedup_files(files: &mut Vec<PathBuf>) {
+    files.sort();
+    files.dedup();
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|f| seen.insert(fs::canonicalize(f).unwrap_or_else(|_| f.clone())));
+}
+
+// Files under every watched folder, one path per file, plus the folders that could not be
+// read at all: missing (an unplugged drive) or without permission. Each of those is logged.
+fn gather_library(state: &AppState, folders: &[String]) -> Result<(Vec<PathBuf>, Vec<String>), String> {
+    let mut all_files: Vec<PathBuf> = Vec::new();
+    let mut unreachable = Vec::new();
+    for folder in folders {
+        let root = PathBuf::from(folder);
+        if let Err(e) = fs::read_dir(&root) {
+            eprintln!("quietlibrary: watched folder unreachable folder={} error={}", folder, e);
+            unreachable.push(folder.clone());
+            continue;
+        }
+        let mut walk = library::FolderWalk::for_folder(state, &root);
+        gather_files(&root, &mut walk, &mut all_files)?;
+        walk.log_skipped();
+    }
+    dedup_files(&mut all_files);
+    Ok((all_files, unreachable))
+}
+
+// Walk the watched folders the way a rebuild does and tally what it would read, without
+// extracting or writing anything.
+pub fn preview_index(state: &AppState) -> Result<IndexPreview, QuietError> {
+    let folders = library::watched_folders(state);
+    let (files, unreachable) = gather_library(state, &folders)?;
+    let config = ExtractConfig::from_state(state);
+    let mut by_ext: std::collections::HashMap<String, ExtensionStats> = std::collections::HashMap::new();
+    for path in &files {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        let bytes = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let stats = by_ext.entry(ext.clone()).or_insert_with(|| ExtensionStats { ext, count: 0, bytes: 0, supported: config.extractors.supports(path) });
+        stats.count += 1;
+        stats.bytes += bytes;
+    }
+    let mut by_extension: Vec<ExtensionStats> = by_ext.into_values().collect();
+    by_extension.sort_by(|a, b| b.count.cmp(&a.count).then(a.ext.cmp(&b.ext)));
+    let unsupported_stats = by_extension.iter().filter(|e| !e.supported);
+    let unsupported = unsupported_stats.clone().map(|e| e.count).sum();
+    let mut unsupported_exts: Vec<String> = unsupported_stats.map(|e| e.ext.clone()).collect();
+    unsupported_exts.sort();
+    Ok(IndexPreview {
+        total_files: files.len(),
+        total_bytes: by_extension.iter().map(|e| e.bytes).sum(),
+        by_extension,
+        unsupported,
+        unsupported_exts,
+        unreachable,
+    })
+}
+
+// Copy the documents indexed under watched folder `folder` from `live` into `writer`.
+// Returns how many were copied.
+fn carry_over_folder(live: &Index, writer: &tantivy::IndexWriter, fields: &IndexFields, folder: &str) -> Result<usize, QuietError> {
+    let searcher = live.reader()?.searcher();
+    let query = tantivy::query::TermQuery::new(tantivy::Term::from_field_text(fields.folder, folder), IndexRecordOption::Basic);
+    let addresses = searcher.search(&query, &tantivy::collector::DocSetCollector)?;
+    for address in &addresses {
+        let mut document: TantivyDocument = searcher.doc(*address)?;
+        // `folder` is indexed but not stored
+        document.add_text(fields.folder, folder);
+        writer.add_document(document)?;
+    }
+    Ok(addresses.len())
+}
+
+// Files the index holds from watched folder `folder`; 0 without an index.
+pub(crate) fn indexed_file_count(state: &AppState, folder: &str) -> usize {
+    let dir = index_dir(state);
+    if !dir.exists() { return 0; }
+    let (_, fields) = schema();
+    let Ok(reader) = cached_reader(state, &dir) else { return 0 };
+    let query = tantivy::query::TermQuery::new(tantivy::Term::from_field_text(fields.folder, folder), IndexRecordOption::Basic);
+    reader.searcher().search(&query, &FileTypeCollector).map(|types| types.iter().map(|t| t.count).sum()).unwrap_or(0)
+}
+
+// Stored title of an indexed file, if it's in the index.
+pub(crate) fn indexed_title(state: &AppState, path: &str) -> Option<String> {
+    let dir = index_dir(state);
+    if !dir.exists() { return None; }
+    let (_, fields) = schema();
+    let searcher = cached_reader(state, &dir).ok()?.searcher();
+    let query = tantivy::query::TermQuery::new(tantivy::Term::from_field_text(fields.path, path), IndexRecordOption::Basic);
+    let (_, addr) = searcher.search(&query, &tantivy::collector::TopDocs::with_limit(1)).ok()?.into_iter().next()?;
+    let document: TantivyDocument = searcher.doc(addr).ok()?;
+    document.get_first(fields.title).and_then(|v| v.as_str()).filter(|t| !t.is_empty()).map(|t| t.to_string())
+}
+
+fn gather_files(dir: &Path, walk: &mut library::FolderWalk, out: &mut Vec<PathBuf>) -> Result<(), String> {
     for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
         let entry = match entry { Ok(e) => e, Err(_) => continue };
         let path = entry.path();
-        if path.is_dir() { gather_files(&path, out)?; }
+        let is_dir = path.is_dir();
+        if walk.is_excluded(&path, is_dir) { continue; }
+        if is_dir { if walk.enter_dir(&path) { gather_files(&path, walk, out)?; } }
         else { out.push(path); }
     }
     Ok(())
 }
 
-pub fn search_index(state: &AppState, q: &str, limit: usize) -> Result<Vec<SearchResult>, String> {
-    let t0 = std::time::Instant::now();
+// Open the index and its reader ahead of the first search, which otherwise pays for it. The
+// handles are opened without holding the caches, and only stored if no reindex dropped the
+// cached handles meanwhile and no search has opened its own.
+pub fn warm_index(state: &AppState) {
     let dir = index_dir(state);
-    if !dir.exists() { return Ok(vec![]); }
-    let (_, fields) = schema();
-    // Lazily open and cache index + reader in AppState for faster subsequent queries
+    if !dir.exists() { return; }
+    let generation = state.index_generation.load(std::sync::atomic::Ordering::SeqCst);
+    let t0 = std::time::Instant::now();
+    let opened = open_index(&dir).map_err(QuietError::from).and_then(|index| Ok((index.reader()?, index)));
+    let (reader, index) = match opened {
+        Ok(handles) => handles,
+        Err(e) => { eprintln!("quietlibrary: index warm-up failed error={}", e); return; }
+    };
+    // Reader before index, the order `cached_reader` takes them in
+    let mut reader_lock = lock_cache(&state.reader);
+    let mut idx_lock = lock_cache(&state.index);
+    if state.index_generation.load(std::sync::atomic::Ordering::SeqCst) != generation || idx_lock.is_some() || reader_lock.is_some() { return; }
+    *idx_lock = Some(index);
+    *reader_lock = Some(reader);
+    eprintln!("quietlibrary: warmed index elapsed={}ms", t0.elapsed().as_millis());
+}
+
+// Lazily open and cache index + reader in AppState so repeated queries (and keystrokes) reuse
+// them, then reload to pick up segments committed since.
+fn cached_reader(state: &AppState, dir: &Path) -> Result<tantivy::IndexReader, QuietError> {
     {
-        let mut idx_lock = state.index.lock().map_err(|_| "index lock".to_string())?;
+        let mut idx_lock = lock_cache(&state.index);
         if idx_lock.is_none() {
-            let index = Index::open_in_dir(&dir).map_err(|e| e.to_string())?;
+            let t0 = std::time::Instant::now();
+            let index = open_index(dir)?;
+            eprintln!("quietlibrary: opened index elapsed={}ms", t0.elapsed().as_millis());
             *idx_lock = Some(index);
         }
     }
-    {
-        let mut reader_lock = state.reader.lock().map_err(|_| "reader lock".to_string())?;
+    let reader = {
+        let mut reader_lock = lock_cache(&state.reader);
         if reader_lock.is_none() {
-            let idx_lock = state.index.lock().map_err(|_| "index lock".to_string())?;
-            let index = idx_lock.as_ref().ok_or_else(|| "index not available".to_string())?;
-            let reader = index.reader().map_err(|e| e.to_string())?;
-            *reader_lock = Some(reader);
+            let idx_lock = lock_cache(&state.index);
+            let index = idx_lock.as_ref().ok_or(QuietError::IndexMissing)?;
+            *reader_lock = Some(index.reader()?);
         }
-    }
-    let reader = {
-        let r = state.reader.lock().map_err(|_| "reader lock".to_string())?;
-        r.as_ref().unwrap().clone()
+        reader_lock.as_ref().ok_or(QuietError::IndexMissing)?.clone()
     };
-    // Pick up any new segments if index was rebuilt
     let _ = reader.reload();
-    let searcher = reader.searcher();
-    let idx_guard = state.index.lock().map_err(|_| "index lock".to_string())?;
-    let index_ref = idx_guard.as_ref().ok_or_else(|| "index not available".to_string())?;
-    let qp = tantivy::query::QueryParser::for_index(index_ref, vec![fields.title, fields.body]);
-    let query = qp.parse_query(q).map_err(|e| e.to_string())?;
-    let top_docs = searcher
-        .search(&query, &tantivy::collector::TopDocs::with_limit(limit))
-        .map_err(|e| e.to_string())?;
+    Ok(reader)
+}
+
+// Fields whose terms are whole words. Stemmed bodies store word stems, which make poor
+// suggestions, so only titles are used then.
+fn word_fields(dir: &Path) -> Vec<Field> {
+    let (_, fields) = schema();
+    let mut out = vec![fields.title];
+    if !load_index_meta(dir).body_tokenizer.starts_with(STEM_TOKENIZER_PREFIX) { out.push(fields.body); }
+    out
+}
+
+// Completions for the last word of `prefix` from the title and body term dictionaries, most
+// frequent first (document frequency summed over segments and fields).
+pub fn suggest_terms(state: &AppState, prefix: &str, limit: usize) -> Result<Vec<String>, QuietError> {
+    let dir = index_dir(state);
+    if !dir.exists() || limit == 0 { return Ok(vec![]); }
+    let searcher = cached_reader(state, &dir)?.searcher();
+    // Terms are stored lowercased and accent-folded
+    let word = match fold_words(prefix.split_whitespace().last().unwrap_or("")).pop() { Some(w) => w, None => return Ok(vec![]) };
+    let mut freqs: std::collections::HashMap<String, u32> = std::collections::HashMap::new();
+    let fields = word_fields(&dir);
+    for segment in searcher.segment_readers() {
+        for field in &fields {
+            let inverted = segment.inverted_index(*field)?;
+            let mut stream = inverted.terms().range().ge(word.as_bytes()).into_stream()?;
+            while stream.advance() {
+                if !stream.key().starts_with(word.as_bytes()) { break; }
+                let term = String::from_utf8_lossy(stream.key()).to_string();
+                *freqs.entry(term).or_default() += stream.value().doc_freq;
+            }
+        }
+    }
+    let mut ranked: Vec<(String, u32)> = freqs.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    Ok(ranked.into_iter().take(limit).map(|(term, _)| term).collect())
+}
+
+// Largest edit distance for a "did you mean" correction
+const MAX_CORRECTION_DISTANCE: usize = 2;
+
+// Building the automaton tables is the costly part, so it's done once
+static CORRECTION_AUTOMATA: Lazy<LevenshteinAutomatonBuilder> = Lazy::new(|| LevenshteinAutomatonBuilder::new(MAX_CORRECTION_DISTANCE as u8, false));
+
+// Terms within `MAX_CORRECTION_DISTANCE` edits of a word, as a term dictionary search.
+struct WithinDistance<'a>(&'a DFA);
+
+impl tantivy_fst::Automaton for WithinDistance<'_> {
+    type State = u32;
+    fn start(&self) -> u32 { self.0.initial_state() }
+    fn is_match(&self, state: &u32) -> bool { matches!(self.0.distance(*state), Distance::Exact(_)) }
+    fn can_match(&self, state: &u32) -> bool { *state != SINK_STATE }
+    fn accept(&self, state: &u32, byte: u8) -> u32 { self.0.transition(*state, byte) }
+}
+
+// A corrected query for one that found nothing: each word missing from (or rare in) the index
+// is replaced by the closest indexed word within `MAX_CORRECTION_DISTANCE` edits, preferring
+// fewer edits, then more documents. A replacement must appear in over twice as many documents
+// as the word it replaces. None when no word needs changing.
+pub fn suggest_correction(state: &AppState, q: &str) -> Result<Option<String>, QuietError> {
+    let dir = index_dir(state);
+    if !dir.exists() { return Ok(None); }
+    let searcher = cached_reader(state, &dir)?.searcher();
+    let words: Vec<String> = snippet_query(q).split_whitespace().map(|w| w.to_string()).collect();
+    let folded: Vec<Option<String>> = words.iter().map(|w| Some(fold_words(w)).filter(|f| f.len() == 1).and_then(|mut f| f.pop())).collect();
+    // Document frequency of each query word, and its best (distance, doc freq, term) candidate
+    let mut own: Vec<u32> = vec![0; words.len()];
+    let mut best: Vec<Option<(usize, u32, String)>> = vec![None; words.len()];
+    let fields = word_fields(&dir);
+    for (i, word) in folded.iter().enumerate() {
+        let Some(word) = word else { continue };
+        // Only terms within reach of the word are read from the dictionaries
+        let dfa = CORRECTION_AUTOMATA.build_dfa(word);
+        let mut freqs: std::collections::HashMap<String, u32> = std::collections::HashMap::new();
+        for segment in searcher.segment_readers() {
+            for field in &fields {
+                let inverted = segment.inverted_index(*field)?;
+                let mut stream = inverted.terms().search(WithinDistance(&dfa)).into_stream()?;
+                while stream.advance() {
+                    *freqs.entry(String::from_utf8_lossy(stream.key()).to_string()).or_default() += stream.value().doc_freq;
+                }
+            }
+        }
+        for (term, freq) in freqs {
+            if term == *word { own[i] = freq; continue; }
+            let distance = levenshtein(word, &term);
+            let better = match &best[i] {
+                None => true,
+                Some((d, f, t)) => (distance, std::cmp::Reverse(freq), &term) < (*d, std::cmp::Reverse(*f), t),
+            };
+            if better { best[i] = Some((distance, freq, term)); }
+        }
+    }
+    let mut changed = false;
+    let corrected: Vec<String> = words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| match &best[i] {
+            Some((_, freq, term)) if *freq > own[i] * 2 => { changed = true; term.clone() }
+            _ => word.clone(),
+        })
+        .collect();
+    Ok(if changed { Some(corrected.join(" ")) } else { None })
+}
+
+// Edit distance (insertions, deletions, substitutions) between two words, by characters.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            row[j + 1] = (prev[j] + usize::from(ca != *cb)).min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+// Words of `text` as the folded tokenizer indexes them.
+fn fold_words(text: &str) -> Vec<String> {
+    let mut analyzer = TextAnalyzer::builder(SimpleTokenizer::default()).filter(LowerCaser).filter(AsciiFoldingFilter).build();
+    let mut stream = analyzer.token_stream(text);
+    let mut words = Vec::new();
+    while stream.advance() { words.push(stream.token().text.clone()); }
+    words
+}
+
+#[cfg(test)]
+pub fn search_index(state: &AppState, q: &str, opts: &SearchOptions, progress: &mut dyn FnMut(&[SearchResult]) -> bool) -> Result<Vec<SearchResult>, QuietError> {
+    Ok(search_index_faceted(state, q, opts, progress, false)?.results)
+}
+
+// Query syntax is Tantivy's: bare words match any default field, and a double-quoted
+// substring ("machine learning") is a phrase query that needs the words adjacent and in
+// order. Pages/chapters are separate documents, so a phrase never spans a page break.
+// An unterminated quote is dropped so the rest still runs as plain terms. Any schema field
+// can scope a clause: `title:networks`, `section:"Part II"`, `page:12`, `page:>100`.
+// Page clauses are filters, so they are made required rather than OR'd with the words.
+// `opts.folder` limits hits to files indexed under that watched folder, and `min_size` /
+// `max_size` (inclusive, bytes) to files of that size.
+// `progress` receives the growing result list after each matching document; returning false
+// stops the search early (e.g. a cancelled stream). Pass `&mut |_| true` to run to completion.
+// With `with_facets`, also counts matching files per extension over every match (not just the
+// top `limit`), collected alongside the top documents.
+pub fn search_index_faceted(state: &AppState, q: &str, opts: &SearchOptions, progress: &mut dyn FnMut(&[SearchResult]) -> bool, with_facets: bool) -> Result<SearchResponse, QuietError> {
+    let t0 = std::time::Instant::now();
+    let limit = opts.limit as usize;
+    let dir = index_dir(state);
+    if !dir.exists() { return Ok(SearchResponse::default()); }
+    let (_, fields) = schema();
+    let searcher = cached_reader(state, &dir)?.searcher();
+    let idx_guard = lock_cache(&state.index);
+    let index_ref = idx_guard.as_ref().ok_or(QuietError::IndexMissing)?;
+    if opts.regex {
+        // Regex matching reads documents until `limit`, so only the results found are counted
+        let results = regex_search(&searcher, index_ref, &fields, q, opts, progress)?;
+        let facets = if with_facets { file_type_counts(results.iter().map(|r| r.path.as_str())) } else { vec![] };
+        return Ok(SearchResponse { total: document_count(&results), results, facets });
+    }
+    let weights = settings::read_settings(state).field_weights;
+    let mut weighted = vec![(SearchField::Title, fields.title, weights.title), (SearchField::Body, fields.body, weights.body), (SearchField::Section, fields.section, weights.section)];
+    // Source files match identifiers written any way (`parseConfig`, `parse_config`); weighed
+    // like bodies. Indexes built before the field existed don't have it
+    if index_ref.schema().get_field("code").is_ok() { weighted.push((SearchField::Body, fields.code, weights.body)); }
+    if opts.case_sensitive {
+        if index_ref.schema().get_field("body_cs").is_err() {
+            return Err(QuietError::InvalidInput("case-sensitive search needs the index rebuilt".into()));
+        }
+        // Titles and identifiers are indexed folded; sections are matched as written anyway
+        weighted = vec![(SearchField::Body, fields.body_cs, weights.body), (SearchField::Section, fields.section, weights.section)];
+    }
+    // Fields left out of `opts.fields` are still searched when the query names them (`title:x`)
+    let weighted: Vec<(Field, f32)> = weighted.into_iter().filter(|&(kind, _, w)| w > 0.0 && opts.searches(kind)).map(|(_, f, w)| (f, w)).collect();
+    let mut qp = tantivy::query::QueryParser::for_index(index_ref, weighted.iter().map(|(f, _)| *f).collect());
+    qp.set_conjunction_by_default();
+    for &(field, weight) in &weighted { qp.set_field_boost(field, weight); }
+    let mut query = parse_query_with_prefixes(&qp, index_ref, &weighted, q, opts.case_sensitive)?;
+    let filters = search_filters(index_ref, &fields, opts)?;
+    if !filters.is_empty() {
+        use tantivy::query::{BooleanQuery, Occur};
+        let clauses = std::iter::once(query).chain(filters).map(|q| (Occur::Must, q)).collect();
+        query = Box::new(BooleanQuery::new(clauses));
+    }
+    let snippet_q = snippet_query(q);
+    let snippet_q = snippet_q.as_str();
+    // Case-sensitive snippets match the query's words exactly as written
+    let cased_re = if opts.case_sensitive { Some(cased_words_regex(snippet_q)?) } else { None };
+    let top = tantivy::collector::TopDocs::with_limit(limit);
+    // One pass over the matches fills the top documents, the total and the facets
+    let (top_docs, total, facets) = searcher.search(&query, &(top, tantivy::collector::Count, with_facets.then_some(FileTypeCollector)))?;
 
     let mut results: Vec<SearchResult> = Vec::new();
+    // (path, snippet) already returned: running headers and footers repeat the same passage on
+    // every PDF page, and only the best-scoring (then earliest) page is worth showing
+    let mut seen: std::collections::HashSet<(String, String)> = Default::default();
     'outer: for (score, addr) in top_docs {
-        let document: TantivyDocument = searcher.doc::<TantivyDocument>(addr).map_err(|e| e.to_string())?;
-        let title = document.get_first(fields.title).and_then(|v| v.as_str()).unwrap_or("").to_string();
-        let path = document.get_first(fields.path).and_then(|v| v.as_str()).unwrap_or("").to_string();
-        let page = document.get_first(fields.page).and_then(|v| v.as_u64()).map(|v| v as u32);
-        let section = document.get_first(fields.section).and_then(|v| v.as_str()).map(|s| s.to_string());
+        // Top docs come best first, so the rest score lower still
+        if !opts.score_matches(score) { break; }
+        let document: TantivyDocument = searcher.doc::<TantivyDocument>(addr)?;
         let body = document.get_first(fields.body).and_then(|v| v.as_str()).unwrap_or("");
+        let base = stored_result(&fields, &document, score);
 
         // Prefer multiple paragraph snippets if available; otherwise a single centered snippet.
-        let mut snippets = crate::util::snippet::make_snippets(body, q, 400);
+        let mut snippets = match &cased_re {
+            Some(re) => make_regex_snippets(body, re, opts.snippet_len()),
+            None => make_snippets_highlighted(body, snippet_q, opts.snippet_len()),
+        };
         if snippets.is_empty() {
-            let one = crate::util::snippet::make_snippet(body, q, 400);
-            if !one.is_empty() { snippets.push(one); }
+            let one = make_snippet(body, snippet_q, opts.snippet_len());
+            let highlights = match &cased_re {
+                Some(re) => re.find_iter(&one).filter(|m| !m.is_empty()).map(|m| (m.start(), m.end())).collect(),
+                None => term_ranges(&one, snippet_q),
+            };
+            if !one.is_empty() { snippets.push(Snippet { highlights, text: one }); }
         }
 
-        for snippet in snippets {
-            results.push(SearchResult { title: title.clone(), path: path.clone(), page, section: section.clone(), snippet, score: score as f32 });
+        for Snippet { text: snippet, highlights } in top_snippets(snippets, snippet_q, opts.max_snippets_per_doc()) {
+            if !seen.insert((base.path.clone(), snippet.clone())) { continue; }
+            let match_count = highlights.len() as u32;
+            let highlights = if opts.highlight { highlights } else { vec![] };
+            results.push(SearchResult { snippet, highlights, match_count, ..base.clone() });
             if results.len() >= limit { break 'outer; }
         }
+        if !progress(&results) { break; }
     }
     let elapsed = t0.elapsed();
-    eprintln!("quietlibrary: search_index q=\"{}\" n={} elapsed={}ms", q, results.len(), elapsed.as_millis());
+    eprintln!("quietlibrary: search_index q=\"{}\" n={} total={} elapsed={}ms", q, results.len(), total, elapsed.as_millis());
+    Ok(SearchResponse { results, total, facets: facets.unwrap_or_default() })
+}
+
+// Distinct documents (file plus page or section) among `results`
+pub(crate) fn document_count(results: &[SearchResult]) -> usize {
+    results.iter().map(|r| (&r.path, r.page, &r.section)).collect::<std::collections::HashSet<_>>().len()
+}
+
+// Counts files by lowercase extension, most common first, then by extension.
+pub(crate) fn file_type_counts<'a>(paths: impl Iterator<Item = &'a str>) -> Vec<FacetCount> {
+    let unique: std::collections::HashSet<&str> = paths.collect();
+    let mut counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    for path in unique {
+        let ext = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        *counts.entry(ext).or_default() += 1;
+    }
+    let mut out: Vec<FacetCount> = counts.into_iter().map(|(ext, count)| FacetCount { ext, count }).collect();
+    out.sort_by(|a, b| b.count.cmp(&a.count).then(a.ext.cmp(&b.ext)));
+    out
+}
+
+// Distinct matching paths per segment from the `path` fast field, merged into counts by file
+// type. Indexes built before `path` was a fast field have no column and count nothing.
+struct FileTypeCollector;
+
+struct FileTypeSegmentCollector {
+    paths: Option<tantivy::columnar::StrColumn>,
+    ords: std::collections::HashSet<u64>,
+}
+
+impl tantivy::collector::Collector for FileTypeCollector {
+    type Fruit = Vec<FacetCount>;
+    type Child = FileTypeSegmentCollector;
+
+    fn for_segment(&self, _segment_ord: tantivy::SegmentOrdinal, segment: &tantivy::SegmentReader) -> tantivy::Result<FileTypeSegmentCollector> {
+        let paths = segment.fast_fields().str("path")?;
+        if paths.is_none() { eprintln!("quietlibrary: rebuild the index to count results by file type"); }
+        Ok(FileTypeSegmentCollector { paths, ords: Default::default() })
+    }
+
+    fn requires_scoring(&self) -> bool { false }
+
+    fn merge_fruits(&self, segment_paths: Vec<Vec<String>>) -> tantivy::Result<Vec<FacetCount>> {
+        Ok(file_type_counts(segment_paths.iter().flatten().map(|p| p.as_str())))
+    }
+}
+
+impl tantivy::collector::SegmentCollector for FileTypeSegmentCollector {
+    type Fruit = Vec<String>;
+
+    fn collect(&mut self, doc: tantivy::DocId, _score: tantivy::Score) {
+        if let Some(paths) = &self.paths { self.ords.extend(paths.term_ords(doc)); }
+    }
+
+    fn harvest(self) -> Vec<String> {
+        let Some(paths) = self.paths else { return vec![] };
+        self.ords
+            .into_iter()
+            .filter_map(|ord| {
+                let mut path = String::new();
+                matches!(paths.ord_to_str(ord, &mut path), Ok(true)).then_some(path)
+            })
+            .collect()
+    }
+}
+
+// A result carrying a document's stored fields, without snippet.
+fn stored_result(fields: &IndexFields, document: &TantivyDocument, score: f32) -> SearchResult {
+    let text = |f: Field| document.get_first(f).and_then(|v| v.as_str()).map(|s| s.to_string());
+    let page = document.get_first(fields.page).and_then(|v| v.as_u64()).map(|v| v as u32);
+    let (section, extractor) = match text(fields.extractor) {
+        Some(extractor) => (text(fields.section), Some(extractor)),
+        // Indexes built before the `extractor` field kept it in a PDF page's `section`
+        None if page.is_some() => (None, text(fields.section)),
+        None => (text(fields.section), None),
+    };
+    SearchResult {
+        title: text(fields.title).unwrap_or_default(),
+        path: text(fields.path).unwrap_or_default(),
+        page,
+        section,
+        extractor,
+        score,
+        author: text(fields.author),
+        created: text(fields.created),
+        modified: document.get_first(fields.mtime).and_then(|v| v.as_u64()),
+        size: document.get_first(fields.size).and_then(|v| v.as_u64()),
+        ..Default::default()
+    }
+}
+
+// Compiled-size cap for regex searches; the regex crate runs in linear time, so this bounds
+// the remaining cost of huge patterns such as `\w{1000}{1000}`.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+// Case-insensitive regular expression over stored document text. Unlike the indexed words a
+// query matches, the text keeps its spacing and punctuation, so patterns may span words
+// (`RFC\s?\d{4}`). Every document passing the folder/size filters is read, in index order;
+// the score is the number of matches, so documents matching more often rank first.
+fn regex_search(searcher: &tantivy::Searcher, index: &Index, fields: &IndexFields, pattern: &str, opts: &SearchOptions, progress: &mut dyn FnMut(&[SearchResult]) -> bool) -> Result<Vec<SearchResult>, QuietError> {
+    use tantivy::query::{AllQuery, BooleanQuery, Occur};
+    let t0 = std::time::Instant::now();
+    let re = regex::RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| QuietError::InvalidInput(format!("invalid regular expression: {}", e)))?;
+    let filters = search_filters(index, fields, opts)?;
+    let query = BooleanQuery::new(std::iter::once(Box::new(AllQuery) as Box<dyn tantivy::query::Query>).chain(filters).map(|q| (Occur::Must, q)).collect());
+    let mut addrs: Vec<tantivy::DocAddress> = searcher.search(&query, &tantivy::collector::DocSetCollector)?.into_iter().collect();
+    addrs.sort();
+    let limit = opts.limit as usize;
+    let mut results: Vec<SearchResult> = Vec::new();
+    'outer: for addr in addrs {
+        let document: TantivyDocument = searcher.doc::<TantivyDocument>(addr)?;
+        let body = document.get_first(fields.body).and_then(|v| v.as_str()).unwrap_or("");
+        let snippets = make_regex_snippets(body, &re, opts.snippet_len());
+        if snippets.is_empty() { continue; }
+        let score = re.find_iter(body).filter(|m| !m.is_empty()).count() as f32;
+        if !opts.score_matches(score) { continue; }
+        let base = stored_result(fields, &document, score);
+        for Snippet { text: snippet, highlights } in top_snippets(snippets, "", opts.max_snippets_per_doc()) {
+            let match_count = highlights.len() as u32;
+            let highlights = if opts.highlight { highlights } else { vec![] };
+            results.push(SearchResult { snippet, highlights, match_count, ..base.clone() });
+            if results.len() >= limit { break 'outer; }
+        }
+        if !progress(&results) { break; }
+    }
+    eprintln!("quietlibrary: regex_search pattern=\"{}\" n={} elapsed={}ms", pattern, results.len(), t0.elapsed().as_millis());
     Ok(results)
 }
 
 // Return sorted distinct pages within a single document path that match the query.
-pub fn search_pages_for_document(state: &AppState, path: &str, q: &str, limit: usize) -> Result<Vec<u32>, String> {
+pub fn search_pages_for_document(state: &AppState, path: &str, q: &str, limit: usize) -> Result<Vec<u32>, QuietError> {
+    let mut pages: Vec<u32> = search_page_hits(state, path, q, limit)?.into_iter().map(|h| h.page).collect();
+    pages.sort_unstable();
+    Ok(pages)
+}
+
+// The pages of `path` matching `q`, best first, each with its score and a snippet.
+pub fn search_page_hits(state: &AppState, path: &str, q: &str, limit: usize) -> Result<Vec<PageHit>, QuietError> {
     let t0 = std::time::Instant::now();
     let dir = index_dir(state);
     if !dir.exists() { return Ok(vec![]); }
     let (_, fields) = schema();
-    {
-        let mut idx_lock = state.index.lock().map_err(|_| "index lock".to_string())?;
-        if idx_lock.is_none() {
-            let index = Index::open_in_dir(&dir).map_err(|e| e.to_string())?;
-            *idx_lock = Some(index);
-        }
-    }
-    {
-        let mut reader_lock = state.reader.lock().map_err(|_| "reader lock".to_string())?;
-        if reader_lock.is_none() {
-            let idx_lock = state.index.lock().map_err(|_| "index lock".to_string())?;
-            let index = idx_lock.as_ref().ok_or_else(|| "index not available".to_string())?;
-            let reader = index.reader().map_err(|e| e.to_string())?;
-            *reader_lock = Some(reader);
-        }
-    }
-    let reader = {
-        let r = state.reader.lock().map_err(|_| "reader lock".to_string())?;
-        r.as_ref().unwrap().clone()
-    };
-    let _ = reader.reload();
-    let searcher = reader.searcher();
-    let idx_guard = state.index.lock().map_err(|_| "index lock".to_string())?;
-    let index_ref = idx_guard.as_ref().ok_or_else(|| "index not available".to_string())?;
+    let searcher = cached_reader(state, &dir)?.searcher();
+    let idx_guard = lock_cache(&state.index);
+    let index_ref = idx_guard.as_ref().ok_or(QuietError::IndexMissing)?;
 
     // Query: path == {path} AND body matches {q}
     use tantivy::query::{BooleanQuery, Occur, QueryParser, TermQuery};
     let path_term = tantivy::Term::from_field_text(fields.path, path);
     let path_q = TermQuery::new(path_term, IndexRecordOption::Basic);
-    let qp = QueryParser::for_index(index_ref, vec![fields.body]);
-    let body_q = qp.parse_query(q).map_err(|e| e.to_string())?;
+    let mut qp = QueryParser::for_index(index_ref, vec![fields.body]);
+    qp.set_conjunction_by_default();
+    let body_q = parse_query_with_prefixes(&qp, index_ref, &[(fields.body, 1.0)], q, false)?;
     let boolean = BooleanQuery::new(vec![
         (Occur::Must, Box::new(path_q) as Box<dyn tantivy::query::Query>),
         (Occur::Must, body_q),
     ]);
 
     let top_docs = searcher
-        .search(&boolean, &tantivy::collector::TopDocs::with_limit(limit))
-        .map_err(|e| e.to_string())?;
-    use std::collections::BTreeSet;
-    let mut pages: BTreeSet<u32> = BTreeSet::new();
-    for (_score, addr) in top_docs {
-        let document: TantivyDocument = searcher.doc::<TantivyDocument>(addr).map_err(|e| e.to_string())?;
-        if let Some(p) = document.get_first(fields.page).and_then(|v| v.as_u64()) { pages.insert(p as u32); }
-    }
-    let pages_vec: Vec<u32> = pages.into_iter().collect();
+        .search(&boolean, &tantivy::collector::TopDocs::with_limit(limit))?;
+    let snippet_q = snippet_query(q);
+    let mut hits: Vec<PageHit> = Vec::new();
+    for (score, addr) in top_docs {
+        let document: TantivyDocument = searcher.doc::<TantivyDocument>(addr)?;
+        let Some(page) = document.get_first(fields.page).and_then(|v| v.as_u64()) else { continue };
+        if hits.iter().any(|h| h.page == page as u32) { continue; }
+        let body = document.get_first(fields.body).and_then(|v| v.as_str()).unwrap_or("");
+        hits.push(PageHit { page: page as u32, score, snippet: make_snippet(body, &snippet_q, crate::util::snippet::DEFAULT_SNIPPET_LEN) });
+    }
     let elapsed = t0.elapsed();
-    eprintln!("quietlibrary: search_pages_for_document file={} hits={} elapsed={}ms", path, pages_vec.len(), elapsed.as_millis());
-    Ok(pages_vec)
+    eprintln!("quietlibrary: search_pages_for_document file={} hits={} elapsed={}ms", path, hits.len(), elapsed.as_millis());
+    Ok(hits)
+}
+
+// Required clauses for the folder and size options.
+fn search_filters(index: &Index, fields: &IndexFields, opts: &SearchOptions) -> Result<Vec<Box<dyn tantivy::query::Query>>, String> {
+    use std::ops::Bound;
+    use tantivy::query::{RangeQuery, TermQuery};
+    // Indexes built before a field existed can't be filtered on it
+    let require = |name: &str| -> Result<(), String> {
+        index.schema().get_field(name).map(|_| ()).map_err(|_| format!("rebuild the index to filter by {}", name))
+    };
+    let mut filters: Vec<Box<dyn tantivy::query::Query>> = Vec::new();
+    if let Some(folder) = &opts.folder {
+        require("folder")?;
+        filters.push(Box::new(TermQuery::new(tantivy::Term::from_field_text(fields.folder, folder), IndexRecordOption::Basic)));
+    }
+    if opts.min_size.is_some() || opts.max_size.is_some() {
+        require("size")?;
+        let bound = |v: Option<u64>| v.map_or(Bound::Unbounded, |v| Bound::Included(tantivy::Term::from_field_u64(fields.size, v)));
+        filters.push(Box::new(RangeQuery::new(bound(opts.min_size), bound(opts.max_size))));
+    }
+    if opts.modified_after.is_some() || opts.modified_before.is_some() {
+        require("mtime")?;
+        let term = |v: u64| tantivy::Term::from_field_u64(fields.mtime, v);
+        let lower = opts.modified_after.map_or(Bound::Unbounded, |v| Bound::Included(term(v)));
+        let upper = opts.modified_before.map_or(Bound::Unbounded, |v| Bound::Excluded(term(v)));
+        filters.push(Box::new(RangeQuery::new(lower, upper)));
+    }
+    Ok(filters)
+}
+
+// With an odd number of quotes, remove the last (unterminated) one so the query parses.
+fn balance_quotes(q: &str) -> String {
+    if q.matches('"').count().is_multiple_of(2) { return q.to_string(); }
+    let i = q.rfind('"').unwrap();
+    format!("{}{}", &q[..i], &q[i + 1..])
+}
+
+// Normalize user input before handing it to the QueryParser.
+fn prepare_query(q: &str) -> String {
+    let words = balance_quotes(q)
+        .split(' ')
+        .map(|w| if w.starts_with("page:") { format!("+{}", w) } else { w.to_string() })
+        .collect::<Vec<_>>();
+    rewrite_near(words).join(" ")
+}
+
+// Spell out the `AND` between words that have no operator between them. Left implicit, the
+// QueryParser makes such words required but the words around an `OR` optional, so `a OR b c`
+// would need only `c`; explicit, `AND` binds tighter and it means `a OR (b AND c)`.
+fn join_with_and(q: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    let mut in_phrase = false;
+    for word in q.split(' ').filter(|w| !w.is_empty()) {
+        if let Some(prev) = out.last() {
+            let joinable = !in_phrase && !is_operator(prev) && !prev.ends_with('(') && !is_operator(word) && !word.starts_with(')');
+            if joinable { out.push("AND"); }
+        }
+        if word.matches('"').count() % 2 == 1 { in_phrase = !in_phrase; }
+        out.push(word);
+    }
+    out.join(" ")
+}
+
+// A trailing-`*` word of a query: `config*` matches every indexed word starting with `config`.
+struct PrefixTerm {
+    occur: tantivy::query::Occur,
+    // `title:config*` only looks in that field
+    field: Option<String>,
+    // Lowercased and accent-folded like the indexed words
+    prefix: String,
+    // Joined to the rest of the query with `OR` rather than the default `AND`
+    either: bool,
+}
+
+// Operator words of the query syntax, outside phrases.
+fn is_operator(word: &str) -> bool {
+    matches!(word, "AND" | "OR" | "NOT")
 }
 
-// Drop cached index/reader after a rebuild
+// Pull the trailing-`*` words outside phrases out of a prepared query, which the QueryParser
+// would otherwise look for literally. A bare `*` is dropped rather than matching everything.
+// Operators next to a prefix word are taken with it: `NOT config*` excludes, `OR config*` makes
+// it optional beside the rest of the query.
+fn split_prefix_terms(prepared: &str, cased: bool) -> (String, Vec<PrefixTerm>) {
+    use tantivy::query::Occur;
+    enum Part<'a> { Word(&'a str), Prefix(PrefixTerm) }
+    let mut parts = Vec::new();
+    let mut in_phrase = false;
+    for word in prepared.split(' ') {
+        let quoted = in_phrase || word.contains('"');
+        if word.matches('"').count() % 2 == 1 { in_phrase = !in_phrase; }
+        let (occur, bare) = match word.as_bytes().first() {
+            Some(b'+') => (Occur::Must, &word[1..]),
+            Some(b'-') => (Occur::MustNot, &word[1..]),
+            _ => (Occur::Must, word),
+        };
+        if !quoted && bare == "*" { continue; }
+        let Some(stem) = bare.strip_suffix('*').filter(|_| !quoted) else { parts.push(Part::Word(word)); continue };
+        let (field, value) = match stem.split_once(':') {
+            Some((f, v)) if matches!(f, "title" | "body" | "section") => (Some(f.to_string()), v),
+            Some(_) => { parts.push(Part::Word(word)); continue; }
+            None => (None, stem),
+        };
+        // A single word; `foo-ba*` and the like are left to the QueryParser
+        let words = if cased { vec![value.to_string()] } else { fold_words(value) };
+        match words.as_slice() {
+            [prefix] if value.chars().all(char::is_alphanumeric) => parts.push(Part::Prefix(PrefixTerm { occur, field, prefix: prefix.clone(), either: false })),
+            _ => parts.push(Part::Word(word)),
+        }
+    }
+    let mut rest = Vec::new();
+    let mut prefixes = Vec::new();
+    let mut pending_or = false;
+    let mut pending_not = false;
+    let mut parts = parts.into_iter().peekable();
+    while let Some(part) = parts.next() {
+        match part {
+            Part::Prefix(mut term) => {
+                if std::mem::take(&mut pending_not) { term.occur = Occur::MustNot; }
+                term.either = std::mem::take(&mut pending_or);
+                if let Some(Part::Word("OR")) = parts.peek() { term.either = true; parts.next(); }
+                else if let Some(Part::Word("AND")) = parts.peek() { parts.next(); }
+                prefixes.push(term);
+            }
+            Part::Word(op) if is_operator(op) && matches!(parts.peek(), Some(Part::Prefix(_))) => {
+                match op {
+                    "OR" => pending_or = true,
+                    "NOT" => pending_not = true,
+                    _ => {}
+                }
+                // `a OR NOT config*`
+                if op == "NOT" && rest.last() == Some(&"OR") { rest.pop(); pending_or = true; }
+            }
+            Part::Word(word) => rest.push(word),
+        }
+    }
+    (rest.join(" "), prefixes)
+}
+
+// Parse `q`, matching its prefix words against the index's terms: each becomes a regex query
+// over `weighted` fields (or its own `field:`), joined with the clauses the QueryParser makes of
+// the rest of the query so `+`/`-` keep their meaning.
+// With `cased`, prefixes keep their case for the `body_cs` field.
+fn parse_query_with_prefixes(qp: &tantivy::query::QueryParser, index: &Index, weighted: &[(Field, f32)], q: &str, cased: bool) -> Result<Box<dyn tantivy::query::Query>, QuietError> {
+    use tantivy::query::{BooleanQuery, BoostQuery, EmptyQuery, Occur, Query, RegexQuery};
+    let (rest, prefixes) = split_prefix_terms(&join_with_and(&prepare_query(q)), cased);
+    if prefixes.is_empty() {
+        if rest.trim().is_empty() { return Ok(Box::new(EmptyQuery)); }
+        return Ok(qp.parse_query(&rest)?);
+    }
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+    let either = prefixes.iter().any(|p| p.either);
+    let excluded: Vec<&str> = rest.split_whitespace().map(|w| w.strip_prefix('-')).collect::<Option<_>>().unwrap_or_default();
+    if !excluded.is_empty() {
+        // The QueryParser refuses a query of only `-` words, which here sit beside prefix words
+        clauses.push((Occur::MustNot, qp.parse_query(&excluded.join(" ").replace(' ', " OR "))?));
+    } else if !rest.trim().is_empty() {
+        let parsed = qp.parse_query(&rest)?;
+        match parsed.downcast_ref::<BooleanQuery>() {
+            Some(boolean) if !either => clauses.extend(boolean.clauses().iter().map(|(occur, q)| (*occur, q.box_clone()))),
+            _ => clauses.push((if either { Occur::Should } else { Occur::Must }, parsed)),
+        }
+    }
+    for PrefixTerm { occur, field, prefix, either } in prefixes {
+        let occur = if either && occur == Occur::Must { Occur::Should } else { occur };
+        let fields = match field {
+            Some(name) => index.schema().get_field(&name).map(|f| vec![(f, 1.0)]).unwrap_or_default(),
+            None => weighted.to_vec(),
+        };
+        let pattern = format!("{}.*", regex::escape(&prefix));
+        let mut per_field: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for (field, weight) in fields {
+            let regex: Box<dyn Query> = Box::new(RegexQuery::from_pattern(&pattern, field)?);
+            per_field.push((Occur::Should, Box::new(BoostQuery::new(regex, weight))));
+        }
+        clauses.push((occur, Box::new(BooleanQuery::new(per_field))));
+    }
+    Ok(Box::new(BooleanQuery::new(clauses)))
+}
+
+// Slop of a `NEAR/n` operator word.
+fn near_slop(word: &str) -> Option<u32> {
+    word.strip_prefix("NEAR/").and_then(|n| n.parse().ok())
+}
+
+// `a NEAR/n b` becomes the body phrase `body:"a b"~n`: both words, in order, with at most n
+// words between them (reversed order uses 2 of the n). A chain shares one budget, so
+// `a NEAR/1 b NEAR/2 c` is `body:"a b c"~3`. Each PDF page is indexed as its own document,
+// so a NEAR match is always within one page.
+fn rewrite_near(words: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    // Terms and slop of the phrase at the end of `out`, while a chain can still grow
+    let mut phrase: Option<(Vec<String>, u32)> = None;
+    let mut rest = words.into_iter().filter(|w| !w.is_empty()).peekable();
+    while let Some(word) = rest.next() {
+        match near_slop(&word) {
+            Some(n) if !out.is_empty() && rest.peek().is_some() => {
+                let prev = out.pop().unwrap_or_default();
+                let (mut terms, slop) = phrase.take().unwrap_or_else(|| (vec![prev], 0));
+                terms.extend(rest.next());
+                let slop = slop + n;
+                out.push(format!("body:\"{}\"~{}", terms.join(" "), slop));
+                phrase = Some((terms, slop));
+            }
+            _ => {
+                phrase = None;
+                out.push(word);
+            }
+        }
+    }
+    out
+}
+
+// Snippets match the query text literally: drop phrase quotes, `page:` filters and field
+// prefixes so only the words themselves are looked for in the body.
+fn snippet_query(q: &str) -> String {
+    q.replace('"', " ")
+        .split_whitespace()
+        .filter(|w| !w.starts_with("page:") && near_slop(w).is_none() && !is_operator(w))
+        .map(|w| w.split_once(':').filter(|(f, _)| matches!(*f, "title" | "body" | "section")).map(|(_, v)| v).unwrap_or(w))
+        .map(|w| w.trim_end_matches('*'))
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Matches of any of `words` (a snippet query) with their case as written.
+fn cased_words_regex(words: &str) -> Result<regex::Regex, QuietError> {
+    let alternatives: Vec<String> = words.split_whitespace().map(regex::escape).collect();
+    regex::Regex::new(&alternatives.join("|")).map_err(|e| QuietError::InvalidInput(e.to_string()))
+}
+
+// Drop cached index/reader/writer after a rebuild
 pub fn drop_cached_index(state: &AppState) {
-    if let Ok(mut r) = state.reader.lock() { *r = None; }
-    if let Ok(mut i) = state.index.lock() { *i = None; }
+    state.index_generation.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    *lock_cache(&state.writer) = None;
+    *lock_cache(&state.reader) = None;
+    *lock_cache(&state.index) = None;
+}
+
+// Lock one of the handles cached in `AppState`. A thread that panicked while holding the lock
+// poisons it, but what's inside is only a cache: it is emptied (so the next use reopens it)
+// and the poison cleared, instead of every later search failing until restart.
+fn lock_cache<T>(cache: &Mutex<Option<T>>) -> std::sync::MutexGuard<'_, Option<T>> {
+    cache.lock().unwrap_or_else(|poisoned| {
+        eprintln!("quietlibrary: recovering index handle after a panic");
+        let mut guard = poisoned.into_inner();
+        *guard = None;
+        cache.clear_poison();
+        guard
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+
+    // App dir + one watched folder containing `files`, indexed from scratch.
+    fn indexed_state(files: &[(&str, &str)]) -> (TempDir, AppState) {
+        let tmp = tempdir().unwrap();
+        let docs = tmp.path().join("docs");
+        fs::create_dir_all(&docs).unwrap();
+        for (name, content) in files { fs::write(docs.join(name), content).unwrap(); }
+        let app_dir = tmp.path().join("app");
+        fs::create_dir_all(&app_dir).unwrap();
+        let lib = serde_json::json!({ "folders": [docs.to_string_lossy()] });
+        fs::write(app_dir.join("library.json"), serde_json::to_vec(&lib).unwrap()).unwrap();
+        let state = AppState::new(app_dir);
+        rebuild_index(&state).unwrap();
+        (tmp, state)
+    }
+
+    fn opts(limit: u32) -> SearchOptions { SearchOptions { limit, ..Default::default() } }
+
+    fn first_title(state: &AppState, q: &str) -> String {
+        search_index(state, q, &opts(10), &mut |_| true).unwrap().first().map(|r| r.title.clone()).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_progress_throttle_reports_first_and_last() {
+        let throttle = ProgressThrottle::new(50, Duration::from_secs(60));
+        let reported: Vec<usize> = (0..50).filter_map(|i| throttle.file_done(Path::new(&format!("f{}.txt", i)))).map(|p| p.done).collect();
+        assert_eq!(reported, vec![1, 50]);
+    }
+
+    #[test]
+    fn test_rebuild_reports_progress() {
+        let (tmp, state) = indexed_state(&[("a.txt", "A\nalpha"), ("b.txt", "B\nbeta"), ("c.txt", "C\ngamma")]);
+        let seen = Mutex::new(Vec::new());
+        rebuild_index_with_progress(&state, &|p| seen.lock().unwrap().push(p)).unwrap();
+        let seen = seen.into_inner().unwrap();
+        let last = seen.last().unwrap();
+        assert_eq!((last.done, last.total), (3, 3));
+        assert!(last.file.starts_with(&*tmp.path().to_string_lossy()));
+    }
+
+    #[test]
+    fn test_rebuild_streams_more_files_than_the_queue_holds() {
+        let names: Vec<String> = (0..EXTRACT_QUEUE * 3).map(|i| format!("note{}.txt", i)).collect();
+        let files: Vec<(&str, &str)> = names.iter().map(|n| (n.as_str(), "Note\nshared walrus")).collect();
+        let (_tmp, state) = indexed_state(&files);
+        let response = search_index_faceted(&state, "walrus", &opts(10), &mut |_| true, false).unwrap();
+        assert_eq!(response.total, EXTRACT_QUEUE * 3);
+    }
+
+    #[test]
+    fn test_extraction_buffer_stays_under_budget() {
+        let tmp = tempdir().unwrap();
+        let line = "lorem ipsum dolor sit amet ".repeat(4_000);
+        let files: Vec<PathBuf> = (0..40).map(|i| {
+            let path = tmp.path().join(format!("big{}.txt", i));
+            fs::write(&path, format!("Big {}\n{}", i, line)).unwrap();
+            path
+        }).collect();
+        let app_dir = tmp.path().join("app");
+        fs::create_dir_all(&app_dir).unwrap();
+        let state = AppState::new(app_dir);
+        let config = ExtractConfig::from_state(&state);
+        let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let pdf_pool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        // Room for about three files
+        let budget = ByteBudget::new(line.len() * 3 + 100);
+        let mut added = 0;
+        let (errors, _, _) = extract_streaming(&files, &config, &pool, &pdf_pool, &budget, &|_| {}, &mut |_| {
+            // A slow writer lets the extractors run ahead
+            std::thread::sleep(Duration::from_millis(2));
+            added += 1;
+        });
+        assert!(errors.is_empty());
+        assert_eq!(added, 40);
+        assert!(budget.peak() <= line.len() * 3 + 100, "peak {}", budget.peak());
+        assert!(budget.peak() >= line.len());
+    }
+
+    #[test]
+    fn test_pdfs_extract_on_their_own_pool() {
+        let files: Vec<PathBuf> = (0..24).map(|i| PathBuf::from(if i % 2 == 0 { format!("d{}.pdf", i) } else { format!("t{}.txt", i) })).collect();
+        let pool = ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let pdf_pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let (pdfs_now, pdfs_peak, others_peak) = (AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0));
+        let others_now = AtomicUsize::new(0);
+        let out = map_by_type(&files, &pool, &pdf_pool, &|path| {
+            let (now, peak) = if is_pdf(path) { (&pdfs_now, &pdfs_peak) } else { (&others_now, &others_peak) };
+            peak.fetch_max(now.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            now.fetch_sub(1, Ordering::SeqCst);
+            path.clone()
+        });
+        assert_eq!(out.len(), 24);
+        assert!(out[..12].iter().all(|p| !is_pdf(p)) && out[12..].iter().all(|p| is_pdf(p)));
+        assert!(pdfs_peak.load(Ordering::SeqCst) <= 2, "{:?}", pdfs_peak);
+        // Text files aren't held back behind the PDFs
+        assert!(others_peak.load(Ordering::SeqCst) > 2, "{:?}", others_peak);
+    }
+
+    #[test]
+    fn test_preview_counts_files_by_extension() {
+        let (tmp, state) = indexed_state(&[("a.txt", "Alpha"), ("b.TXT", "Beta"), ("notes.md", "# Notes"), ("photo.png", "not really"), ("Makefile", "all:")]);
+        let preview = preview_index(&state).unwrap();
+        assert_eq!(preview.total_files, 5);
+        assert_eq!(preview.total_bytes, 5 + 4 + 7 + 10 + 4);
+        assert_eq!(preview.by_extension[0], ExtensionStats { ext: "txt".into(), count: 2, bytes: 9, supported: true });
+        assert_eq!(preview.unsupported, 2);
+        assert_eq!(preview.unsupported_exts, vec!["", "png"]);
+        assert!(preview.unreachable.is_empty());
+        // Walking only: the index is left as it was
+        fs::write(tmp.path().join("docs/new.txt"), "Gamma walrus").unwrap();
+        preview_index(&state).unwrap();
+        assert!(search_index(&state, "walrus", &opts(10), &mut |_| true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_warm_index_caches_handles() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nheron")]);
+        drop_cached_index(&state);
+        warm_index(&state);
+        assert!(lock_cache(&state.index).is_some() && lock_cache(&state.reader).is_some());
+        assert_eq!(search_index(&state, "heron", &opts(10), &mut |_| true).unwrap().len(), 1);
+        // Nothing to open without an index
+        let empty = AppState::new(tempdir().unwrap().path().join("app"));
+        warm_index(&empty);
+        assert!(lock_cache(&empty.index).is_none());
+    }
+
+    #[test]
+    fn test_rebuild_swaps_in_staged_index() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nlantern")]);
+        assert_eq!(first_title(&state, "lantern"), "A");
+        // A stale partial build is replaced, and nothing is left next to the live index
+        fs::create_dir_all(staging_dir(&state).join("junk")).unwrap();
+        rebuild_index(&state).unwrap();
+        assert!(!staging_dir(&state).exists() && !retired_dir(&state).exists());
+        assert_eq!(first_title(&state, "lantern"), "A");
+    }
+
+    #[test]
+    fn test_recover_interrupted_rebuild() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nlantern")]);
+        // Killed mid-build: the partial index is discarded, the live one kept
+        fs::create_dir_all(staging_dir(&state)).unwrap();
+        recover_interrupted_rebuild(&state);
+        assert!(!staging_dir(&state).exists());
+        assert_eq!(first_title(&state, "lantern"), "A");
+
+        // Killed between the swap's renames, before and after the new index was committed
+        drop_cached_index(&state);
+        fs::rename(index_dir(&state), retired_dir(&state)).unwrap();
+        recover_interrupted_rebuild(&state);
+        assert!(!retired_dir(&state).exists());
+        assert_eq!(first_title(&state, "lantern"), "A");
+
+        drop_cached_index(&state);
+        fs::rename(index_dir(&state), staging_dir(&state)).unwrap();
+        fs::create_dir_all(retired_dir(&state)).unwrap();
+        recover_interrupted_rebuild(&state);
+        assert!(!staging_dir(&state).exists() && !retired_dir(&state).exists());
+        assert_eq!(first_title(&state, "lantern"), "A");
+    }
+
+    #[test]
+    fn test_rebuild_keeps_documents_of_unreachable_folder() {
+        let (tmp, state) = indexed_state(&[("a.txt", "Alpha\nzebra"), ("b.txt", "Beta\nyak")]);
+        let docs = tmp.path().join("docs");
+        let folder = docs.to_string_lossy().to_string();
+        assert_eq!(indexed_file_count(&state, &folder), 2);
+        // Drive unplugged
+        fs::rename(&docs, tmp.path().join("unplugged")).unwrap();
+        rebuild_index(&state).unwrap();
+        assert_eq!(first_title(&state, "zebra"), "Alpha");
+        assert_eq!(indexed_file_count(&state, &folder), 2);
+        assert_eq!(load_report(&state).unreachable, vec![folder.clone()]);
+        // Restrictions to the folder still apply to the kept documents
+        let scoped = SearchOptions { folder: Some(folder.clone()), ..opts(10) };
+        assert_eq!(search_index(&state, "yak", &scoped, &mut |_| true).unwrap().len(), 1);
+
+        fs::rename(tmp.path().join("unplugged"), &docs).unwrap();
+        fs::remove_file(docs.join("b.txt")).unwrap();
+        rebuild_index(&state).unwrap();
+        assert!(load_report(&state).unreachable.is_empty());
+        assert_eq!(indexed_file_count(&state, &folder), 1);
+    }
+
+    #[test]
+    fn test_remove_path_drops_document_and_fingerprint() {
+        let (tmp, state) = indexed_state(&[("old.txt", "Old\nshared quartz"), ("new.txt", "New\nshared quartz")]);
+        incremental_update(&state).unwrap();
+        let gone = tmp.path().join("docs/old.txt").to_string_lossy().to_string();
+        remove_path(&state, &gone).unwrap();
+        let paths: Vec<String> = search_index(&state, "quartz", &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths.len(), 1);
+        assert!(!paths.contains(&gone));
+        assert!(!load_fingerprints(&index_dir(&state)).entries.contains_key(&gone));
+    }
+
+    #[test]
+    fn test_zip_members_indexed_under_member_paths() {
+        use std::io::Write;
+        let (tmp, state) = indexed_state(&[("loose.txt", "Loose\nnothing here")]);
+        let zip_path = tmp.path().join("docs/bundle.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        zip.start_file("notes/plan.md", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"# Plan\n\nfeed the herons").unwrap();
+        zip.finish().unwrap();
+        incremental_update(&state).unwrap();
+
+        let archive = zip_path.to_string_lossy().to_string();
+        let hits = search_index(&state, "herons", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, format!("{}!/notes/plan.md", archive));
+        assert_eq!((hits[0].title.as_str(), hits[0].section.as_deref()), ("Plan", Some("notes/plan.md")));
+
+        remove_path(&state, &archive).unwrap();
+        assert!(search_index(&state, "herons", &opts(10), &mut |_| true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_quietignore_excludes_gathered_files() {
+        let tmp = tempdir().unwrap();
+        let root = tmp.path();
+        for dir in ["drafts", "notes/old"] { fs::create_dir_all(root.join(dir)).unwrap(); }
+        for file in ["drafts/a.md", "notes/b.md", "notes/old/c.md", "notes/d.md"] { fs::write(root.join(file), "x").unwrap(); }
+        fs::write(root.join(library::IGNORE_FILE), "# not for the index\ndrafts/*\n").unwrap();
+        // Relative to its own directory, and combined with the one above
+        fs::write(root.join("notes").join(library::IGNORE_FILE), "old/*\n").unwrap();
+        let mut walk = library::FolderWalk::new(root, library::ExcludeMatcher::new(root, &[]).unwrap(), None, true);
+        let mut files = Vec::new();
+        gather_files(root, &mut walk, &mut files).unwrap();
+        let mut rel: Vec<String> = files.iter().map(|f| f.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/")).collect();
+        rel.sort();
+        assert_eq!(rel, [".quietignore", "notes/.quietignore", "notes/b.md", "notes/d.md"]);
+    }
+
+    #[test]
+    fn test_split_extracted_collects_truncated_files() {
+        let long = TruncatedFile { path: "/lib/long.pdf".into(), last_indexed_page: 300, total_pages: 812 };
+        let extracted = vec![
+            Ok(ExtractedFile { docs: vec![], truncated: Some(long), empty: None }),
+            Ok(ExtractedFile::default()),
+            Err(IndexFileError { path: "/lib/bad.pdf".into(), error: "damaged".into(), attempts: 1 }),
+        ];
+        let (_, errors, truncated, _) = split_extracted(extracted);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(truncated.len(), 1);
+        assert_eq!((truncated[0].last_indexed_page, truncated[0].total_pages), (300, 812));
+    }
+
+    #[test]
+    fn test_files_without_text_are_not_read_again() {
+        let (tmp, state) = indexed_state(&[("a.txt", "Alpha\nzephyr"), ("blank.txt", "  \n\n")]);
+        let blank = tmp.path().join("docs").join("blank.txt");
+        let key = blank.to_string_lossy().to_string();
+        assert_eq!(load_fingerprints(&index_dir(&state)).empty.get(&key).copied(), file_fp(&blank));
+        // The rebuild's record spares the first update reading it
+        assert_eq!(incremental_update(&state).unwrap(), IndexUpdateStats::default());
+        let fps = load_fingerprints(&index_dir(&state));
+        assert!(fps.entries.contains_key(&key) && fps.empty.contains_key(&key));
+        assert_eq!(incremental_update(&state).unwrap().changed, 0);
+        // Once it has text it's indexed like any other change
+        fs::write(&blank, "Blank no more\nquokka").unwrap();
+        let stats = incremental_update(&state).unwrap();
+        assert_eq!(stats.changed, 1);
+        assert!(!load_fingerprints(&index_dir(&state)).empty.contains_key(&key));
+        assert_eq!(search_index(&state, "quokka", &opts(10), &mut |_| true).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_exclude_patterns_skip_files() {
+        let (tmp, state) = indexed_state(&[("keep.txt", "Keep\nzephyr"), ("scratch.txt", "Scratch\nzephyr")]);
+        let docs = tmp.path().join("docs");
+        fs::create_dir_all(docs.join("node_modules/pkg")).unwrap();
+        fs::write(docs.join("node_modules/pkg/readme.md"), "Pkg\nzephyr").unwrap();
+        let lib = serde_json::json!({ "folders": [docs.to_string_lossy()], "excludes": ["**/node_modules/**", "scratch*"] });
+        fs::write(state.app_dir.join("library.json"), serde_json::to_vec(&lib).unwrap()).unwrap();
+        rebuild_index(&state).unwrap();
+        let titles: Vec<String> = search_index(&state, "zephyr", &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+        assert_eq!(titles, vec!["Keep".to_string()]);
+    }
+
+    #[test]
+    fn test_max_depth_stops_descending() {
+        let (tmp, state) = indexed_state(&[("top.txt", "Top\nzephyr")]);
+        let docs = tmp.path().join("docs");
+        fs::create_dir_all(docs.join("one/two")).unwrap();
+        fs::write(docs.join("one/mid.txt"), "Mid\nzephyr").unwrap();
+        fs::write(docs.join("one/two/deep.txt"), "Deep\nzephyr").unwrap();
+        let lib = serde_json::json!({ "folders": [docs.to_string_lossy()], "max_depth": 1 });
+        fs::write(state.app_dir.join("library.json"), serde_json::to_vec(&lib).unwrap()).unwrap();
+        rebuild_index(&state).unwrap();
+        let mut titles: Vec<String> = search_index(&state, "zephyr", &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+        titles.sort();
+        assert_eq!(titles, vec!["Mid".to_string(), "Top".to_string()]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_cycle_terminates() {
+        let (tmp, state) = indexed_state(&[("once.txt", "Once\nzephyr")]);
+        let docs = tmp.path().join("docs");
+        std::os::unix::fs::symlink(&docs, docs.join("loop")).unwrap();
+        rebuild_index(&state).unwrap();
+        let hits = search_index(&state, "zephyr", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_overlapping_roots_index_files_once() {
+        let (tmp, state) = indexed_state(&[("a.txt", "A\nquince")]);
+        let docs = tmp.path().join("docs");
+        fs::create_dir_all(docs.join("papers")).unwrap();
+        fs::write(docs.join("papers").join("b.txt"), "B\nquince").unwrap();
+        // The same tree once more through a symlink, plus a nested root
+        let alias = tmp.path().join("alias");
+        std::os::unix::fs::symlink(&docs, &alias).unwrap();
+        let folders = [docs.clone(), docs.join("papers"), alias].map(|p| p.to_string_lossy().to_string());
+        fs::write(state.app_dir.join("library.json"), serde_json::to_vec(&serde_json::json!({ "folders": folders })).unwrap()).unwrap();
+        rebuild_index(&state).unwrap();
+        let mut titles: Vec<String> = search_index(&state, "quince", &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+        titles.sort();
+        assert_eq!(titles, vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_extra_text_extensions() {
+        let (tmp, state) = indexed_state(&[("script.py", "def main():\n    print('xylophone')\n")]);
+        assert!(search_index(&state, "xylophone", &opts(10), &mut |_| true).unwrap().is_empty());
+        let docs = tmp.path().join("docs");
+        let lib = serde_json::json!({ "folders": [docs.to_string_lossy()], "extra_text_exts": ["py"] });
+        fs::write(state.app_dir.join("library.json"), serde_json::to_vec(&lib).unwrap()).unwrap();
+        rebuild_index(&state).unwrap();
+        drop_cached_index(&state);
+        let hits = search_index(&state, "xylophone", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "script");
+    }
+
+    #[test]
+    fn test_incremental_update_counts() {
+        let (tmp, state) = indexed_state(&[("keep.txt", "Keep\nalpha"), ("edit.txt", "Edit\nbeta"), ("gone.txt", "Gone\ngamma")]);
+        // The rebuild fingerprinted everything, so an update finds nothing to do
+        let fps = load_fingerprints(&index_dir(&state));
+        assert_eq!(fps.entries.len(), 3);
+        assert!(fps.hashes.is_empty());
+        assert_eq!(incremental_update(&state).unwrap(), IndexUpdateStats::default());
+
+        let docs = tmp.path().join("docs");
+        fs::write(docs.join("edit.txt"), "Edit\nbeta with more words").unwrap();
+        fs::remove_file(docs.join("gone.txt")).unwrap();
+        fs::write(docs.join("new.txt"), "New\ndelta").unwrap();
+        let stats = incremental_update(&state).unwrap();
+        assert_eq!(stats, IndexUpdateStats { added: 1, changed: 1, deleted: 1, renamed: 0 });
+        assert_eq!(first_title(&state, "delta"), "New");
+        assert!(search_index(&state, "gamma", &opts(10), &mut |_| true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_incremental_update_keeps_unreachable_folder() {
+        let (tmp, state) = indexed_state(&[("a.txt", "Alpha\nzebra")]);
+        let docs = tmp.path().join("docs");
+        let more = tmp.path().join("more");
+        fs::create_dir_all(&more).unwrap();
+        fs::write(more.join("b.txt"), "Beta\nyak").unwrap();
+        let lib = serde_json::json!({ "folders": [docs.to_string_lossy(), more.to_string_lossy()] });
+        fs::write(state.app_dir.join("library.json"), serde_json::to_vec(&lib).unwrap()).unwrap();
+        assert_eq!(incremental_update(&state).unwrap().added, 1);
+
+        // `docs` goes offline while a file in the reachable folder is really deleted
+        fs::rename(&docs, tmp.path().join("unplugged")).unwrap();
+        fs::remove_file(more.join("b.txt")).unwrap();
+        let stats = incremental_update(&state).unwrap();
+        assert_eq!(stats, IndexUpdateStats { added: 0, changed: 0, deleted: 1, renamed: 0 });
+        assert_eq!(first_title(&state, "zebra"), "Alpha");
+        assert!(search_index(&state, "yak", &opts(10), &mut |_| true).unwrap().is_empty());
+        assert_eq!(load_report(&state).unreachable, vec![docs.to_string_lossy().to_string()]);
+
+        // Back online: nothing changed, so nothing is re-extracted
+        fs::rename(tmp.path().join("unplugged"), &docs).unwrap();
+        assert_eq!(incremental_update(&state).unwrap(), IndexUpdateStats::default());
+        fs::remove_file(docs.join("a.txt")).unwrap();
+        assert_eq!(incremental_update(&state).unwrap().deleted, 1);
+        assert!(load_report(&state).unreachable.is_empty());
+    }
+
+    #[test]
+    fn test_incremental_update_detects_renames() {
+        let big = format!("Thesis\n{}", "chapter on zebra migration ".repeat(20_000));
+        let (tmp, state) = indexed_state(&[("thesis.txt", &big), ("untitled.txt", "\nyak herding"), ("copy1.txt", "Copy\nemu"), ("copy2.txt", "Copy\nemu")]);
+        let docs = tmp.path().join("docs");
+        let same_time = std::time::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        for copy in ["copy1.txt", "copy2.txt"] { fs::File::options().write(true).open(docs.join(copy)).unwrap().set_modified(same_time).unwrap(); }
+        assert_eq!(incremental_update(&state).unwrap().changed, 2);
+
+        fs::create_dir_all(docs.join("archive")).unwrap();
+        fs::rename(docs.join("thesis.txt"), docs.join("archive/thesis-final.txt")).unwrap();
+        fs::rename(docs.join("untitled.txt"), docs.join("herding.txt")).unwrap();
+        // Identical copies can't be told apart, so they are re-extracted
+        fs::rename(docs.join("copy1.txt"), docs.join("copy3.txt")).unwrap();
+        fs::rename(docs.join("copy2.txt"), docs.join("copy4.txt")).unwrap();
+        let stats = incremental_update(&state).unwrap();
+        assert_eq!(stats, IndexUpdateStats { added: 2, changed: 0, deleted: 2, renamed: 2 });
+
+        let hits = search_index(&state, "zebra", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, docs.join("archive/thesis-final.txt").to_string_lossy());
+        assert_eq!(hits[0].title, "Thesis");
+        // A title that was the file name follows the rename
+        assert_eq!(first_title(&state, "yak"), "herding.txt");
+        assert_eq!(search_index(&state, "emu", &opts(10), &mut |_| true).unwrap().len(), 2);
+        assert_eq!(incremental_update(&state).unwrap(), IndexUpdateStats::default());
+    }
+
+    #[test]
+    fn test_content_hash_skips_rewritten_files() {
+        let (tmp, state) = indexed_state(&[("a.txt", "Alpha\nzebra")]);
+        let file = tmp.path().join("docs/a.txt");
+        let touch = |secs: u64| fs::File::options().write(true).open(&file).unwrap().set_modified(std::time::UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
+        incremental_update(&state).unwrap();
+        // Off by default: a new mtime alone means re-extraction
+        touch(1_000_000);
+        assert_eq!(incremental_update(&state).unwrap().changed, 1);
+
+        fs::write(state.app_dir.join("settings.json"), r#"{"contentHashFingerprints": true}"#).unwrap();
+        // The file was indexed before hashing was on; the next update records its hash
+        assert_eq!(incremental_update(&state).unwrap(), IndexUpdateStats::default());
+        touch(3_000_000);
+        assert_eq!(incremental_update(&state).unwrap(), IndexUpdateStats::default());
+        // Same size, different content
+        fs::write(&file, "Alpha\nzebrb").unwrap();
+        touch(4_000_000);
+        assert_eq!(incremental_update(&state).unwrap().changed, 1);
+        assert_eq!(first_title(&state, "zebrb"), "Alpha");
+        // A rebuild records hashes too
+        rebuild_index(&state).unwrap();
+        touch(5_000_000);
+        assert_eq!(incremental_update(&state).unwrap(), IndexUpdateStats::default());
+        // Stored hashes must read the same in every build
+        assert_eq!(fnv1a(b"a".iter()), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_writer_is_kept_between_updates() {
+        let (tmp, state) = indexed_state(&[("a.txt", "A\nkettle")]);
+        incremental_update(&state).unwrap();
+        let heap = |state: &AppState| state.writer.lock().unwrap().as_ref().map(|w| w.heap);
+        assert_eq!(heap(&state), Some(MIN_WRITER_HEAP));
+        fs::write(tmp.path().join("docs").join("b.txt"), "B\nkettle").unwrap();
+        incremental_update(&state).unwrap();
+        remove_path(&state, &tmp.path().join("docs").join("a.txt").to_string_lossy()).unwrap();
+        assert_eq!(heap(&state), Some(MIN_WRITER_HEAP));
+        assert_eq!(first_title(&state, "kettle"), "B");
+        // Big updates get a bigger heap, up to the rebuild's
+        assert_eq!(update_heap(10 * 1024 * 1024, 128 * 1024 * 1024), 40 * 1024 * 1024);
+        assert_eq!(update_heap(u64::MAX, 128 * 1024 * 1024), 128 * 1024 * 1024);
+        assert_eq!(update_heap(u64::MAX, 0), MIN_WRITER_HEAP);
+        // A rebuild swaps the directory out from under the writer, so it is closed
+        rebuild_index(&state).unwrap();
+        assert_eq!(heap(&state), None);
+    }
+
+    #[test]
+    fn test_writer_heap_setting_is_clamped() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nkettle")]);
+        let mut s = settings::read_settings(&state);
+        s.writer_heap_bytes = 1024;
+        settings::write_settings(&state, &s).unwrap();
+        assert_eq!(writer_heap_limit(&state), MIN_WRITER_HEAP);
+        rebuild_index(&state).unwrap();
+        assert_eq!(first_title(&state, "kettle"), "A");
+        s.writer_heap_bytes = usize::MAX;
+        settings::write_settings(&state, &s).unwrap();
+        assert_eq!(writer_heap_limit(&state), MAX_WRITER_HEAP);
+    }
+
+    #[test]
+    fn test_search_recovers_from_poisoned_locks() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nlantern")]);
+        assert_eq!(first_title(&state, "lantern"), "A");
+        std::thread::scope(|scope| {
+            for poison in [
+                Box::new(|| { let _g = state.index.lock().unwrap(); panic!("poison index"); }) as Box<dyn FnOnce() + Send + Sync>,
+                Box::new(|| { let _g = state.reader.lock().unwrap(); panic!("poison reader"); }),
+            ] {
+                assert!(scope.spawn(poison).join().is_err());
+            }
+        });
+        assert!(state.index.is_poisoned() && state.reader.is_poisoned());
+        assert_eq!(first_title(&state, "lantern"), "A");
+        assert!(!state.index.is_poisoned() && !state.reader.is_poisoned());
+    }
+
+    #[test]
+    fn test_first_incremental_update_creates_index_dir() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nmarble")]);
+        fs::remove_dir_all(index_dir(&state)).unwrap();
+        drop_cached_index(&state);
+        assert_eq!(incremental_update(&state).unwrap().added, 1);
+        assert!(index_dir(&state).join("meta.json").exists());
+        assert_eq!(first_title(&state, "marble"), "A");
+    }
+
+    #[test]
+    fn test_incremental_update_replaces_changed_document() {
+        let (tmp, state) = indexed_state(&[("edit.txt", "Edit\noriginal wording")]);
+        incremental_update(&state).unwrap();
+        fs::write(tmp.path().join("docs").join("edit.txt"), "Edit\nrevised wording").unwrap();
+        incremental_update(&state).unwrap();
+        assert!(search_index(&state, "original", &opts(10), &mut |_| true).unwrap().is_empty());
+        let hits = search_index(&state, "wording", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].snippet.contains("revised wording"));
+    }
+
+    #[test]
+    fn test_code_identifiers_match_any_spelling() {
+        let (tmp, state) = indexed_state(&[("notes.txt", "Notes\nhow to parse config files")]);
+        let docs = tmp.path().join("docs");
+        fs::write(docs.join("config.rs"), "fn parse_config(path: &str) {}").unwrap();
+        fs::write(docs.join("Loader.rs"), "struct Loader { inner: ParseConfig }").unwrap();
+        let lib = serde_json::json!({ "folders": [docs.to_string_lossy()], "extra_text_exts": ["rs"] });
+        fs::write(state.app_dir.join("library.json"), serde_json::to_vec(&lib).unwrap()).unwrap();
+        rebuild_index(&state).unwrap();
+        let paths = |q: &str| {
+            let mut found: Vec<String> = search_index(&state, q, &opts(10), &mut |_| true).unwrap().into_iter().map(|r| Path::new(&r.path).file_name().unwrap().to_string_lossy().to_string()).collect();
+            found.sort();
+            found
+        };
+        for q in ["parseConfig", "ParseConfig", "parseconfig"] {
+            assert_eq!(paths(q), vec!["Loader.rs", "config.rs"], "{}", q);
+        }
+        // Underscores separate words everywhere, so this is also the phrase "parse config"
+        assert_eq!(paths("parse_config"), vec!["Loader.rs", "config.rs", "notes.txt"]);
+        assert_eq!(paths("inner"), vec!["Loader.rs"]);
+    }
+
+    #[test]
+    fn test_phrase_queries() {
+        let (_tmp, state) = indexed_state(&[
+            ("ml.txt", "Notes\nan intro to machine learning methods"),
+            ("rev.txt", "Other\nlearning about the machine shop"),
+            ("end.txt", "Split\nthe last word is machine"),
+            ("start.txt", "Next\nlearning begins here"),
+        ]);
+        let hits = search_index(&state, "\"machine learning\"", &opts(10), &mut |_| true).unwrap();
+        let titles: Vec<&str> = hits.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, vec!["Notes"]);
+        assert!(hits[0].snippet.contains("machine learning"));
+
+        // Unterminated quote degrades to plain terms instead of erroring
+        let hits = search_index(&state, "\"machine learning", &opts(10), &mut |_| true).unwrap();
+        assert!(hits.len() >= 2);
+    }
+
+    #[test]
+    fn test_near_queries() {
+        let (_tmp, state) = indexed_state(&[
+            ("adj.txt", "Adjacent\na neural network model"),
+            ("gap.txt", "Gap\nneural models form a network"),
+            ("far.txt", "Far\nneural ideas span many many many many pages before any network"),
+            ("rev.txt", "Reversed\nthe network is neural"),
+        ]);
+        let titles = |q: &str| -> Vec<String> {
+            let mut t: Vec<String> = search_index(&state, q, &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+            t.sort();
+            t
+        };
+        assert_eq!(titles("neural NEAR/0 network"), vec!["Adjacent"]);
+        assert_eq!(titles("neural NEAR/5 network"), vec!["Adjacent", "Gap", "Reversed"]);
+        assert_eq!(titles("a NEAR/0 neural NEAR/0 network"), vec!["Adjacent"]);
+        assert_eq!(prepare_query("neural  NEAR/5 network NEAR/2"), "body:\"neural network\"~5 NEAR/2");
+        // Snippets highlight the words, not the operator
+        assert_eq!(snippet_query("neural NEAR/5 network"), "neural network");
+    }
+
+    #[test]
+    fn test_field_scoped_queries() {
+        let (_tmp, state) = indexed_state(&[("nets.txt", "Networks\nrouting basics"), ("body.txt", "Plumbing\nnetworks of pipes")]);
+        let hits = search_index(&state, "title:networks", &opts(10), &mut |_| true).unwrap();
+        let titles: Vec<&str> = hits.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, vec!["Networks"]);
+
+        // Page filters need paged documents; add a few straight to the index
+        let (_, fields) = schema();
+        let index = open_index(&index_dir(&state)).unwrap();
+        let mut writer: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+        for page in [3u64, 101, 250] {
+            writer.add_document(doc!(fields.title => "Manual", fields.path => "/m.pdf", fields.page => page, fields.section => "pdfium", fields.body => format!("networks chapter {}", page))).unwrap();
+        }
+        writer.commit().unwrap();
+        drop_cached_index(&state);
+        let mut pages: Vec<u32> = search_index(&state, "networks page:>100", &opts(10), &mut |_| true).unwrap().iter().filter_map(|r| r.page).collect();
+        pages.sort();
+        assert_eq!(pages, vec![101, 250]);
+        let hits = search_index(&state, "title:manual page:3", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet, "networks chapter 3");
+    }
+
+    #[test]
+    fn test_extractor_reported_apart_from_section() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nnothing here")]);
+        let (_, fields) = schema();
+        let index = open_index(&index_dir(&state)).unwrap();
+        let mut writer: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+        let page = IndexDoc { title: "Scan".into(), path: "/scan.pdf".into(), page: Some(1), body: "heron in reeds".into(), extractor: Some("ocr".into()), ..Default::default() };
+        writer.add_document(make_doc(&fields, page, &[])).unwrap();
+        // Written before the extractor field: the name sat in `section`
+        writer.add_document(doc!(fields.title => "Old", fields.path => "/old.pdf", fields.page => 2u64, fields.section => "lopdf", fields.body => "heron nesting")).unwrap();
+        writer.commit().unwrap();
+        drop_cached_index(&state);
+        let hits = search_index(&state, "heron", &opts(10), &mut |_| true).unwrap();
+        let scan = hits.iter().find(|r| r.title == "Scan").unwrap();
+        assert_eq!((scan.extractor.as_deref(), scan.section.as_deref(), scan.snippet.as_str()), (Some("ocr"), None, "heron in reeds"));
+        let old = hits.iter().find(|r| r.title == "Old").unwrap();
+        assert_eq!((old.extractor.as_deref(), old.section.as_deref()), (Some("lopdf"), None));
+    }
+
+    #[test]
+    fn test_folder_restriction_uses_deepest_root() {
+        let (tmp, state) = indexed_state(&[("top.txt", "Top\nshared term")]);
+        let docs = tmp.path().join("docs");
+        let nested = docs.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("inner.txt"), "Inner\nshared term").unwrap();
+        let lib = serde_json::json!({ "folders": [docs.to_string_lossy(), nested.to_string_lossy()] });
+        fs::write(state.app_dir.join("library.json"), serde_json::to_vec(&lib).unwrap()).unwrap();
+        drop_cached_index(&state);
+        rebuild_index(&state).unwrap();
+
+        let search_in = |folder: &Path| -> Vec<String> {
+            let o = SearchOptions { folder: Some(folder.to_string_lossy().to_string()), ..opts(10) };
+            search_index(&state, "shared", &o, &mut |_| true).unwrap().into_iter().map(|r| r.title).collect()
+        };
+        assert_eq!(search_in(&docs), vec!["Top"]);
+        assert_eq!(search_in(&nested), vec!["Inner"]);
+        // Unrestricted search sees both, each indexed once
+        assert_eq!(search_index(&state, "shared", &opts(10), &mut |_| true).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_default_weights_rank_title_hit_first() {
+        let (_tmp, state) = indexed_state(&[
+            ("body.txt", "Plumbing handbook\nnetworks of pipes run under the city streets"),
+            ("title.txt", "Networks\nrouting tables and switching fabrics explained"),
+        ]);
+        let hits = search_index(&state, "networks", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].title, "Networks");
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    #[test]
+    fn test_highlights_only_when_requested() {
+        let (_tmp, state) = indexed_state(&[("h.txt", "Heads\nsignal and more signal here")]);
+        let plain = search_index(&state, "signal", &opts(10), &mut |_| true).unwrap();
+        assert!(plain[0].highlights.is_empty());
+        let o = SearchOptions { highlight: true, ..opts(10) };
+        let hits = search_index(&state, "signal", &o, &mut |_| true).unwrap();
+        let r = &hits[0];
+        assert_eq!(r.highlights.len(), 2);
+        for (a, b) in &r.highlights { assert_eq!(&r.snippet[*a..*b], "signal"); }
+    }
+
+    #[test]
+    fn test_snippet_len_option() {
+        let words = "filler ".repeat(200);
+        let (_tmp, state) = indexed_state(&[("long.txt", &format!("Long\n{}target {}", words, words))]);
+        let short = search_index(&state, "target", &SearchOptions { snippet_len: Some(40), ..opts(10) }, &mut |_| true).unwrap();
+        let default = search_index(&state, "target", &opts(10), &mut |_| true).unwrap();
+        assert!(short[0].snippet.contains("target"));
+        // Cut edges add an ellipsis each
+        assert!(short[0].snippet.trim_matches('…').len() <= 50);
+        assert!(default[0].snippet.len() > 300);
+    }
+
+    #[test]
+    fn test_snippets_per_document_capped() {
+        let body = format!("Birds\n{}", (0..8).map(|i| format!("heron {} {}", i, "filler ".repeat(20))).collect::<String>());
+        let (_tmp, state) = indexed_state(&[("birds.txt", &body)]);
+        let short = SearchOptions { snippet_len: Some(40), ..opts(50) };
+        let hits = search_index(&state, "heron", &short, &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 3);
+        let more = search_index(&state, "heron", &SearchOptions { max_snippets_per_doc: Some(5), ..short }, &mut |_| true).unwrap();
+        assert_eq!(more.len(), 5);
+    }
+
+    #[test]
+    fn test_accent_insensitive_matching() {
+        let (_tmp, state) = indexed_state(&[("fr.txt", "Menu\nun café naïve"), ("en.txt", "Plain\na cafe for the naive")]);
+        for q in ["cafe", "café", "naive", "naïve"] {
+            let mut titles: Vec<String> = search_index(&state, q, &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+            titles.sort();
+            assert_eq!(titles, vec!["Menu", "Plain"], "query {}", q);
+        }
+        let hits = search_index(&state, "cafe", &SearchOptions { highlight: true, ..opts(10) }, &mut |_| true).unwrap();
+        let menu = hits.iter().find(|r| r.title == "Menu").unwrap();
+        let (a, b) = menu.highlights[0];
+        assert_eq!(&menu.snippet[a..b], "café");
+    }
+
+    #[test]
+    fn test_suggest_terms_by_document_frequency() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "Machines\nmachine learning"), ("b.txt", "Notes\nmachine shop"), ("c.txt", "Misc\nmachinery and Mach numbers")]);
+        // The default English stemming leaves only titles to draw from
+        assert_eq!(suggest_terms(&state, "mach", 10).unwrap(), vec!["machines"]);
+        let mut s = settings::read_settings(&state);
+        s.stem_language = String::new();
+        settings::write_settings(&state, &s).unwrap();
+        drop_cached_index(&state);
+        rebuild_index(&state).unwrap();
+        // `machines` counts once as a title and once in its body's first line
+        assert_eq!(suggest_terms(&state, "Mach", 2).unwrap(), vec!["machine", "machines"]);
+        assert_eq!(suggest_terms(&state, "deep mach", 10).unwrap(), vec!["machine", "machines", "mach", "machinery"]);
+        assert!(suggest_terms(&state, "  ", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_regex_search_spans_words() {
+        let (_tmp, state) = indexed_state(&[("http.txt", "HTTP\nDefined in RFC 2616, later rfc7230."), ("mail.txt", "Mail\nSee RFC 822."), ("none.txt", "None\nno standards here")]);
+        let o = SearchOptions { regex: true, highlight: true, ..opts(10) };
+        let hits = search_index(&state, r"RFC\s?\d{4}", &o, &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].title.as_str(), hits[0].score), ("HTTP", 2.0));
+        let (a, b) = hits[0].highlights[0];
+        assert_eq!(&hits[0].snippet[a..b], "RFC 2616");
+        match search_index(&state, "RFC(", &o, &mut |_| true) {
+            Err(QuietError::InvalidInput(msg)) => assert!(msg.contains("regular expression")),
+            other => panic!("expected invalid input, got {:?}", other.map(|r| r.len())),
+        }
+    }
+
+    #[test]
+    fn test_file_type_facets_count_files() {
+        let (_tmp, state) = indexed_state(&[
+            ("a.txt", "A\nzebra stripes"),
+            ("b.txt", "B\nzebra crossing"),
+            ("c.md", "# C\n\nzebra notes\n\nmore zebra"),
+            ("d.html", "<html><body><p>zebra page</p></body></html>"),
+            ("e.TXT", "E\nzebra shouting"),
+            ("f.txt", "F\nno stripes here"),
+        ]);
+        let response = search_index_faceted(&state, "zebra", &opts(1), &mut |_| true, true).unwrap();
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(response.total, 5);
+        let counts: Vec<(&str, usize)> = response.facets.iter().map(|f| (f.ext.as_str(), f.count)).collect();
+        assert_eq!(counts, vec![("txt", 3), ("html", 1), ("md", 1)]);
+        assert!(search_index_faceted(&state, "zebra", &opts(1), &mut |_| true, false).unwrap().facets.is_empty());
+    }
+
+    #[test]
+    fn test_total_counts_documents_not_snippets() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nzebra"), ("b.md", "# B\n\nzebra one\n\nzebra two"), ("c.txt", "C\nhorse")]);
+        let response = search_index_faceted(&state, "zebra", &opts(10), &mut |_| true, false).unwrap();
+        assert_eq!(response.results.len(), 3);
+        assert_eq!(response.total, 2);
+        assert_eq!(search_index_faceted(&state, "zebra", &opts(1), &mut |_| true, false).unwrap().total, 2);
+    }
+
+    #[test]
+    fn test_markdown_sections_are_separate_documents() {
+        let (_tmp, state) = indexed_state(&[("guide.md", "# Guide\n\nWelcome.\n\n## Install\n\nRun the installer.\n\n## Upgrade\n\nRun the upgrader."), ("notes.txt", "Notes\nRun everything")]);
+        let hits = search_index(&state, "upgrader", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "Guide");
+        assert_eq!(hits[0].section.as_deref(), Some("Upgrade"));
+        let mut sections: Vec<Option<String>> = search_index(&state, "run", &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.section).collect();
+        sections.sort();
+        assert_eq!(sections, vec![None, Some("Install".to_string()), Some("Upgrade".to_string())]);
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("machien", "machine"), 2);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("café", "cafe"), 1);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_suggest_correction_for_misspelling() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "Lathe\nthe machine shop"), ("b.txt", "Notes\nmachine learning"), ("c.txt", "Misc\nmachina et deus")]);
+        let mut s = settings::read_settings(&state);
+        s.stem_language = String::new();
+        settings::write_settings(&state, &s).unwrap();
+        drop_cached_index(&state);
+        rebuild_index(&state).unwrap();
+        assert!(search_index(&state, "machien", &opts(10), &mut |_| true).unwrap().is_empty());
+        // `machina` is also 2 edits away but in fewer documents
+        assert_eq!(suggest_correction(&state, "machien learning").unwrap().as_deref(), Some("machine learning"));
+        assert_eq!(suggest_correction(&state, "machine").unwrap(), None);
+        assert_eq!(suggest_correction(&state, "zzzzqqq").unwrap(), None);
+    }
+
+    #[test]
+    fn test_stemming_follows_index_meta() {
+        let files = [("a.txt", "Daily\nI run every morning"), ("b.txt", "Log\nshe runs at night")];
+        let (_tmp, state) = indexed_state(&files);
+        assert_eq!(load_index_meta(&index_dir(&state)).body_tokenizer, "stem_english");
+        assert_eq!(search_index(&state, "running", &opts(10), &mut |_| true).unwrap().len(), 2);
+
+        // Changing the setting only takes effect after a rebuild
+        let mut s = settings::read_settings(&state);
+        s.stem_language = String::new();
+        settings::write_settings(&state, &s).unwrap();
+        assert_eq!(search_index(&state, "running", &opts(10), &mut |_| true).unwrap().len(), 2);
+        drop_cached_index(&state);
+        rebuild_index(&state).unwrap();
+        assert_eq!(load_index_meta(&index_dir(&state)).body_tokenizer, FOLDED_TOKENIZER);
+        assert!(search_index(&state, "running", &opts(10), &mut |_| true).unwrap().is_empty());
+
+        s.stem_language = "klingon".into();
+        settings::write_settings(&state, &s).unwrap();
+        assert!(rebuild_index(&state).is_err());
+        assert!(index_dir(&state).join("index_meta.json").exists());
+    }
+
+    #[test]
+    fn test_stop_words_removed_but_phrases_match() {
+        let (tmp, state) = indexed_state(&[]);
+        let mut s = settings::read_settings(&state);
+        s.remove_stop_words = true;
+        settings::write_settings(&state, &s).unwrap();
+        let docs = tmp.path().join("docs");
+        fs::write(docs.join("art.txt"), "Survey\nthe state of the art in parsing").unwrap();
+        fs::write(docs.join("other.txt"), "Other\nart of the state").unwrap();
+        drop_cached_index(&state);
+        rebuild_index(&state).unwrap();
+        assert_eq!(load_index_meta(&index_dir(&state)).body_tokenizer, "stem_english_stop");
+
+        let index = open_index(&index_dir(&state)).unwrap();
+        let searcher = index.reader().unwrap().searcher();
+        let (_, fields) = schema();
+        let df = |w: &str| searcher.doc_freq(&tantivy::Term::from_field_text(fields.body, w)).unwrap();
+        assert_eq!(df("the"), 0);
+        assert_eq!(df("of"), 0);
+        assert_eq!(df("art"), 2);
+
+        let hits = search_index(&state, "\"state of the art\"", &opts(10), &mut |_| true).unwrap();
+        let titles: Vec<&str> = hits.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, vec!["Survey"]);
+    }
+
+    #[test]
+    fn test_author_field_search() {
+        let (_tmp, state) = indexed_state(&[("notes.txt", "Knuth notes\nreading about algorithms")]);
+        let (_, fields) = schema();
+        let index = open_index(&index_dir(&state)).unwrap();
+        let mut writer: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+        let taocp = IndexDoc {
+            title: "The Art of Computer Programming".into(),
+            path: "/books/taocp.pdf".into(),
+            page: Some(1),
+            body: "fundamental algorithms".into(),
+            author: Some("Donald E. Knuth".into()),
+            created: Some("1968-01-01".into()),
+            ..Default::default()
+        };
+        writer.add_document(make_doc(&fields, taocp, &[])).unwrap();
+        writer.commit().unwrap();
+        drop_cached_index(&state);
+
+        let hits = search_index(&state, "author:knuth", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].author.as_deref(), Some("Donald E. Knuth"));
+        assert_eq!(hits[0].created.as_deref(), Some("1968-01-01"));
+        // The text file only mentions Knuth in its title
+        assert_eq!(search_index(&state, "knuth", &opts(10), &mut |_| true).unwrap()[0].title, "Knuth notes");
+    }
+
+    #[test]
+    fn test_repeated_page_snippets_shown_once() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nnothing here")]);
+        let (_, fields) = schema();
+        let index = open_index(&index_dir(&state)).unwrap();
+        let mut writer: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+        for (page, body) in [(1, "Quarterly Report Draft\n\nrevenue grew"), (2, "Quarterly Report Draft\n\ncosts fell")] {
+            let doc = IndexDoc { title: "Report".into(), path: "/docs/report.pdf".into(), page: Some(page), body: body.into(), ..Default::default() };
+            writer.add_document(make_doc(&fields, doc, &[])).unwrap();
+        }
+        writer.commit().unwrap();
+        drop_cached_index(&state);
+
+        let hits = search_index(&state, "quarterly", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet, "Quarterly Report Draft");
+        assert_eq!(hits[0].page, Some(1));
+    }
+
+    #[test]
+    fn test_match_count_in_indexed_results() {
+        let (_tmp, state) = indexed_state(&[("birds.txt", "Birds\nA heron, another Heron and a third HERON near reeds.")]);
+        let hits = search_index(&state, "heron", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits[0].match_count, 3);
+        let hits = search_index(&state, "heron reeds", &opts(10), &mut |_| true).unwrap();
+        assert_eq!(hits[0].match_count, 4);
+    }
+
+    #[test]
+    fn test_trailing_star_matches_prefix() {
+        let (_tmp, state) = indexed_state(&[
+            ("a.txt", "Nets\nA neural network learns."),
+            ("b.txt", "Cells\nEach neuron fires."),
+            ("c.txt", "Birds\nA heron wades."),
+        ]);
+        let mut titles: Vec<String> = search_index(&state, "neur*", &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+        titles.sort();
+        titles.dedup();
+        assert_eq!(titles, ["Cells", "Nets"]);
+        let hits = search_index(&state, "neur* -fires", &opts(10), &mut |_| true).unwrap();
+        assert!(hits.iter().all(|r| r.title == "Nets"), "{:?}", hits);
+        assert!(search_index(&state, "*", &opts(10), &mut |_| true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_boolean_operators() {
+        let (_tmp, state) = indexed_state(&[
+            ("a.txt", "Rust Notes\nasync runtimes and tokio"),
+            ("b.txt", "Rust Basics\nownership and borrowing"),
+            ("c.txt", "Tokio\nasync tasks in practice"),
+        ]);
+        let titles = |q: &str| {
+            let mut t: Vec<String> = search_index(&state, q, &opts(10), &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+            t.sort();
+            t.dedup();
+            t
+        };
+        // Words are joined with AND unless told otherwise
+        assert_eq!(titles("rust async"), ["Rust Notes"]);
+        assert_eq!(titles("rust AND async"), ["Rust Notes"]);
+        assert_eq!(titles("ownership OR tasks"), ["Rust Basics", "Tokio"]);
+        // Exclusion covers titles as well as bodies
+        assert_eq!(titles("async -tokio"), Vec::<String>::new());
+        assert_eq!(titles("rust NOT tokio"), ["Rust Basics"]);
+        assert_eq!(titles("rust -basics"), ["Rust Notes"]);
+        assert_eq!(titles("rust AND async NOT tokio"), Vec::<String>::new());
+        assert_eq!(titles("ownership OR async tokio"), ["Rust Basics", "Rust Notes", "Tokio"]);
+        assert_eq!(titles("own* OR tasks"), ["Rust Basics", "Tokio"]);
+        assert_eq!(titles("rust NOT own*"), ["Rust Notes"]);
+    }
+
+    #[test]
+    fn test_body_only_search_skips_title_matches() {
+        let (_tmp, state) = indexed_state(&[
+            ("a.html", "<html><head><title>Heron Notes</title></head><body><p>wading birds</p></body></html>"),
+            ("b.txt", "Birds\na heron wades"),
+        ]);
+        let titles = |fields: Option<Vec<SearchField>>| -> Vec<String> {
+            let o = SearchOptions { fields, ..opts(10) };
+            let mut t: Vec<String> = search_index(&state, "heron", &o, &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+            t.sort();
+            t
+        };
+        assert_eq!(titles(None), ["Birds", "Heron Notes"]);
+        assert_eq!(titles(Some(vec![SearchField::Body])), ["Birds"]);
+        assert_eq!(titles(Some(vec![SearchField::Title])), ["Heron Notes"]);
+        assert!(matches!(SearchOptions { fields: Some(vec![]), ..opts(10) }.validate(), Err(QuietError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_case_sensitive_search() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "Upper\nan Error was raised"), ("b.txt", "Lower\nno error here")]);
+        let titles = |q: &str, case_sensitive: bool| -> Vec<String> {
+            let o = SearchOptions { case_sensitive, highlight: true, ..opts(10) };
+            let mut t: Vec<String> = search_index(&state, q, &o, &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+            t.sort();
+            t
+        };
+        assert_eq!(titles("Error", false), ["Lower", "Upper"]);
+        assert_eq!(titles("Error", true), ["Upper"]);
+        assert_eq!(titles("error", true), ["Lower"]);
+        assert_eq!(titles("Err*", true), ["Upper"]);
+        // Highlights only the exact spelling
+        let o = SearchOptions { case_sensitive: true, highlight: true, ..opts(10) };
+        let hit = &search_index(&state, "Error", &o, &mut |_| true).unwrap()[0];
+        let (a, b) = hit.highlights[0];
+        assert_eq!(&hit.snippet[a..b], "Error");
+    }
+
+    #[test]
+    fn test_indexed_title_by_path() {
+        let (tmp, state) = indexed_state(&[("heron.txt", "Grey Heron\nwading birds")]);
+        let path = tmp.path().join("docs").join("heron.txt");
+        assert_eq!(indexed_title(&state, &path.to_string_lossy()).as_deref(), Some("Grey Heron"));
+        assert_eq!(indexed_title(&state, "/not/indexed.txt"), None);
+    }
+
+    #[test]
+    fn test_page_hits_ranked_by_score() {
+        let (_tmp, state) = indexed_state(&[("a.txt", "A\nnothing here")]);
+        let (_, fields) = schema();
+        let index = open_index(&index_dir(&state)).unwrap();
+        let mut writer: tantivy::IndexWriter = index.writer(15_000_000).unwrap();
+        let bodies = [(1, "the heron"), (2, "intro"), (3, "heron heron heron nesting"), (4, "a heron in the reeds near other birds")];
+        for (page, body) in bodies {
+            let doc = IndexDoc { title: "Birds".into(), path: "/docs/birds.pdf".into(), page: Some(page), body: body.into(), ..Default::default() };
+            writer.add_document(make_doc(&fields, doc, &[])).unwrap();
+        }
+        writer.commit().unwrap();
+        drop_cached_index(&state);
+
+        let hits = search_page_hits(&state, "/docs/birds.pdf", "heron", 10).unwrap();
+        assert_eq!(hits.iter().map(|h| h.page).collect::<Vec<_>>(), vec![3, 1, 4]);
+        assert!(hits[0].score > hits[1].score && hits[1].score > hits[2].score);
+        assert_eq!(hits[0].snippet, "heron heron heron nesting");
+        // The page-ordered variant is unchanged
+        assert_eq!(search_pages_for_document(&state, "/docs/birds.pdf", "heron", 10).unwrap(), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_modified_time_is_indexed() {
+        let (tmp, state) = indexed_state(&[("a.txt", "A\nshared"), ("b.txt", "B\nshared")]);
+        let docs = tmp.path().join("docs");
+        let set = |name: &str, secs: u64| {
+            let f = fs::File::options().write(true).open(docs.join(name)).unwrap();
+            f.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)).unwrap();
+        };
+        set("a.txt", 1_000_000);
+        set("b.txt", 2_000_000);
+        drop_cached_index(&state);
+        rebuild_index(&state).unwrap();
+        let hits = search_index(&state, "shared", &opts(10), &mut |_| true).unwrap();
+        let modified = |title: &str| hits.iter().find(|r| r.title == title).unwrap().modified;
+        assert_eq!(modified("A"), Some(1_000_000));
+        assert_eq!(modified("B"), Some(2_000_000));
+    }
+
+    #[test]
+    fn test_modified_window_filter() {
+        let (tmp, state) = indexed_state(&[("a.txt", "A\nshared"), ("b.txt", "B\nshared"), ("c.txt", "C\nshared")]);
+        let docs = tmp.path().join("docs");
+        for (name, secs) in [("a.txt", 1_000), ("b.txt", 2_000), ("c.txt", 3_000)] {
+            let f = fs::File::options().write(true).open(docs.join(name)).unwrap();
+            f.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs)).unwrap();
+        }
+        drop_cached_index(&state);
+        rebuild_index(&state).unwrap();
+        let titles = |modified_after, modified_before| -> Vec<String> {
+            let o = SearchOptions { modified_after, modified_before, ..opts(10) };
+            let mut t: Vec<String> = search_index(&state, "shared", &o, &mut |_| true).unwrap().into_iter().map(|r| r.title).collect();
+            t.sort();
+            t
+        };
+        // After is inclusive, before exclusive
+        assert_eq!(titles(Some(2_000), None), vec!["B", "C"]);
+        assert_eq!(titles(None, Some(2_000)), vec!["A"]);
+        assert_eq!(titles(Some(1_000), Some(3_000)), vec!["A", "B"]);
+        assert!(titles(Some(2_000), Some(2_000)).is_empty());
+        let inverted = SearchOptions { modified_after: Some(3_000), modified_before: Some(1_000), ..opts(10) };
+        assert!(matches!(inverted.validate(), Err(QuietError::InvalidInput(_))));
+        assert!(!inverted.modified_matches(Some(2_000)) && opts(10).modified_matches(None));
+    }
+
+    #[test]
+    fn test_size_filter() {
+        let (_tmp, state) = indexed_state(&[("stub.txt", "Stub\nword"), ("full.txt", &format!("Full\nword {}", "more ".repeat(100)))]);
+        let titles = |min_size, max_size| -> Vec<String> {
+            let o = SearchOptions { min_size, max_size, ..opts(10) };
+            search_index(&state, "word", &o, &mut |_| true).unwrap().into_iter().map(|r| r.title).collect()
+        };
+        assert_eq!(titles(Some(100), None), vec!["Full"]);
+        assert_eq!(titles(None, Some(100)), vec!["Stub"]);
+        assert_eq!(titles(Some(9), Some(9)), vec!["Stub"]);
+        let hits = search_index(&state, "word", &opts(10), &mut |_| true).unwrap();
+        assert!(hits.iter().all(|r| r.size.is_some()));
+    }
+
+    #[test]
+    fn test_title_weight_reorders_results() {
+        let filler = "lorem ipsum dolor sit amet ".repeat(40);
+        let (_tmp, state) = indexed_state(&[
+            ("a.txt", &format!("Quantum notes\n{}", filler)),
+            ("b.txt", "Misc jottings\nquantum quantum quantum quantum"),
+        ]);
+        let mut s = settings::read_settings(&state);
+        s.field_weights = settings::FieldWeights { title: 0.1, body: 1.0, section: 1.0 };
+        settings::write_settings(&state, &s).unwrap();
+        assert_eq!(first_title(&state, "quantum"), "Misc jottings");
+
+        s.field_weights.title = 20.0;
+        settings::write_settings(&state, &s).unwrap();
+        assert_eq!(first_title(&state, "quantum"), "Quantum notes");
+    }
 }
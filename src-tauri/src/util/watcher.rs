@@ -0,0 +1,129 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tauri::{AppHandle, Manager};
+
+use crate::{commands::indexer, util::tantivy_index, AppState};
+
+// How long the watched folders must stay quiet before an incremental update runs. Saving a
+// file usually produces several events (and editors save repeatedly), so updates are batched.
+const QUIET_PERIOD: Duration = Duration::from_secs(2);
+
+// Watches every library folder and runs `incremental_update` after changes settle, then
+// emits `index-updated` with the update counts. Managed as its own Tauri state.
+pub struct FolderWatcher {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+impl FolderWatcher {
+    pub fn start(app: AppHandle, app_dir: PathBuf, folders: &[String]) -> FolderWatcher {
+        let (tx, rx) = mpsc::channel::<()>();
+        let ignore = app_dir;
+        let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                if is_relevant(&event, &ignore) { let _ = tx.send(()); }
+            }
+        });
+        let watcher = match watcher {
+            Ok(w) => Some(w),
+            Err(e) => {
+                eprintln!("quietlibrary: file watcher unavailable error={}", e);
+                None
+            }
+        };
+        std::thread::spawn(move || {
+            run_debouncer(&rx, QUIET_PERIOD, || {
+                let Some(state) = app.try_state::<AppState>() else { return };
+                match tantivy_index::incremental_update(&state) {
+                    Ok(stats) => {
+                        let _ = app.emit_all("index-updated", stats);
+                        indexer::emit_unreachable(&app);
+                    }
+                    Err(e) => eprintln!("quietlibrary: watch reindex failed error={}", e),
+                }
+            });
+        });
+        let fw = FolderWatcher { watcher: Mutex::new(watcher) };
+        for folder in folders { fw.watch(folder); }
+        fw
+    }
+
+    pub fn watch(&self, folder: &str) {
+        if let Ok(mut guard) = self.watcher.lock() {
+            if let Some(w) = guard.as_mut() {
+                if let Err(e) = w.watch(Path::new(folder), RecursiveMode::Recursive) {
+                    eprintln!("quietlibrary: watch failed folder={} error={}", folder, e);
+                }
+            }
+        }
+    }
+
+    pub fn unwatch(&self, folder: &str) {
+        if let Ok(mut guard) = self.watcher.lock() {
+            if let Some(w) = guard.as_mut() { let _ = w.unwatch(Path::new(folder)); }
+        }
+    }
+}
+
+// Content changes only; reads and our own app dir (index, caches) are ignored.
+fn is_relevant(event: &Event, app_dir: &Path) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+        && event.paths.iter().any(|p| !p.starts_with(app_dir))
+}
+
+// Wait for a first event, then keep absorbing events until none arrives for `quiet`, then
+// call `on_quiet` once. Returns when the sending side is dropped.
+fn run_debouncer(rx: &mpsc::Receiver<()>, quiet: Duration, mut on_quiet: impl FnMut()) {
+    while rx.recv().is_ok() {
+        loop {
+            match rx.recv_timeout(quiet) {
+                Ok(()) => continue,
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => { on_quiet(); return; }
+            }
+        }
+        on_quiet();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+    #[test]
+    fn test_burst_triggers_single_update() {
+        let (tx, rx) = mpsc::channel();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let worker = std::thread::spawn(move || run_debouncer(&rx, Duration::from_millis(100), || { counter.fetch_add(1, Ordering::SeqCst); }));
+        // A burst of saves
+        for _ in 0..20 {
+            tx.send(()).unwrap();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        // A later, separate change triggers another update
+        tx.send(()).unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        drop(tx);
+        worker.join().unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_ignores_app_dir_and_access_events() {
+        let app_dir = Path::new("/lib/.quiet");
+        let modify = |p: &str| Event::new(EventKind::Modify(notify::event::ModifyKind::Any)).add_path(PathBuf::from(p));
+        assert!(is_relevant(&modify("/lib/notes.md"), app_dir));
+        assert!(!is_relevant(&modify("/lib/.quiet/index/meta.json"), app_dir));
+        let access = Event::new(EventKind::Access(notify::event::AccessKind::Any)).add_path(PathBuf::from("/lib/notes.md"));
+        assert!(!is_relevant(&access, app_dir));
+    }
+}
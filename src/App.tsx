@@ -26,7 +26,7 @@ export default function App() {
   const [clearing, setClearing] = useState(false)
 
   useEffect(() => {
-    listWatchedFolders().then(setFolders).catch(() => setFolders([]))
+    listWatchedFolders().then(fs => setFolders(fs.map(f => f.path))).catch(() => setFolders([]))
   }, [])
 
   useEffect(() => {
@@ -36,7 +36,7 @@ export default function App() {
       setSearching(true)
       try {
         const r = await search(debounced, 50)
-        if (!cancelled) setResults(r)
+        if (!cancelled) setResults(r.results)
       } finally {
         if (!cancelled) setSearching(false)
       }
@@ -112,7 +112,7 @@ export default function App() {
                     await reindexIncremental()
                     if (debounced.trim()) {
                       const r = await search(debounced, 50)
-                      setResults(r)
+                      setResults(r.results)
                     }
                   } finally {
                     setIndexing(false)
@@ -127,7 +127,7 @@ export default function App() {
                     await reindexAll()
                     if (debounced.trim()) {
                       const r = await search(debounced, 50)
-                      setResults(r)
+                      setResults(r.results)
                     }
                   } finally {
                     setIndexing(false)
@@ -9,7 +9,8 @@ export default function FolderManager({ folders, setFolders }: { folders: string
   const handleAddFolder = async () => {
     const picked = await openDialog({ directory: true, multiple: false });
     if (typeof picked === 'string') {
-      await addWatchedFolder(picked);
+      const warnings = await addWatchedFolder(picked);
+      if (warnings.length > 0) { alert(warnings.join('\n')); return; }
       setFolders([...folders, picked]);
     }
   };
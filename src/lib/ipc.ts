@@ -1,14 +1,54 @@
 import { invoke } from '@tauri-apps/api/tauri'
 // copyCitation temporarily disabled; keep function as no-op
 
+// Shape of every rejected invoke(); branch on `kind`, show `message`
+export type QuietErrorKind =
+  | 'index_missing'
+  | 'io'
+  | 'pdfium_unavailable'
+  | 'extraction'
+  | 'password_required'
+  | 'tantivy'
+  | 'not_found'
+  | 'invalid_input'
+  | 'other'
+export type QuietError = { kind: QuietErrorKind; message: string }
+
 export type SearchResult = {
   title: string
+  // A member of a ZIP archive is 'archive.zip!/inner/file.md' (member name also in section);
+  // openDocument, openTarget and revealInOs act on the archive
   path: string
   page?: number
+  // EPUB chapter, or the `#`/`##` Markdown heading the match falls under
   section?: string
+  // PDF text extractor for the page: 'pdfium', 'lopdf' or 'ocr'
+  extractor?: string
   snippet: string
   score: number
+  // Byte offsets of matches within snippet (UTF-8), present when highlight was requested
+  highlights?: [number, number][]
+  // Query word (or regex) occurrences within snippet, always present; 0 for filename-only matches
+  match_count: number
+  author?: string
+  // Document creation date, YYYY-MM-DD
+  created?: string
+  // Source file mtime, seconds since the epoch
+  modified?: number
+  // Source file size in bytes (whole file, also for PDF page results)
+  size?: number
+  // Only with groupByDocument: the document's best snippets, best first
+  snippets?: string[]
+  // Only with snippetParts: snippet split at each match, context stopping at the neighbouring ones
+  parts?: SnippetParts[]
 }
+export type SnippetParts = { before: string; matched: string; after: string }
+
+// Matching files per extension ('' for none), most common first; a PDF counts once
+export type FacetCount = { ext: string; count: number }
+// `total` counts matching documents (a PDF page, EPUB chapter or Markdown section each), not
+// snippets, ignoring minScore; regex and no-index searches stop at the limit so it's a lower bound
+export type SearchResponse = { results: SearchResult[]; total: number; facets?: FacetCount[] }
 
 export type Bookmark = {
   id: string
@@ -17,45 +57,310 @@ export type Bookmark = {
   section?: string
   note?: string
   createdAt: string
+  tags: string[]
+}
+export type TagCount = { tag: string; count: number }
+// `searchedAt` is an RFC 3339 UTC timestamp
+export type HistoryEntry = { query: string; searchedAt: string }
+
+export type OutlineEntry = {
+  title: string
+  level: number
+  line?: number
+  page?: number
+  children: OutlineEntry[]
+}
+
+// `source` is e.g. 'bundled:/path/libpdfium.so', 'env:…' or 'system'; `tried` lists searched dirs
+export type PdfiumStatus = { loaded: boolean; source?: string; tried: string[] }
+
+export type FieldWeights = { title: number; body: number; section: number }
+
+export type Settings = {
+  readRetry: { attempts: number; initialBackoffMs: number; maxBackoffMs: number }
+  fieldWeights: FieldWeights
+  // Stemming language for document text ('' disables); takes effect after a full reindex
+  stemLanguage: string
+  // Drop stopWords from document text at index time; also needs a reindex
+  removeStopWords: boolean
+  stopWords: string[]
+  // Bytes read per text file (default 2 MB); the rest of a longer file isn't indexed
+  maxTextBytes: number
+  // Extensions read in full regardless of maxTextBytes, e.g. ['md']
+  fullTextExts: string[]
+  // PDF pages indexed per document (default 300); search scans later pages directly
+  maxPdfPages: number
+  // IndexWriter memory in bytes (default 128 MB, clamped to 15 MB-2 GB): more makes full reindexes
+  // faster, less suits low-memory machines. Incremental updates use only what they need
+  writerHeapBytes: number
+  // Skip files whose mtime changed but whose content (first 64 KB + size) did not; off by default
+  contentHashFingerprints: boolean
+  // Extracted text a full reindex buffers ahead of the index writer (default 256 MB): less caps
+  // memory on huge libraries, more keeps extraction from pausing behind big files
+  extractBufferBytes: number
+  // PDFs extracted at once while indexing (default 2, 1-8); other files use every indexing thread
+  pdfThreads: number
 }
 
+export type IndexReport = {
+  errors: { path: string; error: string; attempts: number }[]
+  // PDFs longer than maxPdfPages; pages past lastIndexedPage aren't in the index. totalPages
+  // counts blank pages too
+  truncated: { path: string; lastIndexedPage: number; totalPages: number }[]
+  // Watched folders that could not be read; their documents were kept from the previous index.
+  // Also sent as the 'folders-unreachable' event after each index run that hits one.
+  unreachable: string[]
+}
+
+// exists/readable are false for e.g. an unplugged drive; fileCount is how many of its files
+// the index holds
+export type WatchedFolder = { path: string; exists: boolean; readable: boolean; fileCount: number }
+
+// Resolves to warnings when the folder overlaps a watched folder, in which case it is not added
 export async function addWatchedFolder(path: string) {
-  return invoke<void>('add_watched_folder', { path })
+  return invoke<string[]>('add_watched_folder', { path })
+}
+// Configuration problems such as nested or duplicate watched folders; [] when none
+export async function validateLibrary() {
+  return invoke<string[]>('validate_library')
 }
+// Resolves to the paths actually added, in their canonical form: already-watched paths and any inside (or containing)
+// a watched folder are skipped
+export async function addWatchedFolders(paths: string[]) {
+  return invoke<string[]>('add_watched_folders', { paths })
+}
+// Folders are stored canonicalized, so these may differ from the paths passed to add
 export async function listWatchedFolders() {
-  return invoke<string[]>('list_watched_folders')
+  return invoke<WatchedFolder[]>('list_watched_folders')
 }
 export async function removeWatchedFolder(path: string) {
   return invoke<void>('remove_watched_folder', { path })
 }
+// Globs like '**/node_modules/**' or '*.tmp', matched against paths relative to each watched folder
+// (a .quietignore file in any folder adds its own, one per line, relative to that folder)
+export async function addExcludePattern(pattern: string) {
+  return invoke<void>('add_exclude_pattern', { pattern })
+}
+export async function listExcludePatterns() {
+  return invoke<string[]>('list_exclude_patterns')
+}
+export async function removeExcludePattern(pattern: string) {
+  return invoke<void>('remove_exclude_pattern', { pattern })
+}
+// Deepest subfolder level scanned below each watched folder (0 = top level only); null = unlimited
+export async function getMaxDepth() {
+  return invoke<number | null>('get_max_depth')
+}
+export async function setMaxDepth(depth: number | null) {
+  return invoke<void>('set_max_depth', { depth })
+}
+// When false, symlinked files and folders are skipped; symlink cycles are never followed
+export async function getFollowSymlinks() {
+  return invoke<boolean>('get_follow_symlinks')
+}
+export async function setFollowSymlinks(follow: boolean) {
+  return invoke<void>('set_follow_symlinks', { follow })
+}
+// Extensions indexed as plain text besides txt/md/html/docx/odt/rtf (e.g. ['rs', 'py', 'csv']); reindex to apply
+export async function getExtraTextExts() {
+  return invoke<string[]>('get_extra_text_exts')
+}
+export async function setExtraTextExts(exts: string[]) {
+  return invoke<void>('set_extra_text_exts', { exts })
+}
+// While running, emits 'reindex-progress' events (at most ~10/sec) with the last finished file
+export type ReindexProgress = { done: number; total: number; file: string }
 export async function reindexAll() {
   return invoke<void>('reindex_all')
 }
+// Also the payload of 'index-updated', emitted after watched-folder changes are reindexed
+// renamed: files moved or renamed unchanged, re-filed under the new path without re-extracting
+export type IndexUpdateStats = { added: number; changed: number; deleted: number; renamed: number }
 export async function reindexIncremental() {
-  return invoke<void>('index_incremental')
+  return invoke<IndexUpdateStats>('reindex_incremental')
+}
+export async function removeFromIndex(path: string) {
+  return invoke<void>('remove_from_index', { path })
 }
 export async function clearExtractCache() {
   return invoke<void>('clear_extract_cache')
 }
-export async function search(query: string, limit: number) {
-  return invoke<SearchResult[]>('search', { query, limit })
+// Re-extract just this file next time; resolves to the number of cache entries removed
+export async function clearExtractCacheFor(path: string) {
+  return invoke<number>('clear_extract_cache_for', { path })
+}
+export async function getIndexReport() {
+  return invoke<IndexReport>('get_index_report')
+}
+
+// ext is lowercase, '' for files without one; supported is false when no extractor reads it
+export type ExtensionStats = { ext: string; count: number; bytes: number; supported: boolean }
+export type IndexPreview = {
+  totalFiles: number
+  totalBytes: number
+  byExtension: ExtensionStats[]
+  unsupported: number
+  unsupportedExts: string[]
+  unreachable: string[]
+}
+// What a reindex would read, by walking the watched folders only (no extraction, no writes)
+export async function previewIndex() {
+  return invoke<IndexPreview>('preview_index')
+}
+export async function getSettings() {
+  return invoke<Settings>('get_settings')
+}
+export async function updateSettings(settings: Settings) {
+  return invoke<void>('update_settings', { settings })
+}
+export async function getFieldWeights() {
+  return invoke<FieldWeights>('get_field_weights')
+}
+export async function setFieldWeights(weights: FieldWeights) {
+  return invoke<void>('set_field_weights', { weights })
+}
+// folder: restrict to one watched folder (as returned by listWatchedFolders)
+// titleAsc/pathAsc sort up to 1000 matches alphabetically before applying the limit
+export type SortMode = 'relevance' | 'newest' | 'oldest' | 'titleAsc' | 'pathAsc'
+export type SearchField = 'title' | 'body' | 'section'
+export type SearchOptions = {
+  limit?: number
+  folder?: string
+  highlight?: boolean
+  snippetLen?: number
+  // Snippets kept per file, page or section, most query terms first (default 3)
+  maxSnippetsPerDoc?: number
+  sort?: SortMode
+  minSize?: number
+  maxSize?: number
+  // One result per document with its top snippets in `snippets` (search only, not searchStream)
+  groupByDocument?: boolean
+  // Drop weaker results. Indexed scores are BM25, typically 0.5-20; without an index text
+  // matches score 1.0, PDF pages 1.1 and filename-only matches 0.05
+  minScore?: number
+  // Query is a case-insensitive regular expression over document text, e.g. 'RFC\\s?\\d{4}'.
+  // Needs the index; a bad pattern rejects with kind 'invalid_input'
+  regex?: boolean
+  // File mtime window in Unix seconds: modifiedAfter inclusive, modifiedBefore exclusive, either
+  // optional. After > before rejects with kind 'invalid_input'
+  modifiedAfter?: number
+  modifiedBefore?: number
+  // Also return each snippet split at its matches in `parts` (search only, not searchStream)
+  snippetParts?: boolean
+  // Fields unscoped words are matched in (default all); an empty list rejects with kind
+  // 'invalid_input'. Without an index, leaving out 'title' skips file-name matches
+  fields?: SearchField[]
+  // Match words with their case as written ('Error' but not 'error'), in bodies and sections.
+  // Needs the index (rebuilt since this option exists); ignored with regex
+  caseSensitive?: boolean
+}
+// Resolves to no results when a newer search() or searchStream() started before this one finished
+export async function search(query: string, limit: number, options?: SearchOptions) {
+  return invoke<SearchResponse>('search', { query, limit, options })
+}
+// Like search(), plus file-type counts over every match (not just the top `limit`)
+export async function searchFacets(query: string, limit: number, options?: SearchOptions) {
+  return invoke<SearchResponse>('search_facets', { query, limit, options })
+}
+// Results arrive as 'search-result' events ({ query, generation, results }) followed by
+// 'search-complete' ({ query, generation, total, cancelled }); a newer stream cancels older ones.
+// `limit` overrides options.limit, as in search()
+export async function searchStream(query: string, options?: SearchOptions, limit?: number) {
+  return invoke<void>('search_stream', { query, limit, options })
+}
+// Queries passed to search(), most recent first (up to 100)
+export async function getSearchHistory() {
+  return invoke<HistoryEntry[]>('get_search_history')
+}
+export async function clearSearchHistory() {
+  return invoke<void>('clear_search_history')
+}
+// Index terms completing the last word of `prefix`, most common first
+export async function suggest(prefix: string, limit: number) {
+  return invoke<string[]>('suggest', { prefix, limit })
+}
+// "Did you mean" for a query with no results; null when no close indexed words exist
+export async function suggestCorrection(query: string) {
+  return invoke<string | null>('suggest_correction', { query })
 }
 export async function searchDocumentPages(path: string, query: string, limit: number) {
   return invoke<number[]>('search_document_pages', { path, query, limit })
 }
+// score is BM25 from the index (or the occurrence count without one); best page first
+export type PageHit = { page: number; score: number; snippet: string }
+export async function searchDocumentPageHits(path: string, query: string, limit: number) {
+  return invoke<PageHit[]>('search_document_page_hits', { path, query, limit })
+}
+// Full extracted text of a text file or PDF (pages separated by '\n\f\n'), up to the byte /
+// page limits; `truncated` when they cut it short. Other formats reject with 'invalid_input'
+export type DocumentText = { title: string; text: string; truncated: boolean }
+export async function getDocumentText(path: string) {
+  return invoke<DocumentText>('get_document_text', { path })
+}
+// Text of one PDF page (1-based); rejects with 'invalid_input' for a page out of range or a non-PDF
+export async function getPdfPageText(path: string, page: number) {
+  return invoke<string>('get_pdf_page_text', { path, page })
+}
+export async function documentOutline(path: string) {
+  return invoke<OutlineEntry[]>('document_outline', { path })
+}
+export async function pdfiumStatus() {
+  return invoke<PdfiumStatus>('pdfium_status')
+}
+// In memory only; reindex afterwards to pick up the PDF. An empty password forgets it.
+export async function setPdfPassword(path: string, password: string) {
+  return invoke<void>('set_pdf_password', { path, password })
+}
 export async function resolveOpenTarget(path: string, page?: number, section?: string) {
   return invoke<{ url: string; path: string; page?: number; section?: string }>('resolve_open_target', { path, page, section })
 }
-export async function addBookmark(path: string, page?: number, section?: string, note?: string) {
-  return invoke<void>('add_bookmark', { path, page, section, note })
+export async function addBookmark(path: string, page?: number, section?: string, note?: string, tags?: string[]) {
+  return invoke<void>('add_bookmark', { path, page, section, note, tags })
+}
+export async function listBookmarks(path?: string, tag?: string) {
+  return invoke<Bookmark[]>('list_bookmarks', { path, tag })
+}
+// Case-insensitive match on note, section and path; note matches come first
+export async function searchBookmarks(query: string) {
+  return invoke<Bookmark[]>('search_bookmarks', { query })
 }
-export async function listBookmarks(path?: string) {
-  return invoke<Bookmark[]>('list_bookmarks', { path })
+export async function listAllTags() {
+  return invoke<TagCount[]>('list_all_tags')
 }
 export async function removeBookmark(id: string) {
   return invoke<void>('remove_bookmark', { id })
 }
-export async function revealInOS(_path: string) { /* no-op */ }
+// Omitted fields keep their current value
+export async function updateBookmark(id: string, note?: string, page?: number, section?: string) {
+  return invoke<void>('update_bookmark', { id, note, page, section })
+}
+// Rejects when no file manager could be opened. On Linux, QUIETLIBRARY_FILE_MANAGER
+// (e.g. "nautilus --select") is tried first.
+export async function revealInOS(path: string) {
+  return invoke<void>('reveal_in_os', { path })
+}
+// Opens in the system viewer, jumping to `page` where the viewer supports it. Records the
+// document as recently opened
+export async function openDocument(path: string, page?: number) {
+  return invoke<{ url: string; path: string; page?: number; section?: string }>('open_document', { path, page })
+}
+// `path` is the file on disk; `url` (file://, with #page=N for a PDF page) is filled in by the
+// backend and may be omitted
+export type OpenTarget = { url?: string; path: string; page?: number; section?: string }
+// PDFs open in the system viewer (at `page` if set); other files are revealed in the file
+// manager. Records the document as recently opened. Rejects with kind 'not_found' for a missing file
+export async function openTarget(target: OpenTarget) {
+  return invoke<OpenTarget & { url: string }>('open_target', { target })
+}
+// Documents opened with openDocument/openTarget, newest first, one entry per path (up to 50).
+// `title` comes from the index, else the file name; `openedAt` is RFC 3339 UTC
+export type RecentDocument = { path: string; title: string; openedAt: string }
+export async function getRecentDocuments() {
+  return invoke<RecentDocument[]>('get_recent_documents')
+}
+export async function clearRecentDocuments() {
+  return invoke<void>('clear_recent_documents')
+}
 export async function openExternal(path: string, page?: number, section?: string) {
   return invoke<void>('open_external', { path, page, section })
 }